@@ -0,0 +1,246 @@
+#ifndef PACKED_ENCODER_H
+#define PACKED_ENCODER_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+// The call succeeded.
+#define PE_OK 0
+
+// A required pointer argument is null.
+#define PE_ERROR_NULL -1
+
+// The byte order is neither `PE_ORDER_BIG` nor `PE_ORDER_LITTLE`.
+#define PE_ERROR_ORDER -2
+
+// A string argument is not valid UTF-8.
+#define PE_ERROR_UTF8 -3
+
+// The values could not be encoded.
+#define PE_ERROR_ENCODE -4
+
+// The output buffer is too small, the required size is written to `written`.
+#define PE_ERROR_BUFFER_TOO_SMALL -5
+
+// The schema text could not be parsed.
+#define PE_ERROR_SCHEMA -6
+
+// The input could not be decoded with the schema.
+#define PE_ERROR_DECODE -7
+
+// The index is past the last decoded value.
+#define PE_ERROR_INDEX -8
+
+// The decoded value does not have the requested type.
+#define PE_ERROR_TYPE -9
+
+// Multi-byte values are big endian.
+#define PE_ORDER_BIG 0
+
+// Multi-byte values are little endian.
+#define PE_ORDER_LITTLE 1
+
+// `PeEncoder` holds the list of values to encode.
+typedef struct PeEncoder PeEncoder;
+
+// `PeRecord` holds the values decoded with a schema.
+typedef struct PeRecord PeRecord;
+
+// `PeSchema` is a schema parsed from the schema text language.
+typedef struct PeSchema PeSchema;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Returns a static, null-terminated description of a `PE_*` code.
+const char *pe_error_message(int32_t code);
+
+// Creates an empty encoder, it must be released with `pe_encoder_free`.
+PeEncoder *pe_encoder_new(void);
+
+// Releases an encoder.
+//
+// # Safety
+// `encoder` must be null or come from `pe_encoder_new`, and must not be used afterwards.
+void pe_encoder_free(PeEncoder *encoder);
+
+// Removes the pushed values so the encoder can be reused.
+//
+// # Safety
+// `encoder` must be null or a live encoder.
+int32_t pe_encoder_clear(PeEncoder *encoder);
+
+// Appends an `i8` value.
+//
+// # Safety
+// `encoder` must be null or a live encoder.
+int32_t pe_encoder_push_i8(PeEncoder *encoder, int8_t value);
+
+// Appends an `i16` value.
+//
+// # Safety
+// `encoder` must be null or a live encoder.
+int32_t pe_encoder_push_i16(PeEncoder *encoder, int16_t value);
+
+// Appends an `i32` value.
+//
+// # Safety
+// `encoder` must be null or a live encoder.
+int32_t pe_encoder_push_i32(PeEncoder *encoder, int32_t value);
+
+// Appends an `i64` value.
+//
+// # Safety
+// `encoder` must be null or a live encoder.
+int32_t pe_encoder_push_i64(PeEncoder *encoder, int64_t value);
+
+// Appends a `u8` value.
+//
+// # Safety
+// `encoder` must be null or a live encoder.
+int32_t pe_encoder_push_u8(PeEncoder *encoder, uint8_t value);
+
+// Appends a `u16` value.
+//
+// # Safety
+// `encoder` must be null or a live encoder.
+int32_t pe_encoder_push_u16(PeEncoder *encoder, uint16_t value);
+
+// Appends a `u32` value.
+//
+// # Safety
+// `encoder` must be null or a live encoder.
+int32_t pe_encoder_push_u32(PeEncoder *encoder, uint32_t value);
+
+// Appends a `u64` value.
+//
+// # Safety
+// `encoder` must be null or a live encoder.
+int32_t pe_encoder_push_u64(PeEncoder *encoder, uint64_t value);
+
+// Appends a `f32` value.
+//
+// # Safety
+// `encoder` must be null or a live encoder.
+int32_t pe_encoder_push_f32(PeEncoder *encoder, float value);
+
+// Appends a `f64` value.
+//
+// # Safety
+// `encoder` must be null or a live encoder.
+int32_t pe_encoder_push_f64(PeEncoder *encoder, double value);
+
+// Appends a `bool` value.
+//
+// # Safety
+// `encoder` must be null or a live encoder.
+int32_t pe_encoder_push_bool(PeEncoder *encoder, bool value);
+
+// Appends a UTF-8 string of `len` bytes, the string is not null-terminated in the output.
+//
+// # Safety
+// `encoder` must be null or a live encoder, `data` must point to `len` readable bytes.
+int32_t pe_encoder_push_str(PeEncoder *encoder, const uint8_t *data, size_t len);
+
+// Appends `len` raw bytes.
+//
+// # Safety
+// `encoder` must be null or a live encoder, `data` must point to `len` readable bytes.
+int32_t pe_encoder_push_bytes(PeEncoder *encoder, const uint8_t *data, size_t len);
+
+// Encodes the pushed values with `encode_packed` into `output`. The encoded size is written to `written`, when
+// `capacity` is too small `PE_ERROR_BUFFER_TOO_SMALL` is returned and `written` holds the required size.
+//
+// # Safety
+// `encoder` must be null or a live encoder, `output` must point to `capacity` writable bytes and `written` must be
+// null or writable.
+int32_t pe_encoder_encode(const PeEncoder *encoder,
+                          int32_t order,
+                          uint8_t *output,
+                          size_t capacity,
+                          size_t *written);
+
+// Parses a schema written in the schema text language, the schema is stored in `schema` and must be released with
+// `pe_schema_free`.
+//
+// # Safety
+// `text` must point to `len` readable bytes and `schema` must be writable.
+int32_t pe_schema_parse(const uint8_t *text,
+                        size_t len,
+                        PeSchema **schema);
+
+// Releases a schema.
+//
+// # Safety
+// `schema` must be null or come from `pe_schema_parse`, and must not be used afterwards.
+void pe_schema_free(PeSchema *schema);
+
+// Decodes `len` bytes with the schema, the record is stored in `record` and must be released with
+// `pe_record_free`.
+//
+// # Safety
+// `schema` must be null or a live schema, `data` must point to `len` readable bytes and `record` must be writable.
+int32_t pe_decode(const PeSchema *schema,
+                  const uint8_t *data,
+                  size_t len,
+                  int32_t order,
+                  PeRecord **record);
+
+// Releases a record, the pointers returned by `pe_record_get_str` and `pe_record_get_bytes` become invalid.
+//
+// # Safety
+// `record` must be null or come from `pe_decode`, and must not be used afterwards.
+void pe_record_free(PeRecord *record);
+
+// Returns the number of decoded values, 0 for a null record.
+//
+// # Safety
+// `record` must be null or a live record.
+size_t pe_record_len(const PeRecord *record);
+
+// Reads a signed integer, or an unsigned integer up to 32 bits.
+//
+// # Safety
+// `record` must be null or a live record and `value` must be null or writable.
+int32_t pe_record_get_i64(const PeRecord *record, size_t index, int64_t *value);
+
+// Reads an unsigned integer.
+//
+// # Safety
+// `record` must be null or a live record and `value` must be null or writable.
+int32_t pe_record_get_u64(const PeRecord *record, size_t index, uint64_t *value);
+
+// Reads a `f32` or `f64` value.
+//
+// # Safety
+// `record` must be null or a live record and `value` must be null or writable.
+int32_t pe_record_get_f64(const PeRecord *record, size_t index, double *value);
+
+// Reads a boolean.
+//
+// # Safety
+// `record` must be null or a live record and `value` must be null or writable.
+int32_t pe_record_get_bool(const PeRecord *record, size_t index, bool *value);
+
+// Reads a string, `data` points into the record and is not null-terminated.
+//
+// # Safety
+// `record` must be null or a live record, `data` and `len` must be null or writable.
+int32_t pe_record_get_str(const PeRecord *record, size_t index, const uint8_t **data, size_t *len);
+
+// Reads bytes, `data` points into the record.
+//
+// # Safety
+// `record` must be null or a live record, `data` and `len` must be null or writable.
+int32_t pe_record_get_bytes(const PeRecord *record,
+                            size_t index,
+                            const uint8_t **data,
+                            size_t *len);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* PACKED_ENCODER_H */
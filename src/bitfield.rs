@@ -0,0 +1,173 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// `BitOrder` is used to specify how the fields of a bit-group are laid out inside the bytes they occupy.
+/// Example: `BitOrder::Msb` places the first field in the most significant bits of the first byte.
+pub enum BitOrder {
+    /// Msb fills every byte starting from its most significant bit, fields are written most significant bit first
+    Msb,
+    /// Lsb fills every byte starting from its least significant bit, fields are written least significant bit first
+    Lsb,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// `BitField` declares the width (1 to 64 bits) of a field inside a bit-group that needs to be decoded.
+/// Example: `BitField::Signed(5)` tells the decoder to read the next 5 bits as a sign-extended integer.
+pub enum BitField {
+    /// Unsigned(width) reads `width` bits as an unsigned integer
+    Unsigned(u8),
+    /// Signed(width) reads `width` bits as a two's complement integer and sign-extends it
+    Signed(u8),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// `BitValue` holds the width and the value of a field inside a bit-group.
+/// Example: `BitValue::Unsigned(3, 5)` is the value `5` stored in 3 bits.
+pub enum BitValue {
+    /// Unsigned(width, value) is an unsigned integer stored in `width` bits
+    Unsigned(u8, u64),
+    /// Signed(width, value) is a two's complement integer stored in `width` bits
+    Signed(u8, i64),
+}
+
+impl BitField {
+    /// returns the width of the field in bits
+    pub fn width(&self) -> u8 {
+        match self {
+            BitField::Unsigned(width) | BitField::Signed(width) => *width,
+        }
+    }
+}
+
+impl BitValue {
+    /// returns the width of the field in bits
+    pub fn width(&self) -> u8 {
+        match self {
+            BitValue::Unsigned(width, _) | BitValue::Signed(width, _) => *width,
+        }
+    }
+
+    /// returns the raw bits of the value if it can be stored in the given width
+    fn to_raw(self) -> Option<u64> {
+        match self {
+            BitValue::Unsigned(width, value) => {
+                if !(1..=64).contains(&width) || (width < 64 && value >> width != 0) {
+                    return None;
+                }
+                Some(value)
+            }
+            BitValue::Signed(width, value) => {
+                if !(1..=64).contains(&width) {
+                    return None;
+                }
+                if width < 64 {
+                    let limit = 1i64 << (width - 1);
+                    if value < -limit || value >= limit {
+                        return None;
+                    }
+                }
+                Some(value as u64 & mask(width))
+            }
+        }
+    }
+}
+
+#[inline]
+fn mask(width: u8) -> u64 {
+    if width >= 64 {
+        u64::MAX
+    } else {
+        (1u64 << width) - 1
+    }
+}
+
+/// returns the number of bytes occupied by a bit-group whose fields add up to `bits` bits.
+#[inline]
+pub(crate) fn group_size(bits: usize) -> usize {
+    bits.div_ceil(8)
+}
+
+#[inline]
+fn write_bit(array: &mut [u8], position: usize, bit: bool, order: BitOrder) {
+    if bit {
+        let shift = match order {
+            BitOrder::Msb => 7 - (position % 8),
+            BitOrder::Lsb => position % 8,
+        };
+        array[position / 8] |= 1 << shift;
+    }
+}
+
+#[inline]
+fn read_bit(array: &[u8], position: usize, order: BitOrder) -> bool {
+    let shift = match order {
+        BitOrder::Msb => 7 - (position % 8),
+        BitOrder::Lsb => position % 8,
+    };
+    (array[position / 8] >> shift) & 1 == 1
+}
+
+/// packs the given fields into a byte-aligned group, the unused bits of the last byte are set to zero.
+/// Returns the field that cannot be stored in its declared width as error.
+pub(crate) fn pack_bits(fields: &[BitValue], order: BitOrder) -> Result<Vec<u8>, BitValue> {
+    let total: usize = fields.iter().map(|field| field.width() as usize).sum();
+    let mut array = vec![0; group_size(total)];
+    let mut position = 0;
+
+    for field in fields {
+        let raw = field.to_raw().ok_or(*field)?;
+        let width = field.width() as usize;
+        for bit in 0..width {
+            let value_bit = match order {
+                BitOrder::Msb => (raw >> (width - 1 - bit)) & 1 == 1,
+                BitOrder::Lsb => (raw >> bit) & 1 == 1,
+            };
+            write_bit(&mut array, position, value_bit, order);
+            position += 1;
+        }
+    }
+
+    Ok(array)
+}
+
+/// unpacks the given fields from a byte-aligned group, `array` must hold at least `group_size` bytes.
+/// Returns `None` if any of the fields has an invalid width.
+pub(crate) fn unpack_bits(
+    fields: &[BitField],
+    array: &[u8],
+    order: BitOrder,
+) -> Option<Vec<BitValue>> {
+    let mut values = Vec::with_capacity(fields.len());
+    let mut position = 0;
+
+    for field in fields {
+        let width = field.width();
+        if !(1..=64).contains(&width) {
+            return None;
+        }
+
+        let mut raw = 0u64;
+        for bit in 0..width as usize {
+            if read_bit(array, position, order) {
+                raw |= match order {
+                    BitOrder::Msb => 1 << (width as usize - 1 - bit),
+                    BitOrder::Lsb => 1 << bit,
+                };
+            }
+            position += 1;
+        }
+
+        values.push(match field {
+            BitField::Unsigned(_) => BitValue::Unsigned(width, raw),
+            BitField::Signed(_) => {
+                let sign = 1u64 << (width - 1);
+                let extended = if raw & sign != 0 {
+                    raw | !mask(width)
+                } else {
+                    raw
+                };
+                BitValue::Signed(width, extended as i64)
+            }
+        });
+    }
+
+    Some(values)
+}
@@ -0,0 +1,152 @@
+//! `buf` connects the encoder and the decoder to the `bytes` crate, it is enabled by the `bytes` feature. Values are
+//! encoded into any `BufMut` and decoded from any `Buf`, including chained buffers whose bytes are not contiguous.
+//! The `Bytes` and `BytesRef` elements of the decoded list are returned as `Bytes` slices sharing the memory of the
+//! input instead of copies.
+
+use crate::decoder::{self, DecodeError, DecodeOrder, DecodeType, DecodedData, DecodedList};
+use crate::encoder::{self, EncodeError, EncodeOrder, EncodeType};
+use bytes::{Buf, BufMut, Bytes};
+use std::io::IoSlice;
+
+#[derive(Debug, Clone, PartialEq)]
+/// `BufValue` is a value decoded from a `Buf`, the `Bytes` and `BytesRef` elements are `BufValue::Bytes` slices of
+/// the input and the other elements are `BufValue::Data`.
+pub enum BufValue {
+    Data(DecodedData),
+    Bytes(Bytes),
+}
+
+impl BufValue {
+    /// returns the value as `DecodedData`, `Bytes` slices are copied into `DecodedData::Bytes`
+    pub fn into_data(self) -> DecodedData {
+        match self {
+            BufValue::Data(data) => data,
+            BufValue::Bytes(bytes) => DecodedData::Bytes(bytes.to_vec()),
+        }
+    }
+
+    /// returns the slice of a `BufValue::Bytes` value
+    pub fn as_bytes(&self) -> Option<&Bytes> {
+        match self {
+            BufValue::Bytes(bytes) => Some(bytes),
+            BufValue::Data(_) => None,
+        }
+    }
+}
+
+/// `encode_into` encodes the values like `encode_packed` and appends them to the buffer. The buffer is left untouched
+/// when the values cannot be encoded or when it does not have room for them, the latter returns
+/// `EncodeError::BufferFull` with the encoded size.
+pub fn encode_into<B: BufMut>(
+    values: &[EncodeType],
+    buffer: &mut B,
+    encode_order: EncodeOrder,
+) -> Result<(), EncodeError> {
+    let encoded = encoder::encode_packed(values, encode_order)?;
+    if buffer.remaining_mut() < encoded.len() {
+        return Err(EncodeError::BufferFull(encoded.len()));
+    }
+    buffer.put_slice(&encoded);
+    Ok(())
+}
+
+/// `encode_to_bytes` encodes the values like `encode_packed` and returns them as `Bytes` without copying them.
+pub fn encode_to_bytes(
+    values: &[EncodeType],
+    encode_order: EncodeOrder,
+) -> Result<Bytes, EncodeError> {
+    encoder::encode_packed(values, encode_order).map(Bytes::from)
+}
+
+/// `decode_bytes` decodes the given types from the start of the input like `decode_packed`, `Bytes` elements are
+/// slices of the input.
+///
+/// # Examples
+/// ```rust
+/// use bytes::Bytes;
+/// use packed_encoder::buf::{self, BufValue};
+/// use packed_encoder::decoder::{DecodeOrder, DecodeType, DecodedData};
+///
+/// let input = Bytes::from_static(&[2, 0xca, 0xfe]);
+/// let values = buf::decode_bytes(&[DecodeType::Uint8, DecodeType::Bytes(2)], &input, DecodeOrder::Big).unwrap();
+/// assert_eq!(values[0], BufValue::Data(DecodedData::Uint8(2)));
+/// assert_eq!(values[1], BufValue::Bytes(input.slice(1..3)));
+/// ```
+pub fn decode_bytes(
+    types: &[DecodeType],
+    input: &Bytes,
+    decode_order: DecodeOrder,
+) -> Result<Vec<BufValue>, DecodeError> {
+    let list = decoder::decode_list(types, input, 0, decode_order).0?;
+    Ok(share(types, list, input))
+}
+
+/// `decode_buf` decodes the given types from the buffer and advances it past the decoded bytes, so that the next
+/// record can be decoded from it. When the remaining bytes are contiguous, like in `Bytes` and `BytesMut`, the `Bytes`
+/// elements are slices of the buffer, otherwise the bytes of the record are gathered in a single copy first.
+///
+/// The buffer is left untouched when decoding fails, `DecodeError::IndexOutOfBounds` tells that the buffer does not
+/// hold a whole record yet. Non-contiguous buffers are read through `chunks_vectored`, buffers that only expose their
+/// first chunk there return `DecodeError::NonContiguous` for records that extend past it, their bytes need to be
+/// copied into a contiguous buffer first.
+pub fn decode_buf<B: Buf>(
+    types: &[DecodeType],
+    buffer: &mut B,
+    decode_order: DecodeOrder,
+) -> Result<Vec<BufValue>, DecodeError> {
+    if buffer.chunk().len() == buffer.remaining() {
+        let (result, consumed) = decoder::decode_list(types, buffer.chunk(), 0, decode_order);
+        let list = result?;
+        // `copy_to_bytes` shares the memory of `Bytes` and `BytesMut` buffers
+        let input = buffer.copy_to_bytes(consumed);
+        return Ok(share(types, list, &input));
+    }
+
+    let (gathered, complete) = gather(buffer);
+    let input = Bytes::from(gathered);
+    let (result, consumed) = decoder::decode_list(types, &input, 0, decode_order);
+    let list = match result {
+        Err(DecodeError::IndexOutOfBounds) if !complete => return Err(DecodeError::NonContiguous),
+        result => result?,
+    };
+    buffer.advance(consumed);
+    Ok(share(types, list, &input))
+}
+
+/// copies the remaining bytes of a non-contiguous buffer without consuming them, along with whether all of them
+/// could be copied
+fn gather<B: Buf>(buffer: &B) -> (Vec<u8>, bool) {
+    let mut slots = 16;
+    loop {
+        let mut slices = vec![IoSlice::new(&[]); slots];
+        let count = buffer.chunks_vectored(&mut slices);
+        let size: usize = slices[..count].iter().map(|slice| slice.len()).sum();
+        // buffers that do not implement `chunks_vectored` only fill the first slot
+        if size == buffer.remaining() || count < slots {
+            let mut gathered = Vec::with_capacity(size);
+            for slice in &slices[..count] {
+                gathered.extend_from_slice(slice);
+            }
+            return (gathered, size == buffer.remaining());
+        }
+        slots *= 2;
+    }
+}
+
+/// turns the decoded values into `BufValue`s, the `Bytes` elements become slices of the input the list was decoded
+/// from
+fn share(types: &[DecodeType], list: DecodedList, input: &Bytes) -> Vec<BufValue> {
+    let mut values: Vec<BufValue> = list.values.into_iter().map(BufValue::Data).collect();
+    for (idx, kind) in types.iter().enumerate() {
+        if !matches!(kind, DecodeType::Bytes(_) | DecodeType::BytesRef(_)) {
+            continue;
+        }
+        if let Some(position) = list.positions[idx] {
+            if let BufValue::Data(DecodedData::Bytes(content)) = &values[position] {
+                let start = list.offsets[idx];
+                values[position] = BufValue::Bytes(input.slice(start..start + content.len()));
+            }
+        }
+    }
+    values
+}
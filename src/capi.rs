@@ -0,0 +1,529 @@
+//! `capi` exposes the encoder and the schema decoder to C and C++ through `extern "C"` functions, it is enabled by
+//! the `capi` feature. The matching header is `include/packed_encoder.h`, it can be regenerated with
+//! `cbindgen --config cbindgen.toml --output include/packed_encoder.h`.
+//!
+//! Values are encoded by pushing them on a `PeEncoder` and writing them into a buffer owned by the caller. Decoding
+//! uses a `PeSchema` parsed from the schema text language and produces a `PeRecord` whose values are read by index.
+//! Every fallible function returns `PE_OK` or one of the negative `PE_ERROR_*` codes.
+
+use crate::decoder::{DecodeOrder, DecodedData};
+use crate::encoder::{self, EncodeOrder, EncodeType};
+use crate::schema::{DecodedRecord, Schema};
+use crate::text;
+use std::os::raw::c_char;
+use std::ptr;
+use std::slice;
+
+/// The call succeeded.
+pub const PE_OK: i32 = 0;
+/// A required pointer argument is null.
+pub const PE_ERROR_NULL: i32 = -1;
+/// The byte order is neither `PE_ORDER_BIG` nor `PE_ORDER_LITTLE`.
+pub const PE_ERROR_ORDER: i32 = -2;
+/// A string argument is not valid UTF-8.
+pub const PE_ERROR_UTF8: i32 = -3;
+/// The values could not be encoded.
+pub const PE_ERROR_ENCODE: i32 = -4;
+/// The output buffer is too small, the required size is written to `written`.
+pub const PE_ERROR_BUFFER_TOO_SMALL: i32 = -5;
+/// The schema text could not be parsed.
+pub const PE_ERROR_SCHEMA: i32 = -6;
+/// The input could not be decoded with the schema.
+pub const PE_ERROR_DECODE: i32 = -7;
+/// The index is past the last decoded value.
+pub const PE_ERROR_INDEX: i32 = -8;
+/// The decoded value does not have the requested type.
+pub const PE_ERROR_TYPE: i32 = -9;
+
+/// Multi-byte values are big endian.
+pub const PE_ORDER_BIG: i32 = 0;
+/// Multi-byte values are little endian.
+pub const PE_ORDER_LITTLE: i32 = 1;
+
+/// `PeEncoder` holds the list of values to encode.
+pub struct PeEncoder {
+    values: Vec<EncodeType>,
+}
+
+/// `PeSchema` is a schema parsed from the schema text language.
+pub struct PeSchema {
+    schema: Schema,
+}
+
+/// `PeRecord` holds the values decoded with a schema.
+pub struct PeRecord {
+    record: DecodedRecord,
+}
+
+fn decode_order(order: i32) -> Option<DecodeOrder> {
+    match order {
+        PE_ORDER_BIG => Some(DecodeOrder::Big),
+        PE_ORDER_LITTLE => Some(DecodeOrder::Little),
+        _ => None,
+    }
+}
+
+/// returns the `len` bytes at `data`, an empty slice when `len` is 0 even if `data` is null
+unsafe fn input<'a>(data: *const u8, len: usize) -> Option<&'a [u8]> {
+    if len == 0 {
+        Some(&[])
+    } else if data.is_null() {
+        None
+    } else {
+        Some(slice::from_raw_parts(data, len))
+    }
+}
+
+/// Returns a static, null-terminated description of a `PE_*` code.
+#[no_mangle]
+pub extern "C" fn pe_error_message(code: i32) -> *const c_char {
+    let message: &'static [u8] = match code {
+        PE_OK => b"no error\0",
+        PE_ERROR_NULL => b"null pointer argument\0",
+        PE_ERROR_ORDER => b"invalid byte order\0",
+        PE_ERROR_UTF8 => b"string is not valid UTF-8\0",
+        PE_ERROR_ENCODE => b"values cannot be encoded\0",
+        PE_ERROR_BUFFER_TOO_SMALL => b"output buffer is too small\0",
+        PE_ERROR_SCHEMA => b"invalid schema text\0",
+        PE_ERROR_DECODE => b"input cannot be decoded\0",
+        PE_ERROR_INDEX => b"index out of range\0",
+        PE_ERROR_TYPE => b"value has another type\0",
+        _ => b"unknown error\0",
+    };
+    message.as_ptr() as *const c_char
+}
+
+/// Creates an empty encoder, it must be released with `pe_encoder_free`.
+#[no_mangle]
+pub extern "C" fn pe_encoder_new() -> *mut PeEncoder {
+    Box::into_raw(Box::new(PeEncoder { values: vec![] }))
+}
+
+/// Releases an encoder.
+///
+/// # Safety
+/// `encoder` must be null or come from `pe_encoder_new`, and must not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn pe_encoder_free(encoder: *mut PeEncoder) {
+    if !encoder.is_null() {
+        drop(Box::from_raw(encoder));
+    }
+}
+
+/// Removes the pushed values so the encoder can be reused.
+///
+/// # Safety
+/// `encoder` must be null or a live encoder.
+#[no_mangle]
+pub unsafe extern "C" fn pe_encoder_clear(encoder: *mut PeEncoder) -> i32 {
+    match encoder.as_mut() {
+        Some(encoder) => {
+            encoder.values.clear();
+            PE_OK
+        }
+        None => PE_ERROR_NULL,
+    }
+}
+
+// the push functions are written out, cbindgen does not expand macros
+
+/// appends a value to the encoder
+unsafe fn push(encoder: *mut PeEncoder, value: EncodeType) -> i32 {
+    match encoder.as_mut() {
+        Some(encoder) => {
+            encoder.values.push(value);
+            PE_OK
+        }
+        None => PE_ERROR_NULL,
+    }
+}
+
+/// Appends an `i8` value.
+///
+/// # Safety
+/// `encoder` must be null or a live encoder.
+#[no_mangle]
+pub unsafe extern "C" fn pe_encoder_push_i8(encoder: *mut PeEncoder, value: i8) -> i32 {
+    push(encoder, EncodeType::Int8(value))
+}
+
+/// Appends an `i16` value.
+///
+/// # Safety
+/// `encoder` must be null or a live encoder.
+#[no_mangle]
+pub unsafe extern "C" fn pe_encoder_push_i16(encoder: *mut PeEncoder, value: i16) -> i32 {
+    push(encoder, EncodeType::Int16(value))
+}
+
+/// Appends an `i32` value.
+///
+/// # Safety
+/// `encoder` must be null or a live encoder.
+#[no_mangle]
+pub unsafe extern "C" fn pe_encoder_push_i32(encoder: *mut PeEncoder, value: i32) -> i32 {
+    push(encoder, EncodeType::Int32(value))
+}
+
+/// Appends an `i64` value.
+///
+/// # Safety
+/// `encoder` must be null or a live encoder.
+#[no_mangle]
+pub unsafe extern "C" fn pe_encoder_push_i64(encoder: *mut PeEncoder, value: i64) -> i32 {
+    push(encoder, EncodeType::Int64(value))
+}
+
+/// Appends a `u8` value.
+///
+/// # Safety
+/// `encoder` must be null or a live encoder.
+#[no_mangle]
+pub unsafe extern "C" fn pe_encoder_push_u8(encoder: *mut PeEncoder, value: u8) -> i32 {
+    push(encoder, EncodeType::Uint8(value))
+}
+
+/// Appends a `u16` value.
+///
+/// # Safety
+/// `encoder` must be null or a live encoder.
+#[no_mangle]
+pub unsafe extern "C" fn pe_encoder_push_u16(encoder: *mut PeEncoder, value: u16) -> i32 {
+    push(encoder, EncodeType::Uint16(value))
+}
+
+/// Appends a `u32` value.
+///
+/// # Safety
+/// `encoder` must be null or a live encoder.
+#[no_mangle]
+pub unsafe extern "C" fn pe_encoder_push_u32(encoder: *mut PeEncoder, value: u32) -> i32 {
+    push(encoder, EncodeType::Uint32(value))
+}
+
+/// Appends a `u64` value.
+///
+/// # Safety
+/// `encoder` must be null or a live encoder.
+#[no_mangle]
+pub unsafe extern "C" fn pe_encoder_push_u64(encoder: *mut PeEncoder, value: u64) -> i32 {
+    push(encoder, EncodeType::Uint64(value))
+}
+
+/// Appends a `f32` value.
+///
+/// # Safety
+/// `encoder` must be null or a live encoder.
+#[no_mangle]
+pub unsafe extern "C" fn pe_encoder_push_f32(encoder: *mut PeEncoder, value: f32) -> i32 {
+    push(encoder, EncodeType::Float32(value))
+}
+
+/// Appends a `f64` value.
+///
+/// # Safety
+/// `encoder` must be null or a live encoder.
+#[no_mangle]
+pub unsafe extern "C" fn pe_encoder_push_f64(encoder: *mut PeEncoder, value: f64) -> i32 {
+    push(encoder, EncodeType::Float64(value))
+}
+
+/// Appends a `bool` value.
+///
+/// # Safety
+/// `encoder` must be null or a live encoder.
+#[no_mangle]
+pub unsafe extern "C" fn pe_encoder_push_bool(encoder: *mut PeEncoder, value: bool) -> i32 {
+    push(encoder, EncodeType::Bool(value))
+}
+
+/// Appends a UTF-8 string of `len` bytes, the string is not null-terminated in the output.
+///
+/// # Safety
+/// `encoder` must be null or a live encoder, `data` must point to `len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn pe_encoder_push_str(
+    encoder: *mut PeEncoder,
+    data: *const u8,
+    len: usize,
+) -> i32 {
+    let Some(data) = input(data, len) else {
+        return PE_ERROR_NULL;
+    };
+    match std::str::from_utf8(data) {
+        Ok(content) => push(encoder, EncodeType::Str(content.to_owned())),
+        Err(_) => PE_ERROR_UTF8,
+    }
+}
+
+/// Appends `len` raw bytes.
+///
+/// # Safety
+/// `encoder` must be null or a live encoder, `data` must point to `len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn pe_encoder_push_bytes(
+    encoder: *mut PeEncoder,
+    data: *const u8,
+    len: usize,
+) -> i32 {
+    let Some(data) = input(data, len) else {
+        return PE_ERROR_NULL;
+    };
+    push(encoder, EncodeType::Bytes(data.to_vec()))
+}
+
+/// Encodes the pushed values with `encode_packed` into `output`. The encoded size is written to `written`, when
+/// `capacity` is too small `PE_ERROR_BUFFER_TOO_SMALL` is returned and `written` holds the required size.
+///
+/// # Safety
+/// `encoder` must be null or a live encoder, `output` must point to `capacity` writable bytes and `written` must be
+/// null or writable.
+#[no_mangle]
+pub unsafe extern "C" fn pe_encoder_encode(
+    encoder: *const PeEncoder,
+    order: i32,
+    output: *mut u8,
+    capacity: usize,
+    written: *mut usize,
+) -> i32 {
+    let Some(encoder) = encoder.as_ref() else {
+        return PE_ERROR_NULL;
+    };
+    let order = match decode_order(order) {
+        Some(order) => EncodeOrder::from(order),
+        None => return PE_ERROR_ORDER,
+    };
+    let encoded = match encoder::encode_packed(&encoder.values, order) {
+        Ok(encoded) => encoded,
+        Err(_) => return PE_ERROR_ENCODE,
+    };
+
+    if let Some(written) = written.as_mut() {
+        *written = encoded.len();
+    }
+    if encoded.len() > capacity {
+        return PE_ERROR_BUFFER_TOO_SMALL;
+    }
+    if !encoded.is_empty() {
+        if output.is_null() {
+            return PE_ERROR_NULL;
+        }
+        ptr::copy_nonoverlapping(encoded.as_ptr(), output, encoded.len());
+    }
+    PE_OK
+}
+
+/// Parses a schema written in the schema text language, the schema is stored in `schema` and must be released with
+/// `pe_schema_free`.
+///
+/// # Safety
+/// `text` must point to `len` readable bytes and `schema` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn pe_schema_parse(
+    text: *const u8,
+    len: usize,
+    schema: *mut *mut PeSchema,
+) -> i32 {
+    let (Some(text), Some(schema)) = (input(text, len), schema.as_mut()) else {
+        return PE_ERROR_NULL;
+    };
+    let Ok(text) = std::str::from_utf8(text) else {
+        return PE_ERROR_UTF8;
+    };
+    match text::parse_schema(text) {
+        Ok(parsed) => {
+            *schema = Box::into_raw(Box::new(PeSchema { schema: parsed }));
+            PE_OK
+        }
+        Err(_) => PE_ERROR_SCHEMA,
+    }
+}
+
+/// Releases a schema.
+///
+/// # Safety
+/// `schema` must be null or come from `pe_schema_parse`, and must not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn pe_schema_free(schema: *mut PeSchema) {
+    if !schema.is_null() {
+        drop(Box::from_raw(schema));
+    }
+}
+
+/// Decodes `len` bytes with the schema, the record is stored in `record` and must be released with
+/// `pe_record_free`.
+///
+/// # Safety
+/// `schema` must be null or a live schema, `data` must point to `len` readable bytes and `record` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn pe_decode(
+    schema: *const PeSchema,
+    data: *const u8,
+    len: usize,
+    order: i32,
+    record: *mut *mut PeRecord,
+) -> i32 {
+    let (Some(schema), Some(data), Some(record)) =
+        (schema.as_ref(), input(data, len), record.as_mut())
+    else {
+        return PE_ERROR_NULL;
+    };
+    let Some(order) = decode_order(order) else {
+        return PE_ERROR_ORDER;
+    };
+    match schema.schema.decode(data, order) {
+        Ok(decoded) => {
+            *record = Box::into_raw(Box::new(PeRecord { record: decoded }));
+            PE_OK
+        }
+        Err(_) => PE_ERROR_DECODE,
+    }
+}
+
+/// Releases a record, the pointers returned by `pe_record_get_str` and `pe_record_get_bytes` become invalid.
+///
+/// # Safety
+/// `record` must be null or come from `pe_decode`, and must not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn pe_record_free(record: *mut PeRecord) {
+    if !record.is_null() {
+        drop(Box::from_raw(record));
+    }
+}
+
+/// Returns the number of decoded values, 0 for a null record.
+///
+/// # Safety
+/// `record` must be null or a live record.
+#[no_mangle]
+pub unsafe extern "C" fn pe_record_len(record: *const PeRecord) -> usize {
+    record.as_ref().map_or(0, |record| record.record.len())
+}
+
+/// looks up the value at `index` and writes its conversion to `value`
+unsafe fn read_value<T>(
+    record: *const PeRecord,
+    index: usize,
+    value: *mut T,
+    convert: impl FnOnce(&DecodedData) -> Option<T>,
+) -> i32 {
+    let (Some(record), Some(value)) = (record.as_ref(), value.as_mut()) else {
+        return PE_ERROR_NULL;
+    };
+    match record.record.values().get(index) {
+        Some(data) => match convert(data) {
+            Some(converted) => {
+                *value = converted;
+                PE_OK
+            }
+            None => PE_ERROR_TYPE,
+        },
+        None => PE_ERROR_INDEX,
+    }
+}
+
+/// Reads a signed integer, or an unsigned integer up to 32 bits.
+///
+/// # Safety
+/// `record` must be null or a live record and `value` must be null or writable.
+#[no_mangle]
+pub unsafe extern "C" fn pe_record_get_i64(
+    record: *const PeRecord,
+    index: usize,
+    value: *mut i64,
+) -> i32 {
+    read_value(record, index, value, |data| data.as_i64().ok())
+}
+
+/// Reads an unsigned integer.
+///
+/// # Safety
+/// `record` must be null or a live record and `value` must be null or writable.
+#[no_mangle]
+pub unsafe extern "C" fn pe_record_get_u64(
+    record: *const PeRecord,
+    index: usize,
+    value: *mut u64,
+) -> i32 {
+    read_value(record, index, value, |data| data.as_u64().ok())
+}
+
+/// Reads a `f32` or `f64` value.
+///
+/// # Safety
+/// `record` must be null or a live record and `value` must be null or writable.
+#[no_mangle]
+pub unsafe extern "C" fn pe_record_get_f64(
+    record: *const PeRecord,
+    index: usize,
+    value: *mut f64,
+) -> i32 {
+    read_value(record, index, value, |data| data.as_f64().ok())
+}
+
+/// Reads a boolean.
+///
+/// # Safety
+/// `record` must be null or a live record and `value` must be null or writable.
+#[no_mangle]
+pub unsafe extern "C" fn pe_record_get_bool(
+    record: *const PeRecord,
+    index: usize,
+    value: *mut bool,
+) -> i32 {
+    read_value(record, index, value, |data| bool::try_from(data).ok())
+}
+
+/// Reads a string, `data` points into the record and is not null-terminated.
+///
+/// # Safety
+/// `record` must be null or a live record, `data` and `len` must be null or writable.
+#[no_mangle]
+pub unsafe extern "C" fn pe_record_get_str(
+    record: *const PeRecord,
+    index: usize,
+    data: *mut *const u8,
+    len: *mut usize,
+) -> i32 {
+    read_slice(record, index, data, len, |value| {
+        value.as_str().ok().map(str::as_bytes)
+    })
+}
+
+/// Reads bytes, `data` points into the record.
+///
+/// # Safety
+/// `record` must be null or a live record, `data` and `len` must be null or writable.
+#[no_mangle]
+pub unsafe extern "C" fn pe_record_get_bytes(
+    record: *const PeRecord,
+    index: usize,
+    data: *mut *const u8,
+    len: *mut usize,
+) -> i32 {
+    read_slice(record, index, data, len, |value| value.as_bytes().ok())
+}
+
+unsafe fn read_slice(
+    record: *const PeRecord,
+    index: usize,
+    data: *mut *const u8,
+    len: *mut usize,
+    convert: impl FnOnce(&DecodedData) -> Option<&[u8]>,
+) -> i32 {
+    let (Some(record), Some(data), Some(len)) = (record.as_ref(), data.as_mut(), len.as_mut())
+    else {
+        return PE_ERROR_NULL;
+    };
+    match record.record.values().get(index) {
+        Some(value) => match convert(value) {
+            Some(content) => {
+                *data = content.as_ptr();
+                *len = content.len();
+                PE_OK
+            }
+            None => PE_ERROR_TYPE,
+        },
+        None => PE_ERROR_INDEX,
+    }
+}
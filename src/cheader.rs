@@ -0,0 +1,926 @@
+use crate::bitfield::{BitField, BitOrder};
+use crate::decoder::{DecodeOrder, DecodeType};
+use crate::schema::{Field, Schema};
+use crate::text::ParseError;
+use std::collections::HashMap;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// `Packing` selects how the generated struct is declared packed.
+/// Example: `Packing::Pragma` wraps the struct in `#pragma pack(push, 1)` and `#pragma pack(pop)`.
+pub enum Packing {
+    /// Pragma uses `#pragma pack(push, 1)`, understood by GCC, Clang and MSVC
+    Pragma,
+    /// Attribute uses `__attribute__((packed))`, understood by GCC and Clang
+    Attribute,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// `HeaderError` is returned when a schema cannot be expressed as a packed C struct.
+pub enum HeaderError {
+    /// VariableSize occurs when the field at the given index does not have a fixed size, like `StrRef`, or when its
+    /// presence depends on other fields, like conditional and optional fields
+    VariableSize(usize),
+    /// InvalidName occurs when the name of the schema or of the field at the given index is not a valid C identifier,
+    /// C keywords included
+    InvalidName(Option<usize>),
+    /// DuplicateName occurs when the field at the given index has the same member name as an earlier field, the
+    /// names given to unnamed fields included
+    DuplicateName(usize),
+}
+
+impl fmt::Display for HeaderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HeaderError::VariableSize(index) => {
+                write!(f, "field {} does not have a fixed size", index)
+            }
+            HeaderError::InvalidName(Some(index)) => {
+                write!(f, "name of field {} is not a valid C identifier", index)
+            }
+            HeaderError::InvalidName(None) => {
+                f.write_str("schema name is not a valid C identifier")
+            }
+            HeaderError::DuplicateName(index) => {
+                write!(
+                    f,
+                    "name of field {} is already used by another field",
+                    index
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for HeaderError {}
+
+/// `generate` emits a C header declaring a packed struct with the layout written by `encode_packed` for the schema.
+/// Multi-byte fields are stored in the given byte order unless the field has its own, the header notes the byte
+/// order of every multi-byte field and checks the size and the offsets of the struct with `static_assert`.
+/// Unnamed fields are named after their index, the struct is named after the schema or `record`.
+///
+/// # Examples
+/// ```rust
+/// use packed_encoder::cheader::{self, Packing};
+/// use packed_encoder::decoder::{DecodeOrder, DecodeType};
+/// use packed_encoder::schema::Schema;
+///
+/// let schema = Schema::named("header")
+///     .field("version", DecodeType::Uint8)
+///     .field("len", DecodeType::Uint32);
+/// let header = cheader::generate(&schema, DecodeOrder::Little, Packing::Pragma).unwrap();
+/// assert!(header.contains("    uint32_t len; /* little endian */"));
+/// assert!(header.contains("static_assert(offsetof(struct header, len) == 1"));
+/// ```
+pub fn generate(
+    schema: &Schema,
+    order: DecodeOrder,
+    packing: Packing,
+) -> Result<String, HeaderError> {
+    let struct_name = schema.name.as_deref().unwrap_or("record");
+    if !is_identifier(struct_name) {
+        return Err(HeaderError::InvalidName(None));
+    }
+
+    let mut members = vec![];
+    let mut offsets = vec![];
+    let mut offset = 0;
+    for (index, field) in schema.fields.iter().enumerate() {
+        let size = field
+            .kind
+            .fixed_size()
+            .ok_or(HeaderError::VariableSize(index))?;
+        let name = match &field.name {
+            Some(name) if is_identifier(name) => name.clone(),
+            Some(_) => return Err(HeaderError::InvalidName(Some(index))),
+            None => match field.kind {
+                DecodeType::Reserved(_) => format!("reserved_{}", index),
+                DecodeType::Padding(_) => format!("padding_{}", index),
+                _ => format!("field_{}", index),
+            },
+        };
+
+        // elements of size zero cannot be declared in C, they take no room in the encoded bytes either
+        if size > 0 {
+            let order = field.order.as_ref().unwrap_or(&order);
+            let (ctype, dimensions, note) =
+                declaration(&field.kind, order).ok_or(HeaderError::VariableSize(index))?;
+            let mut member = format!("    {} {}{};", ctype, name, dimensions);
+            if let Some(note) = note {
+                member.push_str(&format!(" /* {} */", note));
+            }
+            members.push(member);
+            if offsets.iter().any(|(member, _)| *member == name) {
+                return Err(HeaderError::DuplicateName(index));
+            }
+            offsets.push((name, offset));
+        }
+        offset += size;
+    }
+
+    let guard = format!("{}_H", struct_name.to_ascii_uppercase());
+    let mut header = format!(
+        "/* Generated by packed-encoder, do not edit: the layout must match the encoded bytes. */\n\
+         #ifndef {guard}\n\
+         #define {guard}\n\n\
+         #include <assert.h>\n\
+         #include <stddef.h>\n\
+         #include <stdint.h>\n\n\
+         /*\n \
+         * Fields are packed without padding. Multi-byte fields are stored in the byte order noted next to them,\n \
+         * hosts of the other byte order must swap them, for example with be32toh() or le32toh().\n \
+         */\n",
+    );
+
+    let body = members.join("\n");
+    match packing {
+        Packing::Pragma => header.push_str(&format!(
+            "#pragma pack(push, 1)\nstruct {} {{\n{}\n}};\n#pragma pack(pop)\n\n",
+            struct_name, body
+        )),
+        Packing::Attribute => header.push_str(&format!(
+            "struct __attribute__((packed)) {} {{\n{}\n}};\n\n",
+            struct_name, body
+        )),
+    }
+
+    header.push_str(&format!(
+        "static_assert(sizeof(struct {name}) == {size}, \"struct {name} must be {size} bytes\");\n",
+        name = struct_name,
+        size = offset
+    ));
+    for (member, offset) in offsets {
+        header.push_str(&format!(
+            "static_assert(offsetof(struct {name}, {member}) == {offset}, \"{member} must be at offset {offset}\");\n",
+            name = struct_name,
+        ));
+    }
+    header.push_str(&format!("\n#endif /* {} */\n", guard));
+    Ok(header)
+}
+
+// keywords of C23, which includes the ones of the earlier standards
+const C_KEYWORDS: &[&str] = &[
+    "alignas",
+    "alignof",
+    "auto",
+    "bool",
+    "break",
+    "case",
+    "char",
+    "const",
+    "constexpr",
+    "continue",
+    "default",
+    "do",
+    "double",
+    "else",
+    "enum",
+    "extern",
+    "false",
+    "float",
+    "for",
+    "goto",
+    "if",
+    "inline",
+    "int",
+    "long",
+    "nullptr",
+    "register",
+    "restrict",
+    "return",
+    "short",
+    "signed",
+    "sizeof",
+    "static",
+    "static_assert",
+    "struct",
+    "switch",
+    "thread_local",
+    "true",
+    "typedef",
+    "typeof",
+    "typeof_unqual",
+    "union",
+    "unsigned",
+    "void",
+    "volatile",
+    "while",
+    "_Alignas",
+    "_Alignof",
+    "_Atomic",
+    "_BitInt",
+    "_Bool",
+    "_Complex",
+    "_Decimal128",
+    "_Decimal32",
+    "_Decimal64",
+    "_Generic",
+    "_Imaginary",
+    "_Noreturn",
+    "_Static_assert",
+    "_Thread_local",
+];
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && !C_KEYWORDS.contains(&name)
+}
+
+fn order_note(order: &DecodeOrder) -> &'static str {
+    match order {
+        DecodeOrder::Big => "big endian",
+        DecodeOrder::Little => "little endian",
+    }
+}
+
+/// returns the C type, the array dimensions and the note of a member of the given type, `None` for types that
+/// cannot be declared
+fn declaration(kind: &DecodeType, order: &DecodeOrder) -> Option<(String, String, Option<String>)> {
+    let scalar = |ctype: &str, note: Option<String>| Some((ctype.to_owned(), String::new(), note));
+    let endian = Some(order_note(order).to_owned());
+    match kind {
+        DecodeType::Int8 => scalar("int8_t", None),
+        DecodeType::Uint8 => scalar("uint8_t", None),
+        DecodeType::Int16 => scalar("int16_t", endian),
+        DecodeType::Uint16 => scalar("uint16_t", endian),
+        DecodeType::Int32 => scalar("int32_t", endian),
+        DecodeType::Uint32 => scalar("uint32_t", endian),
+        DecodeType::Int64 => scalar("int64_t", endian),
+        DecodeType::Uint64 => scalar("uint64_t", endian),
+        DecodeType::Float32 => scalar("float", endian.map(|note| format!("IEEE 754, {}", note))),
+        DecodeType::Float64 => scalar("double", endian.map(|note| format!("IEEE 754, {}", note))),
+        DecodeType::Bool => scalar("uint8_t", Some("boolean, 0 or 1".to_owned())),
+        // there is no standard 128-bit integer type
+        DecodeType::Int128 => Some((
+            "uint8_t".to_owned(),
+            "[16]".to_owned(),
+            Some(format!("signed 128-bit integer, {}", order_note(order))),
+        )),
+        DecodeType::Uint128 => Some((
+            "uint8_t".to_owned(),
+            "[16]".to_owned(),
+            Some(format!("unsigned 128-bit integer, {}", order_note(order))),
+        )),
+        DecodeType::Str(size) => Some((
+            "char".to_owned(),
+            format!("[{}]", size),
+            Some("not null-terminated".to_owned()),
+        )),
+        DecodeType::Bytes(size) => Some(("uint8_t".to_owned(), format!("[{}]", size), None)),
+        DecodeType::Const(content) => {
+            let bytes: Vec<String> = content
+                .iter()
+                .map(|byte| format!("0x{:02x}", byte))
+                .collect();
+            Some((
+                "uint8_t".to_owned(),
+                format!("[{}]", content.len()),
+                Some(format!("constant {}", bytes.join(" "))),
+            ))
+        }
+        DecodeType::Reserved(size) => Some((
+            "uint8_t".to_owned(),
+            format!("[{}]", size),
+            Some("reserved, must be zero".to_owned()),
+        )),
+        DecodeType::Padding(size) => Some(("uint8_t".to_owned(), format!("[{}]", size), None)),
+        DecodeType::BitGroup(fields, bit_order) => {
+            // the layout of C bit-fields is implementation defined, the group is declared as bytes
+            let widths: Vec<String> = fields
+                .iter()
+                .map(|field| match field {
+                    BitField::Unsigned(width) => format!("u{}", width),
+                    BitField::Signed(width) => format!("i{}", width),
+                })
+                .collect();
+            let bit_order = match bit_order {
+                BitOrder::Msb => "msb first",
+                BitOrder::Lsb => "lsb first",
+            };
+            Some((
+                "uint8_t".to_owned(),
+                format!("[{}]", kind.fixed_size()?),
+                Some(format!("bit-fields {} ({})", widths.join(" "), bit_order)),
+            ))
+        }
+        DecodeType::Checksum(checksum, range) => {
+            let ctype = match checksum.size() {
+                1 => "uint8_t",
+                2 => "uint16_t",
+                _ => "uint32_t",
+            };
+            let mut note = format!("{:?} over fields {}..{}", checksum, range.start, range.end);
+            if checksum.size() > 1 {
+                note.push_str(&format!(", {}", order_note(order)));
+            }
+            scalar(ctype, Some(note))
+        }
+        DecodeType::Array(item, count) => {
+            let (ctype, dimensions, note) = declaration(item, order)?;
+            Some((ctype, format!("[{}]{}", count, dimensions), note))
+        }
+        DecodeType::StrRef(_)
+        | DecodeType::BytesRef(_)
+        | DecodeType::ArrayRef(..)
+        | DecodeType::Conditional(..)
+        | DecodeType::Optional(_) => None,
+    }
+}
+
+/// `parse` reads the struct declarations of a C header and returns one schema per struct, named after its tag or
+/// its typedef name. It understands a practical subset of C: the `stdint.h` types, `char`, `short`, `int`,
+/// `long long`, `float`, `double` and `bool`, fixed arrays, `char[N]` strings read as bytes, nested structs,
+/// `__attribute__((packed))`, `#pragma pack` and `#define` constants used as array sizes.
+///
+/// Members are aligned to their natural alignment, as on the common 64-bit ABIs, unless the struct is packed, and
+/// the padding is declared with `DecodeType::Padding`. Nested structs are flattened: their fields are named
+/// `outer.inner`, or `outer[1].inner` for arrays of structs. Other declarations, like functions, are skipped.
+///
+/// # Examples
+/// ```rust
+/// use packed_encoder::cheader;
+/// use packed_encoder::decoder::DecodeType;
+///
+/// let schemas = cheader::parse("struct point { uint8_t tag; int32_t x; };").unwrap();
+/// assert_eq!(schemas[0].index_of("x"), Some(2));
+/// assert!(matches!(schemas[0].fields[1].kind, DecodeType::Padding(3)));
+/// ```
+pub fn parse(text: &str) -> Result<Vec<Schema>, ParseError> {
+    let mut parser = CParser {
+        tokens: c_tokenize(text)?,
+        pos: 0,
+        structs: vec![],
+        pack: vec![],
+    };
+    let mut schemas = vec![];
+
+    while parser.peek() != &CToken::End {
+        if let CToken::Pack(action) = parser.peek().clone() {
+            parser.pos += 1;
+            parser.apply_pack(action);
+            continue;
+        }
+
+        let typedef = parser.accept_ident("typedef");
+        if parser.peek_ident("struct") {
+            let start = parser.pos;
+            let layout = parser.parse_struct_type()?;
+            // `typedef struct tag name;` names a struct declared earlier, it is not a new schema
+            let body = parser.tokens[start..parser.pos]
+                .iter()
+                .any(|(token, _, _)| *token == CToken::Punct('{'));
+            let mut names = vec![];
+            if let Some(tag) = &layout.tag {
+                names.push(tag.clone());
+            }
+            // `typedef struct { ... } name;` or `struct tag { ... } variable;`
+            while let CToken::Ident(name) = parser.peek().clone() {
+                parser.pos += 1;
+                if typedef {
+                    parser.structs.push((name.clone(), layout.clone()));
+                    names.push(name);
+                }
+                if !parser.accept_punct(',') {
+                    break;
+                }
+            }
+            parser.expect_punct(';')?;
+            if body {
+                if let Some(name) = names.pop() {
+                    schemas.push(Schema {
+                        name: Some(name),
+                        fields: layout.fields,
+                        version: None,
+                    });
+                }
+            }
+        } else {
+            parser.skip_statement();
+        }
+    }
+    Ok(schemas)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum PackAction {
+    Push(Option<usize>),
+    Pop,
+    Set(Option<usize>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum CToken {
+    Ident(String),
+    Number(u128),
+    Punct(char),
+    Pack(PackAction),
+    End,
+}
+
+/// splits a C header into tokens, `#pragma pack` directives become tokens and `#define` constants are replaced by
+/// their value, other preprocessor lines are skipped
+fn c_tokenize(text: &str) -> Result<Vec<(CToken, usize, usize)>, ParseError> {
+    let mut tokens = vec![];
+    let mut defines: HashMap<String, u128> = HashMap::new();
+
+    let mut comment = false;
+    for (line_index, line) in text.lines().enumerate() {
+        let line_number = line_index + 1;
+        let chars: Vec<char> = line.chars().collect();
+        let mut pos = 0;
+
+        // preprocessor lines, comments spanning several lines are not expected on them
+        if !comment && line.trim_start().starts_with('#') {
+            let words: Vec<String> = line
+                .trim_start()
+                .trim_start_matches('#')
+                .split(|c: char| c.is_whitespace() || "(),".contains(c))
+                .filter(|word| !word.is_empty())
+                .map(str::to_owned)
+                .collect();
+            let column = line.len() - line.trim_start().len() + 1;
+            match words.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+                ["pragma", "pack", "push", value, ..] => tokens.push((
+                    CToken::Pack(PackAction::Push(
+                        parse_c_number(value).map(|value| value as usize),
+                    )),
+                    line_number,
+                    column,
+                )),
+                ["pragma", "pack", "push"] => {
+                    tokens.push((CToken::Pack(PackAction::Push(None)), line_number, column))
+                }
+                ["pragma", "pack", "pop", ..] => {
+                    tokens.push((CToken::Pack(PackAction::Pop), line_number, column))
+                }
+                ["pragma", "pack", value] => tokens.push((
+                    CToken::Pack(PackAction::Set(
+                        parse_c_number(value).map(|value| value as usize),
+                    )),
+                    line_number,
+                    column,
+                )),
+                ["pragma", "pack"] => {
+                    tokens.push((CToken::Pack(PackAction::Set(None)), line_number, column))
+                }
+                ["define", name, value] => {
+                    if let Some(value) = parse_c_number(value) {
+                        defines.insert(name.to_owned(), value);
+                    }
+                }
+                _ => {}
+            }
+            continue;
+        }
+
+        while pos < chars.len() {
+            if comment {
+                if chars[pos] == '*' && chars.get(pos + 1) == Some(&'/') {
+                    comment = false;
+                    pos += 2;
+                } else {
+                    pos += 1;
+                }
+                continue;
+            }
+
+            let c = chars[pos];
+            let column = pos + 1;
+            if c.is_whitespace() {
+                pos += 1;
+            } else if c == '/' && chars.get(pos + 1) == Some(&'/') {
+                break;
+            } else if c == '/' && chars.get(pos + 1) == Some(&'*') {
+                comment = true;
+                pos += 2;
+            } else if c.is_ascii_alphanumeric() || c == '_' {
+                let size = chars[pos..]
+                    .iter()
+                    .take_while(|c| c.is_ascii_alphanumeric() || **c == '_')
+                    .count();
+                let word: String = chars[pos..pos + size].iter().collect();
+                let token = if c.is_ascii_digit() {
+                    CToken::Number(parse_c_number(&word).ok_or_else(|| ParseError {
+                        line: line_number,
+                        column,
+                        message: format!("invalid number '{}'", word),
+                    })?)
+                } else if let Some(value) = defines.get(&word) {
+                    CToken::Number(*value)
+                } else {
+                    CToken::Ident(word)
+                };
+                tokens.push((token, line_number, column));
+                pos += size;
+            } else {
+                tokens.push((CToken::Punct(c), line_number, column));
+                pos += 1;
+            }
+        }
+    }
+
+    let lines = text.lines().count().max(1);
+    tokens.push((CToken::End, lines, 1));
+    Ok(tokens)
+}
+
+/// parses a decimal, hexadecimal or octal C integer literal, suffixes like `U` or `UL` are ignored
+fn parse_c_number(word: &str) -> Option<u128> {
+    let word = word.trim_end_matches(['u', 'U', 'l', 'L']);
+    if let Some(hex) = word.strip_prefix("0x").or_else(|| word.strip_prefix("0X")) {
+        u128::from_str_radix(hex, 16).ok()
+    } else if word.len() > 1 && word.starts_with('0') {
+        u128::from_str_radix(&word[1..], 8).ok()
+    } else {
+        word.parse().ok()
+    }
+}
+
+#[derive(Debug, Clone)]
+/// `StructLayout` is a struct declared in the header, its fields include the padding
+struct StructLayout {
+    tag: Option<String>,
+    fields: Vec<Field>,
+    size: usize,
+    align: usize,
+    // false for forward declarations like `struct tag;`
+    defined: bool,
+}
+
+/// `MemberType` is the type of a member before its array dimensions are applied
+enum MemberType {
+    Scalar(DecodeType, usize),
+    Char,
+    Struct(StructLayout),
+}
+
+struct CParser {
+    tokens: Vec<(CToken, usize, usize)>,
+    pos: usize,
+    // structs declared so far, by tag and by typedef name
+    structs: Vec<(String, StructLayout)>,
+    // stack of `#pragma pack` values, the last one applies
+    pack: Vec<Option<usize>>,
+}
+
+impl CParser {
+    fn peek(&self) -> &CToken {
+        &self.tokens[self.pos].0
+    }
+
+    fn error(&self, message: &str) -> ParseError {
+        let (_, line, column) = self.tokens[self.pos];
+        ParseError {
+            line,
+            column,
+            message: message.to_owned(),
+        }
+    }
+
+    fn peek_ident(&self, word: &str) -> bool {
+        matches!(self.peek(), CToken::Ident(ident) if ident == word)
+    }
+
+    fn accept_ident(&mut self, word: &str) -> bool {
+        let found = self.peek_ident(word);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn accept_punct(&mut self, punct: char) -> bool {
+        let found = self.peek() == &CToken::Punct(punct);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn expect_punct(&mut self, punct: char) -> Result<(), ParseError> {
+        if self.accept_punct(punct) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected '{}'", punct)))
+        }
+    }
+
+    fn ident(&mut self) -> Result<String, ParseError> {
+        match self.peek().clone() {
+            CToken::Ident(ident) => {
+                self.pos += 1;
+                Ok(ident)
+            }
+            _ => Err(self.error("expected a name")),
+        }
+    }
+
+    fn apply_pack(&mut self, action: PackAction) {
+        match action {
+            PackAction::Push(value) => {
+                let current = self.pack.last().copied().flatten();
+                self.pack.push(value.or(current));
+            }
+            PackAction::Pop => {
+                self.pack.pop();
+            }
+            PackAction::Set(value) => match self.pack.last_mut() {
+                Some(last) => *last = value,
+                None => self.pack.push(value),
+            },
+        }
+    }
+
+    /// skips a declaration that is not a struct, up to the `;` or the `}` closing it
+    fn skip_statement(&mut self) {
+        let mut depth = 0;
+        loop {
+            match self.peek() {
+                CToken::End => return,
+                CToken::Punct('{') => depth += 1,
+                CToken::Punct('}') => {
+                    depth -= 1;
+                    if depth <= 0 {
+                        self.pos += 1;
+                        self.accept_punct(';');
+                        return;
+                    }
+                }
+                CToken::Punct(';') if depth == 0 => {
+                    self.pos += 1;
+                    return;
+                }
+                _ => {}
+            }
+            self.pos += 1;
+        }
+    }
+
+    /// parses `__attribute__((...))` lists, returns true if one of them is `packed`
+    fn parse_attributes(&mut self) -> Result<bool, ParseError> {
+        let mut packed = false;
+        while self.accept_ident("__attribute__") {
+            self.expect_punct('(')?;
+            self.expect_punct('(')?;
+            loop {
+                match self.ident()?.as_str() {
+                    "packed" | "__packed__" => packed = true,
+                    _ => return Err(self.error_before("only the packed attribute is supported")),
+                }
+                if !self.accept_punct(',') {
+                    break;
+                }
+            }
+            self.expect_punct(')')?;
+            self.expect_punct(')')?;
+        }
+        Ok(packed)
+    }
+
+    fn error_before(&self, message: &str) -> ParseError {
+        let (_, line, column) = self.tokens[self.pos.saturating_sub(1)];
+        ParseError {
+            line,
+            column,
+            message: message.to_owned(),
+        }
+    }
+
+    /// parses `struct tag`, `struct tag { ... }` or `struct { ... }` along with its attributes
+    fn parse_struct_type(&mut self) -> Result<StructLayout, ParseError> {
+        self.accept_ident("struct");
+        let mut packed = self.parse_attributes()?;
+        let tag = match self.peek() {
+            CToken::Ident(_) => Some(self.ident()?),
+            _ => None,
+        };
+        packed |= self.parse_attributes()?;
+
+        if !self.accept_punct('{') {
+            let tag = tag.ok_or_else(|| self.error("expected a struct name or '{'"))?;
+            return Ok(self.lookup(&tag).unwrap_or(StructLayout {
+                tag: Some(tag),
+                fields: vec![],
+                size: 0,
+                align: 1,
+                defined: false,
+            }));
+        }
+
+        let mut fields = vec![];
+        let mut offset: usize = 0;
+        let mut members = vec![];
+        while !self.accept_punct('}') {
+            members.push(self.parse_member()?);
+        }
+        packed |= self.parse_attributes()?;
+
+        let pack = if packed {
+            Some(1)
+        } else {
+            self.pack.last().copied().flatten()
+        };
+        let mut struct_align = 1;
+        for (name, member, dimensions, member_packed) in members {
+            let (size, natural_align) = match &member {
+                MemberType::Scalar(_, size) => (*size, *size),
+                MemberType::Char => (1, 1),
+                MemberType::Struct(layout) => (layout.size, layout.align),
+            };
+            let align = if member_packed {
+                1
+            } else {
+                pack.map_or(natural_align, |pack| natural_align.min(pack.max(1)))
+            };
+            struct_align = struct_align.max(align);
+
+            let padding = offset.next_multiple_of(align) - offset;
+            if padding > 0 {
+                fields.push(Field::unnamed(DecodeType::Padding(padding)));
+            }
+            let count: usize = dimensions.iter().product();
+            offset += padding + size * count;
+            member_fields(&mut fields, &name, &member, &dimensions);
+        }
+
+        let padding = offset.next_multiple_of(struct_align) - offset;
+        if padding > 0 {
+            fields.push(Field::unnamed(DecodeType::Padding(padding)));
+        }
+        let layout = StructLayout {
+            tag: tag.clone(),
+            fields,
+            size: offset + padding,
+            align: struct_align,
+            defined: true,
+        };
+        if let Some(tag) = tag {
+            self.structs.push((tag, layout.clone()));
+        }
+        Ok(layout)
+    }
+
+    fn lookup(&self, name: &str) -> Option<StructLayout> {
+        self.structs
+            .iter()
+            .rev()
+            .find(|(entry, _)| entry == name)
+            .map(|(_, layout)| layout.clone())
+    }
+
+    /// parses a member declaration, returns its name, type, array dimensions and whether it is packed. Members
+    /// declared together, like `uint8_t a, b;`, are not supported.
+    #[allow(clippy::type_complexity)]
+    fn parse_member(&mut self) -> Result<(String, MemberType, Vec<usize>, bool), ParseError> {
+        while self.accept_ident("const") || self.accept_ident("volatile") {}
+
+        let member = if self.peek_ident("struct") {
+            let layout = self.parse_struct_type()?;
+            if !layout.defined {
+                return Err(self.error_before("struct is not declared before its use"));
+            }
+            MemberType::Struct(layout)
+        } else {
+            self.parse_scalar()?
+        };
+        let mut packed = self.parse_attributes()?;
+
+        let name = self.ident()?;
+        let mut dimensions = vec![];
+        while self.accept_punct('[') {
+            match self.peek().clone() {
+                CToken::Number(count) => {
+                    self.pos += 1;
+                    dimensions.push(
+                        usize::try_from(count)
+                            .map_err(|_| self.error_before("array is too large"))?,
+                    );
+                }
+                _ => return Err(self.error("expected an array size")),
+            }
+            self.expect_punct(']')?;
+        }
+        if self.peek() == &CToken::Punct(':') {
+            return Err(
+                self.error("bit-fields are not supported, their layout is implementation defined")
+            );
+        }
+        if self.peek() == &CToken::Punct(',') {
+            return Err(self.error("declare one member per statement"));
+        }
+        packed |= self.parse_attributes()?;
+        self.expect_punct(';')?;
+        Ok((name, member, dimensions, packed))
+    }
+
+    /// parses a scalar type, like `uint32_t`, `unsigned short` or `char`
+    fn parse_scalar(&mut self) -> Result<MemberType, ParseError> {
+        let mut words = vec![];
+        while let CToken::Ident(word) = self.peek().clone() {
+            // the member name follows the type
+            if !words.is_empty()
+                && !matches!(
+                    word.as_str(),
+                    "int" | "short" | "long" | "char" | "signed" | "unsigned"
+                )
+            {
+                break;
+            }
+            words.push(word);
+            self.pos += 1;
+            if !matches!(
+                words[0].as_str(),
+                "int" | "short" | "long" | "char" | "signed" | "unsigned"
+            ) {
+                break;
+            }
+        }
+
+        let kind = match words.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+            ["char"] => return Ok(MemberType::Char),
+            ["int8_t"] | ["signed", "char"] => DecodeType::Int8,
+            ["uint8_t"] | ["unsigned", "char"] => DecodeType::Uint8,
+            ["int16_t"] | ["short"] | ["short", "int"] | ["signed", "short"] => DecodeType::Int16,
+            ["uint16_t"] | ["unsigned", "short"] | ["unsigned", "short", "int"] => {
+                DecodeType::Uint16
+            }
+            ["int32_t"] | ["int"] | ["signed"] | ["signed", "int"] => DecodeType::Int32,
+            ["uint32_t"] | ["unsigned"] | ["unsigned", "int"] => DecodeType::Uint32,
+            ["int64_t"]
+            | ["long", "long"]
+            | ["long", "long", "int"]
+            | ["signed", "long", "long"] => DecodeType::Int64,
+            ["uint64_t"] | ["unsigned", "long", "long"] | ["unsigned", "long", "long", "int"] => {
+                DecodeType::Uint64
+            }
+            ["float"] => DecodeType::Float32,
+            ["double"] => DecodeType::Float64,
+            ["bool"] | ["_Bool"] => DecodeType::Bool,
+            [.., "long"] | [.., "long", "int"] => {
+                return Err(
+                    self.error_before("long has a platform dependent size, use int32_t or int64_t")
+                )
+            }
+            [] => return Err(self.error("expected a type")),
+            _ => {
+                let name = words.join(" ");
+                return match self.lookup(&name) {
+                    Some(layout) => Ok(MemberType::Struct(layout)),
+                    None => Err(self.error_before(&format!("unknown type '{}'", name))),
+                };
+            }
+        };
+        let size = kind.fixed_size().unwrap_or(1);
+        Ok(MemberType::Scalar(kind, size))
+    }
+}
+
+/// appends the fields of a member, arrays of structs and nested structs are flattened
+fn member_fields(fields: &mut Vec<Field>, name: &str, member: &MemberType, dimensions: &[usize]) {
+    match member {
+        MemberType::Struct(layout) => match dimensions.split_first() {
+            None => {
+                for field in &layout.fields {
+                    fields.push(Field {
+                        name: field
+                            .name
+                            .as_ref()
+                            .map(|inner| format!("{}.{}", name, inner)),
+                        kind: field.kind.clone(),
+                        order: field.order.clone(),
+                        default: None,
+                    });
+                }
+            }
+            Some((count, rest)) => {
+                for index in 0..*count {
+                    member_fields(fields, &format!("{}[{}]", name, index), member, rest);
+                }
+            }
+        },
+        MemberType::Char => {
+            // a single `char` is a signed byte like on most ABIs. `char name[N]` is read as bytes: C strings end at
+            // the first NUL and the bytes after it are often left uninitialized, they need not be valid UTF-8
+            let kind = match dimensions.split_last() {
+                None => DecodeType::Int8,
+                Some((size, outer)) => array_type(DecodeType::Bytes(*size), outer),
+            };
+            fields.push(Field::new(name, kind));
+        }
+        MemberType::Scalar(kind, _) => {
+            fields.push(Field::new(name, array_type(kind.clone(), dimensions)))
+        }
+    }
+}
+
+/// wraps a type in arrays, the first dimension is the outermost one like in C
+fn array_type(kind: DecodeType, dimensions: &[usize]) -> DecodeType {
+    dimensions.iter().rev().fold(kind, |kind, count| {
+        DecodeType::Array(Box::new(kind), *count)
+    })
+}
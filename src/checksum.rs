@@ -0,0 +1,103 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// `ChecksumKind` lists the checksum algorithms that can be used to protect a range of encoded fields.
+/// Example: `ChecksumKind::Crc32` computes the CRC-32 used by ethernet, zlib and PNG.
+pub enum ChecksumKind {
+    /// CRC-8 with polynomial 0x07 and zero init (CRC-8/SMBUS), 1 byte
+    Crc8,
+    /// CRC-16/CCITT with polynomial 0x1021 and init 0xffff (CRC-16/CCITT-FALSE), 2 bytes
+    Crc16Ccitt,
+    /// CRC-32/IEEE 802.3 (reflected polynomial 0xedb88320), 4 bytes
+    Crc32,
+    /// CRC-32C Castagnoli (reflected polynomial 0x82f63b78), 4 bytes
+    Crc32c,
+    /// Adler-32 as used by zlib, 4 bytes
+    Adler32,
+    /// Sum of all the bytes modulo 256, 1 byte
+    Sum8,
+    /// XOR of all the bytes, 1 byte
+    Xor8,
+}
+
+impl ChecksumKind {
+    /// returns the number of bytes occupied by the checksum in the encoded output
+    pub fn size(&self) -> usize {
+        match self {
+            ChecksumKind::Crc8 | ChecksumKind::Sum8 | ChecksumKind::Xor8 => 1,
+            ChecksumKind::Crc16Ccitt => 2,
+            ChecksumKind::Crc32 | ChecksumKind::Crc32c | ChecksumKind::Adler32 => 4,
+        }
+    }
+
+    /// computes the checksum of the given bytes
+    pub fn compute(&self, data: &[u8]) -> u32 {
+        match self {
+            ChecksumKind::Crc8 => crc8(data) as u32,
+            ChecksumKind::Crc16Ccitt => crc16_ccitt(data) as u32,
+            ChecksumKind::Crc32 => crc32_reflected(data, 0xedb8_8320),
+            ChecksumKind::Crc32c => crc32_reflected(data, 0x82f6_3b78),
+            ChecksumKind::Adler32 => adler32(data),
+            ChecksumKind::Sum8 => data.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte)) as u32,
+            ChecksumKind::Xor8 => data.iter().fold(0u8, |sum, byte| sum ^ *byte) as u32,
+        }
+    }
+}
+
+fn crc8(data: &[u8]) -> u8 {
+    let mut crc = 0u8;
+    for byte in data {
+        crc ^= *byte;
+        for _ in 0..8 {
+            crc = if crc & 0x80 != 0 {
+                (crc << 1) ^ 0x07
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+fn crc16_ccitt(data: &[u8]) -> u16 {
+    let mut crc = 0xffffu16;
+    for byte in data {
+        crc ^= (*byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+fn crc32_reflected(data: &[u8], polynomial: u32) -> u32 {
+    let mut crc = 0xffff_ffffu32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ polynomial
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    const MOD_ADLER: u32 = 65521;
+    let (mut a, mut b) = (1u32, 0u32);
+    // 5552 is the largest number of bytes that can be summed before `b` overflows
+    for chunk in data.chunks(5552) {
+        for byte in chunk {
+            a += *byte as u32;
+            b += a;
+        }
+        a %= MOD_ADLER;
+        b %= MOD_ADLER;
+    }
+    (b << 16) | a
+}
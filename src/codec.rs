@@ -0,0 +1,184 @@
+//! `codec` provides a `tokio_util::codec` implementation driven by a schema, it is enabled by the `async` feature.
+//! Wrapping a stream in `Framed<_, PackedCodec>` yields the decoded records and accepts the values to encode.
+
+use crate::decoder::{DecodeError, DecodeOrder};
+use crate::encoder::{EncodeError, EncodeType};
+use crate::framing::{Frame, FrameError, FrameFormat, DEFAULT_MAX_FRAME_SIZE};
+use crate::schema::{DecodedRecord, Schema};
+use bytes::{Buf, BytesMut};
+use std::fmt;
+use std::io;
+use tokio_util::codec::{Decoder, Encoder};
+
+#[derive(Debug)]
+/// `CodecError` is returned by `PackedCodec` when a record cannot be decoded or encoded.
+pub enum CodecError {
+    /// Io occurs when the underlying stream fails
+    Io(io::Error),
+    /// Decode occurs when the received bytes do not match the schema
+    Decode(DecodeError),
+    /// Encode occurs when the values do not match the schema
+    Encode(EncodeError),
+    /// Frame occurs when the received frame header is invalid
+    Frame(FrameError),
+    /// TooLarge occurs when a record is larger than the maximum record size, or when that many bytes were received
+    /// without completing a record
+    TooLarge { size: usize, max: usize },
+}
+
+impl fmt::Display for CodecError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CodecError::Io(error) => write!(f, "{}", error),
+            CodecError::Decode(error) => write!(f, "cannot decode record: {:?}", error),
+            CodecError::Encode(error) => write!(f, "cannot encode record: {:?}", error),
+            CodecError::Frame(error) => write!(f, "{}", error),
+            CodecError::TooLarge { size, max } => {
+                write!(
+                    f,
+                    "record of {} bytes is larger than the maximum of {} bytes",
+                    size, max
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for CodecError {}
+
+impl From<io::Error> for CodecError {
+    fn from(error: io::Error) -> CodecError {
+        CodecError::Io(error)
+    }
+}
+
+impl From<FrameError> for CodecError {
+    fn from(error: FrameError) -> CodecError {
+        CodecError::Frame(error)
+    }
+}
+
+#[derive(Debug, Clone)]
+/// `PackedCodec` decodes the records of a schema from a byte stream and encodes values into it. By default records
+/// follow each other without header, the schema tells where each one ends and a partial record waits for more bytes
+/// instead of failing with `DecodeError::IndexOutOfBounds`. With `with_framing`, every record is wrapped in a frame
+/// of the `framing` module.
+///
+/// # Examples
+/// ```rust
+/// use packed_encoder::codec::PackedCodec;
+/// use packed_encoder::decoder::{DecodeOrder, DecodeType};
+/// use packed_encoder::schema::Schema;
+/// use bytes::BytesMut;
+/// use tokio_util::codec::Decoder;
+///
+/// let schema = Schema::new().field("id", DecodeType::Uint16);
+/// let mut codec = PackedCodec::new(schema, DecodeOrder::Big);
+/// let mut received = BytesMut::from(&[0u8, 1, 0][..]);
+/// assert_eq!(codec.decode(&mut received).unwrap().unwrap().get_u16("id").unwrap(), 1);
+/// assert!(codec.decode(&mut received).unwrap().is_none());
+/// ```
+pub struct PackedCodec {
+    schema: Schema,
+    order: DecodeOrder,
+    framing: Option<FrameFormat>,
+    max_record_size: usize,
+}
+
+impl PackedCodec {
+    /// creates a codec for the records of the given schema
+    pub fn new(schema: Schema, order: DecodeOrder) -> PackedCodec {
+        PackedCodec {
+            schema,
+            order,
+            framing: None,
+            max_record_size: DEFAULT_MAX_FRAME_SIZE,
+        }
+    }
+
+    /// wraps every record in a frame of the given format, the format must not use a type id
+    pub fn with_framing(mut self, format: FrameFormat) -> PackedCodec {
+        self.max_record_size = format.max_frame_size;
+        self.framing = Some(format);
+        self
+    }
+
+    /// sets the largest record accepted, it bounds the bytes buffered while waiting for the end of a record
+    pub fn with_max_record_size(mut self, max_record_size: usize) -> PackedCodec {
+        self.max_record_size = max_record_size;
+        if let Some(format) = &mut self.framing {
+            format.max_frame_size = max_record_size;
+        }
+        self
+    }
+
+    /// returns the schema of the records
+    pub fn schema(&self) -> &Schema {
+        &self.schema
+    }
+}
+
+impl Decoder for PackedCodec {
+    type Item = DecodedRecord;
+    type Error = CodecError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<DecodedRecord>, CodecError> {
+        // records without content would be returned forever
+        if src.is_empty() {
+            return Ok(None);
+        }
+
+        if let Some(format) = &self.framing {
+            return match format.decode(src)? {
+                Some((frame, consumed)) => {
+                    src.advance(consumed);
+                    self.schema
+                        .decode(&frame.payload, self.order.clone())
+                        .map(Some)
+                        .map_err(CodecError::Decode)
+                }
+                None => Ok(None),
+            };
+        }
+
+        match self.schema.decode_prefix(src, self.order.clone()) {
+            Ok((record, consumed)) => {
+                src.advance(consumed);
+                Ok(Some(record))
+            }
+            Err(DecodeError::IndexOutOfBounds) if src.len() < self.max_record_size => Ok(None),
+            Err(DecodeError::IndexOutOfBounds) => Err(CodecError::TooLarge {
+                size: src.len(),
+                max: self.max_record_size,
+            }),
+            Err(error) => Err(CodecError::Decode(error)),
+        }
+    }
+}
+
+impl Encoder<Vec<EncodeType>> for PackedCodec {
+    type Error = CodecError;
+
+    fn encode(&mut self, values: Vec<EncodeType>, dst: &mut BytesMut) -> Result<(), CodecError> {
+        let encoded = self
+            .schema
+            .encode(&values, self.order.clone().into())
+            .map_err(CodecError::Encode)?;
+        if encoded.len() > self.max_record_size {
+            return Err(CodecError::TooLarge {
+                size: encoded.len(),
+                max: self.max_record_size,
+            });
+        }
+
+        match &self.framing {
+            Some(format) => {
+                let mut framed = vec![];
+                format.encode_into(&Frame::new(encoded), &mut framed)?;
+                dst.extend_from_slice(&framed);
+            }
+            None => dst.extend_from_slice(&encoded),
+        }
+        Ok(())
+    }
+}
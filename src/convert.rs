@@ -0,0 +1,358 @@
+use crate::bitfield::{BitField, BitValue};
+use crate::decoder::{DecodeOrder, DecodeType, DecodedData};
+use crate::encoder::{EncodeOrder, EncodeType};
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// `ConversionError` is returned when a `DecodedData` value cannot be converted into the requested Rust type.
+/// Example: converting `DecodedData::Str("hello")` into `u32` fails with `expected: "u32", found: "Str"`.
+pub struct ConversionError {
+    /// requested Rust type
+    pub expected: &'static str,
+    /// variant holding the value
+    pub found: &'static str,
+}
+
+impl fmt::Display for ConversionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "cannot convert {} into {}", self.found, self.expected)
+    }
+}
+
+impl std::error::Error for ConversionError {}
+
+// integers can be converted into any type that holds all of their values, example: `Uint16` into `u64` or `i32`,
+// the same applies to floating point numbers and booleans
+macro_rules! integer_conversion {
+    ($type:ty, [$($variant:ident),*]) => {
+        impl TryFrom<&DecodedData> for $type {
+            type Error = ConversionError;
+
+            fn try_from(value: &DecodedData) -> Result<$type, ConversionError> {
+                match value {
+                    $(DecodedData::$variant(value) => Ok(<$type>::from(*value)),)*
+                    other => Err(ConversionError {
+                        expected: stringify!($type),
+                        found: other.type_name(),
+                    }),
+                }
+            }
+        }
+
+        impl TryFrom<DecodedData> for $type {
+            type Error = ConversionError;
+
+            fn try_from(value: DecodedData) -> Result<$type, ConversionError> {
+                <$type>::try_from(&value)
+            }
+        }
+    };
+}
+
+integer_conversion!(i8, [Int8]);
+integer_conversion!(i16, [Int8, Int16, Uint8]);
+integer_conversion!(i32, [Int8, Int16, Int32, Uint8, Uint16]);
+integer_conversion!(i64, [Int8, Int16, Int32, Int64, Uint8, Uint16, Uint32]);
+integer_conversion!(
+    i128,
+    [Int8, Int16, Int32, Int64, Int128, Uint8, Uint16, Uint32, Uint64]
+);
+integer_conversion!(u8, [Uint8]);
+integer_conversion!(u16, [Uint8, Uint16]);
+integer_conversion!(u32, [Uint8, Uint16, Uint32]);
+integer_conversion!(u64, [Uint8, Uint16, Uint32, Uint64]);
+integer_conversion!(u128, [Uint8, Uint16, Uint32, Uint64, Uint128]);
+integer_conversion!(f32, [Float32]);
+integer_conversion!(f64, [Float32, Float64]);
+integer_conversion!(bool, [Bool]);
+
+impl TryFrom<DecodedData> for String {
+    type Error = ConversionError;
+
+    fn try_from(value: DecodedData) -> Result<String, ConversionError> {
+        match value {
+            DecodedData::Str(value) => Ok(value),
+            other => Err(ConversionError {
+                expected: "String",
+                found: other.type_name(),
+            }),
+        }
+    }
+}
+
+impl TryFrom<DecodedData> for Vec<u8> {
+    type Error = ConversionError;
+
+    fn try_from(value: DecodedData) -> Result<Vec<u8>, ConversionError> {
+        match value {
+            DecodedData::Bytes(value) => Ok(value),
+            other => Err(ConversionError {
+                expected: "Vec<u8>",
+                found: other.type_name(),
+            }),
+        }
+    }
+}
+
+impl<'a> TryFrom<&'a DecodedData> for &'a str {
+    type Error = ConversionError;
+
+    fn try_from(value: &'a DecodedData) -> Result<&'a str, ConversionError> {
+        match value {
+            DecodedData::Str(value) => Ok(value),
+            other => Err(ConversionError {
+                expected: "&str",
+                found: other.type_name(),
+            }),
+        }
+    }
+}
+
+impl<'a> TryFrom<&'a DecodedData> for &'a [u8] {
+    type Error = ConversionError;
+
+    fn try_from(value: &'a DecodedData) -> Result<&'a [u8], ConversionError> {
+        match value {
+            DecodedData::Bytes(value) => Ok(value),
+            other => Err(ConversionError {
+                expected: "&[u8]",
+                found: other.type_name(),
+            }),
+        }
+    }
+}
+
+impl DecodedData {
+    /// returns the value of any integer that fits in `i64`: signed integers up to 64 bits and unsigned integers up to
+    /// 32 bits.
+    pub fn as_i64(&self) -> Result<i64, ConversionError> {
+        i64::try_from(self)
+    }
+
+    /// returns the value of any unsigned integer up to 64 bits
+    pub fn as_u64(&self) -> Result<u64, ConversionError> {
+        u64::try_from(self)
+    }
+
+    /// returns the value of a `Float32` or `Float64` value
+    pub fn as_f64(&self) -> Result<f64, ConversionError> {
+        f64::try_from(self)
+    }
+
+    /// returns the content of a `Str` value
+    pub fn as_str(&self) -> Result<&str, ConversionError> {
+        <&str>::try_from(self)
+    }
+
+    /// returns the content of a `Bytes` value
+    pub fn as_bytes(&self) -> Result<&[u8], ConversionError> {
+        <&[u8]>::try_from(self)
+    }
+}
+
+macro_rules! encode_from {
+    ($type:ty, $variant:ident) => {
+        impl From<$type> for EncodeType {
+            fn from(value: $type) -> EncodeType {
+                EncodeType::$variant(value)
+            }
+        }
+    };
+}
+
+encode_from!(i8, Int8);
+encode_from!(i16, Int16);
+encode_from!(i32, Int32);
+encode_from!(i64, Int64);
+encode_from!(i128, Int128);
+encode_from!(u8, Uint8);
+encode_from!(u16, Uint16);
+encode_from!(u32, Uint32);
+encode_from!(u64, Uint64);
+encode_from!(u128, Uint128);
+encode_from!(f32, Float32);
+encode_from!(f64, Float64);
+encode_from!(bool, Bool);
+encode_from!(String, Str);
+encode_from!(Vec<u8>, Bytes);
+
+impl From<&str> for EncodeType {
+    fn from(value: &str) -> EncodeType {
+        EncodeType::Str(value.to_owned())
+    }
+}
+
+impl From<&[u8]> for EncodeType {
+    fn from(value: &[u8]) -> EncodeType {
+        EncodeType::Bytes(value.to_vec())
+    }
+}
+
+/// Every value converts without loss. `DecodedData::Optional`, returned for conditional elements, has no field
+/// reference nor predicate to rebuild a `Conditional` from, it converts into `EncodeType::Optional`, which writes the
+/// nested values when present.
+impl From<DecodedData> for EncodeType {
+    fn from(value: DecodedData) -> EncodeType {
+        match value {
+            DecodedData::Int8(value) => EncodeType::Int8(value),
+            DecodedData::Int16(value) => EncodeType::Int16(value),
+            DecodedData::Int32(value) => EncodeType::Int32(value),
+            DecodedData::Int64(value) => EncodeType::Int64(value),
+            DecodedData::Int128(value) => EncodeType::Int128(value),
+            DecodedData::Uint8(value) => EncodeType::Uint8(value),
+            DecodedData::Uint16(value) => EncodeType::Uint16(value),
+            DecodedData::Uint32(value) => EncodeType::Uint32(value),
+            DecodedData::Uint64(value) => EncodeType::Uint64(value),
+            DecodedData::Uint128(value) => EncodeType::Uint128(value),
+            DecodedData::Float32(value) => EncodeType::Float32(value),
+            DecodedData::Float64(value) => EncodeType::Float64(value),
+            DecodedData::Bool(value) => EncodeType::Bool(value),
+            DecodedData::Str(value) => EncodeType::Str(value),
+            DecodedData::Bytes(value) => EncodeType::Bytes(value),
+            DecodedData::BitGroup(values, order) => EncodeType::BitGroup(values, order),
+            DecodedData::Array(items) => {
+                EncodeType::Array(items.into_iter().map(EncodeType::from).collect())
+            }
+            DecodedData::Optional(values) => EncodeType::Optional(
+                values.map(|values| values.into_iter().map(EncodeType::from).collect()),
+            ),
+        }
+    }
+}
+
+/// converts a list of values, stops at the first element that has no value of its own
+fn try_from_list(elements: &[EncodeType]) -> Result<Vec<DecodedData>, ConversionError> {
+    elements.iter().map(DecodedData::try_from).collect()
+}
+
+/// Elements that carry a value are converted into the value the decoder would return for them, length references
+/// are dropped. Constant, reserved, checksum and conditional elements depend on the surrounding elements and
+/// cannot be converted, which is why this direction is a `TryFrom` rather than a `From`: a `From` would have to panic
+/// or make up a value for them. It takes a reference since the encoder converts elements it does not own, like the
+/// values tested by conditions.
+impl TryFrom<&EncodeType> for DecodedData {
+    type Error = ConversionError;
+
+    fn try_from(value: &EncodeType) -> Result<DecodedData, ConversionError> {
+        match value {
+            EncodeType::Int8(value) => Ok(DecodedData::Int8(*value)),
+            EncodeType::Int16(value) => Ok(DecodedData::Int16(*value)),
+            EncodeType::Int32(value) => Ok(DecodedData::Int32(*value)),
+            EncodeType::Int64(value) => Ok(DecodedData::Int64(*value)),
+            EncodeType::Int128(value) => Ok(DecodedData::Int128(*value)),
+            EncodeType::Uint8(value) => Ok(DecodedData::Uint8(*value)),
+            EncodeType::Uint16(value) => Ok(DecodedData::Uint16(*value)),
+            EncodeType::Uint32(value) => Ok(DecodedData::Uint32(*value)),
+            EncodeType::Uint64(value) => Ok(DecodedData::Uint64(*value)),
+            EncodeType::Uint128(value) => Ok(DecodedData::Uint128(*value)),
+            EncodeType::Float32(value) => Ok(DecodedData::Float32(*value)),
+            EncodeType::Float64(value) => Ok(DecodedData::Float64(*value)),
+            EncodeType::Bool(value) => Ok(DecodedData::Bool(*value)),
+            EncodeType::Str(value) | EncodeType::StrRef(value, _) => {
+                Ok(DecodedData::Str(value.clone()))
+            }
+            EncodeType::Bytes(value) | EncodeType::BytesRef(value, _) => {
+                Ok(DecodedData::Bytes(value.clone()))
+            }
+            EncodeType::BitGroup(values, order) => {
+                Ok(DecodedData::BitGroup(values.clone(), *order))
+            }
+            EncodeType::Array(items) | EncodeType::ArrayRef(items, _) => {
+                Ok(DecodedData::Array(try_from_list(items)?))
+            }
+            EncodeType::Optional(values) => Ok(DecodedData::Optional(
+                values.as_deref().map(try_from_list).transpose()?,
+            )),
+            EncodeType::Checksum(..) => Err(ConversionError {
+                expected: "DecodedData",
+                found: "Checksum",
+            }),
+            EncodeType::Const(_) => Err(ConversionError {
+                expected: "DecodedData",
+                found: "Const",
+            }),
+            EncodeType::Reserved(_) => Err(ConversionError {
+                expected: "DecodedData",
+                found: "Reserved",
+            }),
+            EncodeType::Conditional(..) => Err(ConversionError {
+                expected: "DecodedData",
+                found: "Conditional",
+            }),
+        }
+    }
+}
+
+impl TryFrom<EncodeType> for DecodedData {
+    type Error = ConversionError;
+
+    fn try_from(value: EncodeType) -> Result<DecodedData, ConversionError> {
+        DecodedData::try_from(&value)
+    }
+}
+
+impl DecodedData {
+    /// returns the type that decodes this value back from its encoded bytes. Strings and byte-arrays use their
+    /// current length, arrays use the type of their first item, bit-groups use the width of every field.
+    pub fn decode_type(&self) -> DecodeType {
+        match self {
+            DecodedData::Int8(_) => DecodeType::Int8,
+            DecodedData::Int16(_) => DecodeType::Int16,
+            DecodedData::Int32(_) => DecodeType::Int32,
+            DecodedData::Int64(_) => DecodeType::Int64,
+            DecodedData::Int128(_) => DecodeType::Int128,
+            DecodedData::Uint8(_) => DecodeType::Uint8,
+            DecodedData::Uint16(_) => DecodeType::Uint16,
+            DecodedData::Uint32(_) => DecodeType::Uint32,
+            DecodedData::Uint64(_) => DecodeType::Uint64,
+            DecodedData::Uint128(_) => DecodeType::Uint128,
+            DecodedData::Float32(_) => DecodeType::Float32,
+            DecodedData::Float64(_) => DecodeType::Float64,
+            DecodedData::Bool(_) => DecodeType::Bool,
+            DecodedData::Str(value) => DecodeType::Str(value.len()),
+            DecodedData::Bytes(value) => DecodeType::Bytes(value.len()),
+            DecodedData::BitGroup(values, order) => DecodeType::BitGroup(
+                values
+                    .iter()
+                    .map(|value| match value {
+                        BitValue::Unsigned(width, _) => BitField::Unsigned(*width),
+                        BitValue::Signed(width, _) => BitField::Signed(*width),
+                    })
+                    .collect(),
+                *order,
+            ),
+            // an empty array decodes nothing whatever the type of its items
+            DecodedData::Array(items) => DecodeType::Array(
+                Box::new(
+                    items
+                        .first()
+                        .map_or(DecodeType::Bytes(0), DecodedData::decode_type),
+                ),
+                items.len(),
+            ),
+            DecodedData::Optional(values) => DecodeType::Optional(
+                values
+                    .as_ref()
+                    .map(|values| values.iter().map(DecodedData::decode_type).collect()),
+            ),
+        }
+    }
+}
+
+impl From<DecodeOrder> for EncodeOrder {
+    fn from(order: DecodeOrder) -> EncodeOrder {
+        match order {
+            DecodeOrder::Big => EncodeOrder::Big,
+            DecodeOrder::Little => EncodeOrder::Little,
+        }
+    }
+}
+
+impl From<EncodeOrder> for DecodeOrder {
+    fn from(order: EncodeOrder) -> DecodeOrder {
+        match order {
+            EncodeOrder::Big => DecodeOrder::Big,
+            EncodeOrder::Little => DecodeOrder::Little,
+        }
+    }
+}
@@ -1,9 +1,13 @@
 extern crate byteorder;
+use crate::bitfield::{self, BitField, BitOrder, BitValue};
+use crate::checksum::ChecksumKind;
 use byteorder::{BigEndian, LittleEndian, ReadBytesExt};
+use std::ops::Range;
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq)]
 /// `DecodedData` enum is used to wrap the decoded content into one of the supported data-type.
 /// Example: `DecodedData::Str("hello")`, contains the string `hello` decoded back from the encoded bytes.
+/// `DecodedData` implements `PartialEq` but not `Eq` since it holds floating point values.
 pub enum DecodedData {
     /// Int8 data representation
     Int8(i8),
@@ -27,14 +31,58 @@ pub enum DecodedData {
     /// Uint128 data representation
     Uint128(u128),
 
+    /// Float32 data representation
+    Float32(f32),
+    /// Float64 data representation
+    Float64(f64),
+
+    /// Bool data representation
+    Bool(bool),
+
     /// Str data representation
     Str(String),
 
     /// Bytes data representation
     Bytes(Vec<u8>),
+
+    /// BitGroup data representation, contains one value per declared bit-field
+    BitGroup(Vec<BitValue>, BitOrder),
+
+    /// Array data representation, contains one value per decoded item
+    Array(Vec<DecodedData>),
+
+    /// Optional data representation of a conditional element, contains the decoded values of the nested list
+    /// or `None` when the condition was not satisfied
+    Optional(Option<Vec<DecodedData>>),
 }
 
-#[derive(Debug, Clone)]
+impl DecodedData {
+    /// returns the name of the variant holding the value, example: `"Uint32"` for `DecodedData::Uint32(7)`
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            DecodedData::Int8(_) => "Int8",
+            DecodedData::Int16(_) => "Int16",
+            DecodedData::Int32(_) => "Int32",
+            DecodedData::Int64(_) => "Int64",
+            DecodedData::Int128(_) => "Int128",
+            DecodedData::Uint8(_) => "Uint8",
+            DecodedData::Uint16(_) => "Uint16",
+            DecodedData::Uint32(_) => "Uint32",
+            DecodedData::Uint64(_) => "Uint64",
+            DecodedData::Uint128(_) => "Uint128",
+            DecodedData::Float32(_) => "Float32",
+            DecodedData::Float64(_) => "Float64",
+            DecodedData::Bool(_) => "Bool",
+            DecodedData::Str(_) => "Str",
+            DecodedData::Bytes(_) => "Bytes",
+            DecodedData::BitGroup(..) => "BitGroup",
+            DecodedData::Array(_) => "Array",
+            DecodedData::Optional(_) => "Optional",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 /// `DecodeType` enum can be used to tell the decoder who a sequence of bytes at a given offset must be decoded back.
 /// Example: `DecodeType::Uint16` can be used to tell the decoder to interpret the next two bytes as `uint16`.
 pub enum DecodeType {
@@ -60,14 +108,178 @@ pub enum DecodeType {
     /// Uint128 tells the decoder to decode next 16 bytes as unsigned 128-bit integer
     Uint128,
 
+    /// Float32 tells the decoder to decode next 4 bytes as a 32-bit IEEE 754 floating point number
+    Float32,
+    /// Float64 tells the decoder to decode next 8 bytes as a 64-bit IEEE 754 floating point number
+    Float64,
+
+    /// Bool tells the decoder to decode next 1 byte as a boolean, any non-zero byte is true
+    Bool,
+
     /// Str(usize) tells the decoded to decode next `x` bytes as a string
     Str(usize),
 
     /// Str(usize) tells the decoded to decode next `x` bytes as a byte-array
     Bytes(usize),
+
+    /// BitGroup tells the decoder to decode the given sub-byte fields from the next bytes using the given bit order,
+    /// the group occupies as many bytes as needed to hold all the fields
+    BitGroup(Vec<BitField>, BitOrder),
+
+    /// Checksum tells the decoder to read a checksum and verify it against the bytes occupied by the elements
+    /// in the given index range, the stored checksum is returned as `Uint8`, `Uint16` or `Uint32` depending on its size
+    Checksum(ChecksumKind, Range<usize>),
+
+    /// Const(bytes) tells the decoder to verify that the next bytes match the given content, like magic numbers
+    /// or version constants. Const elements are left out of the decoded values.
+    Const(Vec<u8>),
+
+    /// Reserved(usize) tells the decoder to verify that the next `x` bytes are zero.
+    /// Reserved elements are left out of the decoded values.
+    Reserved(usize),
+
+    /// Padding(usize) tells the decoder to skip the next `x` bytes without looking at their content, it can be used
+    /// in place of `Reserved` to ignore non-zero reserved bytes. Padding elements are left out of the decoded values.
+    Padding(usize),
+
+    /// StrRef tells the decoder to decode a string whose length in bytes is stored in the integer element it refers to
+    /// Example: `&[DecodeType::Uint16, DecodeType::StrRef(FieldRef::Index(0))]` reads a 16-bit length followed by a string.
+    StrRef(FieldRef),
+
+    /// BytesRef tells the decoder to decode a byte-array whose length is stored in the integer element it refers to
+    BytesRef(FieldRef),
+
+    /// Array(type, usize) tells the decoder to decode `x` items of the given type, the items cannot refer to other elements
+    Array(Box<DecodeType>, usize),
+
+    /// ArrayRef tells the decoder to decode items of the given type, the number of items is stored in the integer
+    /// element it refers to
+    ArrayRef(Box<DecodeType>, FieldRef),
+
+    /// Conditional tells the decoder to decode the nested list of elements only when the value of the element it
+    /// refers to satisfies the predicate. References inside the nested list refer to the elements of the nested list
+    /// by index, names cannot be resolved there.
+    /// Example: `DecodeType::Conditional(FieldRef::Index(0), Predicate::BitsSet(0x01), vec![DecodeType::Uint32])`
+    /// reads a 32-bit integer only when the lowest bit of the first element is set.
+    Conditional(FieldRef, Predicate, Vec<DecodeType>),
+
+    /// Optional tells the decoder to decode the nested list of elements when present and nothing otherwise, it can be
+    /// used for sections whose presence is known in advance. The decoded value is a `DecodedData::Optional`.
+    Optional(Option<Vec<DecodeType>>),
+}
+
+impl DecodeType {
+    /// returns the number of bytes occupied by the element when it does not depend on the decoded content,
+    /// `None` for elements whose length is stored in another element and for conditional elements.
+    pub fn fixed_size(&self) -> Option<usize> {
+        match self {
+            DecodeType::Int8 | DecodeType::Uint8 | DecodeType::Bool => Some(1),
+            DecodeType::Int16 | DecodeType::Uint16 => Some(2),
+            DecodeType::Int32 | DecodeType::Uint32 | DecodeType::Float32 => Some(4),
+            DecodeType::Int64 | DecodeType::Uint64 | DecodeType::Float64 => Some(8),
+            DecodeType::Int128 | DecodeType::Uint128 => Some(16),
+            DecodeType::Str(size)
+            | DecodeType::Bytes(size)
+            | DecodeType::Reserved(size)
+            | DecodeType::Padding(size) => Some(*size),
+            DecodeType::Const(content) => Some(content.len()),
+            DecodeType::BitGroup(fields, _) => Some(bitfield::group_size(
+                fields.iter().map(|field| field.width() as usize).sum(),
+            )),
+            DecodeType::Checksum(kind, _) => Some(kind.size()),
+            DecodeType::Array(item, count) => item.fixed_size()?.checked_mul(*count),
+            DecodeType::Optional(None) => Some(0),
+            DecodeType::Optional(Some(types)) => types
+                .iter()
+                .try_fold(0usize, |size, kind| size.checked_add(kind.fixed_size()?)),
+            DecodeType::StrRef(_)
+            | DecodeType::BytesRef(_)
+            | DecodeType::ArrayRef(..)
+            | DecodeType::Conditional(..) => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// `FieldRef` refers to an element placed earlier in the same list of elements, it is used by elements whose length
+/// is stored in another element.
+/// Example: `FieldRef::Index(2)` refers to the third element of the list.
+pub enum FieldRef {
+    /// Index refers to an element by its position in the list, constant and reserved elements included
+    Index(usize),
+    /// Name refers to a named field of a schema, it is resolved by `schema::Schema::encode` and
+    /// `schema::Schema::decode`. Lists of bare types have no names, `decode_packed` and `encode_packed` return an
+    /// error for names.
+    Name(String),
 }
 
 #[derive(Debug, Clone)]
+/// `Predicate` is the test applied by conditional elements to the value of the element they refer to.
+/// Example: `Predicate::Equals(DecodedData::Uint8(2))` is satisfied when the referred value is `Uint8(2)`.
+pub enum Predicate {
+    /// Equals is satisfied when the referred value is equal to the given one, both must be of the same type
+    Equals(DecodedData),
+    /// NotEquals is satisfied when the referred value is not equal to the given one
+    NotEquals(DecodedData),
+    /// BitsSet is satisfied when all the bits of the mask are set in the referred integer, never when the referred
+    /// value is not an integer, like a bit-group
+    BitsSet(u128),
+    /// BitsClear is satisfied when none of the bits of the mask are set in the referred integer, never when the
+    /// referred value is not an integer
+    BitsClear(u128),
+    /// Custom is satisfied when the given function returns true for the referred value
+    Custom(fn(&DecodedData) -> bool),
+}
+
+impl Predicate {
+    /// returns true if the given value satisfies the predicate
+    pub fn test(&self, value: &DecodedData) -> bool {
+        match self {
+            Predicate::Equals(expected) => value == expected,
+            Predicate::NotEquals(expected) => value != expected,
+            Predicate::BitsSet(mask) => {
+                integer_bits(value).is_some_and(|bits| bits & mask == *mask)
+            }
+            Predicate::BitsClear(mask) => integer_bits(value).is_some_and(|bits| bits & mask == 0),
+            Predicate::Custom(function) => function(value),
+        }
+    }
+}
+
+impl PartialEq for Predicate {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Predicate::Equals(a), Predicate::Equals(b)) => a == b,
+            (Predicate::NotEquals(a), Predicate::NotEquals(b)) => a == b,
+            (Predicate::BitsSet(a), Predicate::BitsSet(b)) => a == b,
+            (Predicate::BitsClear(a), Predicate::BitsClear(b)) => a == b,
+            (Predicate::Custom(a), Predicate::Custom(b)) => std::ptr::fn_addr_eq(*a, *b),
+            _ => false,
+        }
+    }
+}
+
+impl Eq for Predicate {}
+
+/// returns the bits of an integer value, signed integers are taken as two's complement of their own width
+#[inline]
+fn integer_bits(value: &DecodedData) -> Option<u128> {
+    match value {
+        DecodedData::Int8(value) => Some(*value as u8 as u128),
+        DecodedData::Int16(value) => Some(*value as u16 as u128),
+        DecodedData::Int32(value) => Some(*value as u32 as u128),
+        DecodedData::Int64(value) => Some(*value as u64 as u128),
+        DecodedData::Int128(value) => Some(*value as u128),
+        DecodedData::Uint8(value) => Some(*value as u128),
+        DecodedData::Uint16(value) => Some(*value as u128),
+        DecodedData::Uint32(value) => Some(*value as u128),
+        DecodedData::Uint64(value) => Some(*value as u128),
+        DecodedData::Uint128(value) => Some(*value),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 /// `DecodeOrder` is used to specify how signed and unsigned integers encoded as bytes must be assumed w.r.t byte-order for decoding.
 /// Example `DecodeOrder::Little` assumes all the bytes to be decoded are in little endian byte order.
 pub enum DecodeOrder {
@@ -85,6 +297,22 @@ pub enum DecodeError {
     InvalidData(usize),
     /// IndexOutOfBounds occurs when offset > size of the byte array.
     IndexOutOfBounds,
+    /// ChecksumMismatch occurs when the checksum stored at `index` does not match the one computed over the covered bytes.
+    ChecksumMismatch {
+        /// index of the checksum element
+        index: usize,
+        /// checksum found in the byte array
+        expected: u32,
+        /// checksum computed over the covered bytes
+        computed: u32,
+    },
+    /// ConstMismatch occurs when the bytes at the given index do not match the expected constant.
+    ConstMismatch(usize),
+    /// NonZeroReserved occurs when the reserved region at the given index contains non-zero bytes.
+    NonZeroReserved(usize),
+    /// NonContiguous occurs when a record spans chunks of a `Buf` that cannot be read without consuming it,
+    /// see `buf::decode_buf`.
+    NonContiguous,
 }
 
 #[inline]
@@ -159,12 +387,29 @@ fn decode_u128(mut array: &[u8], decode_order: DecodeOrder) -> Option<u128> {
     .ok()
 }
 
+#[inline]
+fn decode_f32(mut array: &[u8], decode_order: DecodeOrder) -> Option<f32> {
+    match decode_order {
+        DecodeOrder::Big => array.read_f32::<BigEndian>(),
+        DecodeOrder::Little => array.read_f32::<LittleEndian>(),
+    }
+    .ok()
+}
+
+#[inline]
+fn decode_f64(mut array: &[u8], decode_order: DecodeOrder) -> Option<f64> {
+    match decode_order {
+        DecodeOrder::Big => array.read_f64::<BigEndian>(),
+        DecodeOrder::Little => array.read_f64::<LittleEndian>(),
+    }
+    .ok()
+}
+
 #[inline]
 fn decode_string(array: &[u8]) -> Option<String> {
     String::from_utf8(array.to_vec()).ok()
 }
 
-#[allow(clippy::question_mark)]
 /// `decode_packed` function decoded a given byte-array into list of required values specified in `types` parameter.
 /// Returns the list of decoded values `Vec<DecodedData>` or `DecodeError`.
 ///
@@ -210,187 +455,571 @@ pub fn decode_packed(
     buffer: &[u8],
     decode_order: DecodeOrder,
 ) -> Result<Vec<DecodedData>, DecodeError> {
+    decode_list(types, buffer, 0, decode_order)
+        .0
+        .map(|list| list.values)
+}
+
+/// `decode_prefix` decodes the given types from the start of the buffer like `decode_packed` and also returns the
+/// number of bytes they use, so that the bytes following them can be decoded next. It is meant for streams where
+/// records are written one after the other, a buffer holding only a part of the record returns
+/// `DecodeError::IndexOutOfBounds`.
+pub fn decode_prefix(
+    types: &[DecodeType],
+    buffer: &[u8],
+    decode_order: DecodeOrder,
+) -> Result<(Vec<DecodedData>, usize), DecodeError> {
+    let (result, consumed) = decode_list(types, buffer, 0, decode_order);
+    result.map(|list| (list.values, consumed))
+}
+
+/// `ListEntry` is implemented by the elements of a list that can be decoded, it gives access to the type of the
+/// element and to its name, if any.
+pub(crate) trait ListEntry {
+    fn kind(&self) -> &DecodeType;
+    fn name(&self) -> Option<&str>;
+    /// byte order of the element, `None` when the element uses the byte order of the list
+    fn order(&self) -> Option<&DecodeOrder> {
+        None
+    }
+}
+
+impl ListEntry for DecodeType {
+    fn kind(&self) -> &DecodeType {
+        self
+    }
+
+    fn name(&self) -> Option<&str> {
+        None
+    }
+}
+
+/// `DecodedList` is the result of `decode_list`.
+pub(crate) struct DecodedList {
+    /// decoded values, constant and reserved elements are left out
+    pub values: Vec<DecodedData>,
+    /// position in `values` of every element, `None` for the elements left out
+    pub positions: Vec<Option<usize>>,
+    /// offset in the buffer where every element starts
+    #[cfg_attr(not(feature = "bytes"), allow(dead_code))]
+    pub offsets: Vec<usize>,
+}
+
+/// decodes a list of elements starting at `offset`, references inside the list refer to the elements of the same list.
+/// Returns the decoded values and the position of every element among them, along with the number of bytes occupied
+/// by the list.
+pub(crate) fn decode_list<E: ListEntry>(
+    entries: &[E],
+    buffer: &[u8],
+    offset: usize,
+    decode_order: DecodeOrder,
+) -> (Result<DecodedList, DecodeError>, usize) {
     let mut decoded_data = vec![];
-    let mut last_read = 0;
-
-    for (idx, entry) in types.iter().enumerate() {
-        let (result, size_offset) = match entry {
-            DecodeType::Int8 => {
-                if buffer.len() < last_read + 1 {
-                    (Err(DecodeError::IndexOutOfBounds), 1)
-                } else {
-                    (Ok(DecodedData::Int8(buffer[last_read] as i8)), 1)
-                }
+    let mut last_read = offset;
+
+    // start offset and decoded position of every element, used by checksums and references
+    let mut offsets = Vec::with_capacity(entries.len());
+    let mut positions = Vec::with_capacity(entries.len());
+
+    for (idx, entry) in entries.iter().enumerate() {
+        offsets.push(last_read);
+        let scope = DecodeScope {
+            entries,
+            offsets: &offsets,
+            positions: &positions,
+            decoded: &decoded_data,
+        };
+        let (result, size_offset) = decode_element(
+            entry.kind(),
+            idx,
+            buffer,
+            last_read,
+            entry.order().unwrap_or(&decode_order).clone(),
+            Some(&scope),
+        );
+
+        if let Err(err) = result {
+            return (Err(err), last_read - offset);
+        }
+
+        last_read += size_offset;
+        // constant and reserved elements are verified but left out of the decoded values
+        match result.unwrap() {
+            Some(decoded) => {
+                positions.push(Some(decoded_data.len()));
+                decoded_data.push(decoded);
             }
+            None => positions.push(None),
+        }
+    }
 
-            DecodeType::Int16 => {
-                if buffer.len() < last_read + 2 {
-                    (Err(DecodeError::IndexOutOfBounds), 2)
-                } else {
-                    let decoded_result =
-                        decode_i16(&buffer[last_read..last_read + 2], decode_order.clone());
-                    (
-                        decoded_result.map_or_else(
-                            || Err(DecodeError::InvalidData(idx)),
-                            |decoded| Ok(DecodedData::Int16(decoded)),
-                        ),
-                        2,
-                    )
-                }
+    let list = DecodedList {
+        values: decoded_data,
+        positions,
+        offsets,
+    };
+    (Ok(list), last_read - offset)
+}
+
+/// `DecodeScope` gives access to the elements of a list decoded so far, it is used to resolve references
+/// and to locate the bytes covered by checksums.
+struct DecodeScope<'a, E> {
+    entries: &'a [E],
+    offsets: &'a [usize],
+    positions: &'a [Option<usize>],
+    decoded: &'a [DecodedData],
+}
+
+impl<E: ListEntry> DecodeScope<'_, E> {
+    /// returns the decoded value of the element referred by `field`, the element must be placed before `idx`
+    fn value(&self, field: &FieldRef, idx: usize) -> Option<&DecodedData> {
+        let target = match field {
+            FieldRef::Index(target) => *target,
+            FieldRef::Name(name) => self.entries[..idx]
+                .iter()
+                .position(|entry| entry.name() == Some(name.as_str()))?,
+        };
+
+        if target >= idx {
+            return None;
+        }
+
+        self.positions
+            .get(target)
+            .copied()
+            .flatten()
+            .map(|position| &self.decoded[position])
+    }
+
+    /// returns the value of the integer element referred by `field` as a length
+    fn length(&self, field: &FieldRef, idx: usize) -> Option<usize> {
+        match self.value(field, idx)? {
+            DecodedData::Int8(value) => usize::try_from(*value).ok(),
+            DecodedData::Int16(value) => usize::try_from(*value).ok(),
+            DecodedData::Int32(value) => usize::try_from(*value).ok(),
+            DecodedData::Int64(value) => usize::try_from(*value).ok(),
+            DecodedData::Int128(value) => usize::try_from(*value).ok(),
+            DecodedData::Uint8(value) => Some(*value as usize),
+            DecodedData::Uint16(value) => Some(*value as usize),
+            DecodedData::Uint32(value) => usize::try_from(*value).ok(),
+            DecodedData::Uint64(value) => usize::try_from(*value).ok(),
+            DecodedData::Uint128(value) => usize::try_from(*value).ok(),
+            _ => None,
+        }
+    }
+}
+
+/// decodes `count` items of the given type starting at `offset`, the items cannot refer to other elements.
+fn decode_array(
+    item: &DecodeType,
+    count: usize,
+    idx: usize,
+    buffer: &[u8],
+    offset: usize,
+    decode_order: DecodeOrder,
+) -> (Result<Option<DecodedData>, DecodeError>, usize) {
+    let mut items = vec![];
+    let mut last_read = offset;
+
+    for _ in 0..count {
+        let (result, size_offset) =
+            decode_element::<DecodeType>(item, idx, buffer, last_read, decode_order.clone(), None);
+
+        if let Err(err) = result {
+            return (Err(err), last_read - offset);
+        }
+
+        last_read += size_offset;
+        if let Some(decoded) = result.unwrap() {
+            items.push(decoded);
+        }
+    }
+
+    (Ok(Some(DecodedData::Array(items))), last_read - offset)
+}
+
+/// returns true when the buffer holds less than `size` bytes from `offset`, sizes that overflow included
+fn out_of_bounds(buffer: &[u8], offset: usize, size: usize) -> bool {
+    match offset.checked_add(size) {
+        Some(end) => end > buffer.len(),
+        None => true,
+    }
+}
+
+/// decodes a single element starting at `last_read`. Returns the decoded value, or `None` for elements that are
+/// left out of the decoded values, along with the number of bytes occupied by the element. `scope` is `None` for
+/// the items of an array.
+fn decode_element<E: ListEntry>(
+    entry: &DecodeType,
+    idx: usize,
+    buffer: &[u8],
+    last_read: usize,
+    decode_order: DecodeOrder,
+    scope: Option<&DecodeScope<E>>,
+) -> (Result<Option<DecodedData>, DecodeError>, usize) {
+    match entry {
+        DecodeType::Int8 => {
+            if buffer.len() < last_read + 1 {
+                (Err(DecodeError::IndexOutOfBounds), 1)
+            } else {
+                (Ok(Some(DecodedData::Int8(buffer[last_read] as i8))), 1)
             }
+        }
 
-            DecodeType::Int32 => {
-                if buffer.len() < last_read + 4 {
-                    (Err(DecodeError::IndexOutOfBounds), 4)
-                } else {
-                    let decoded_result =
-                        decode_i32(&buffer[last_read..last_read + 4], decode_order.clone());
-                    (
-                        decoded_result.map_or_else(
-                            || Err(DecodeError::InvalidData(idx)),
-                            |decoded| Ok(DecodedData::Int32(decoded)),
-                        ),
-                        4,
-                    )
-                }
+        DecodeType::Int16 => {
+            if buffer.len() < last_read + 2 {
+                (Err(DecodeError::IndexOutOfBounds), 2)
+            } else {
+                let decoded_result =
+                    decode_i16(&buffer[last_read..last_read + 2], decode_order.clone());
+                (
+                    decoded_result.map_or_else(
+                        || Err(DecodeError::InvalidData(idx)),
+                        |decoded| Ok(Some(DecodedData::Int16(decoded))),
+                    ),
+                    2,
+                )
             }
+        }
 
-            DecodeType::Int64 => {
-                if buffer.len() < last_read + 8 {
-                    (Err(DecodeError::IndexOutOfBounds), 8)
-                } else {
-                    let decoded_result =
-                        decode_i64(&buffer[last_read..last_read + 8], decode_order.clone());
-                    (
-                        decoded_result.map_or_else(
-                            || Err(DecodeError::InvalidData(idx)),
-                            |decoded| Ok(DecodedData::Int64(decoded)),
-                        ),
-                        8,
-                    )
-                }
+        DecodeType::Int32 => {
+            if buffer.len() < last_read + 4 {
+                (Err(DecodeError::IndexOutOfBounds), 4)
+            } else {
+                let decoded_result =
+                    decode_i32(&buffer[last_read..last_read + 4], decode_order.clone());
+                (
+                    decoded_result.map_or_else(
+                        || Err(DecodeError::InvalidData(idx)),
+                        |decoded| Ok(Some(DecodedData::Int32(decoded))),
+                    ),
+                    4,
+                )
             }
+        }
 
-            DecodeType::Int128 => {
-                if buffer.len() < last_read + 16 {
-                    (Err(DecodeError::IndexOutOfBounds), 16)
-                } else {
-                    let decoded_result =
-                        decode_i128(&buffer[last_read..last_read + 16], decode_order.clone());
-                    (
-                        decoded_result.map_or_else(
-                            || Err(DecodeError::InvalidData(idx)),
-                            |decoded| Ok(DecodedData::Int128(decoded)),
-                        ),
-                        16,
-                    )
-                }
+        DecodeType::Int64 => {
+            if buffer.len() < last_read + 8 {
+                (Err(DecodeError::IndexOutOfBounds), 8)
+            } else {
+                let decoded_result =
+                    decode_i64(&buffer[last_read..last_read + 8], decode_order.clone());
+                (
+                    decoded_result.map_or_else(
+                        || Err(DecodeError::InvalidData(idx)),
+                        |decoded| Ok(Some(DecodedData::Int64(decoded))),
+                    ),
+                    8,
+                )
             }
+        }
 
-            DecodeType::Uint8 => {
-                if buffer.len() < last_read + 1 {
-                    (Err(DecodeError::IndexOutOfBounds), 1)
-                } else {
-                    (Ok(DecodedData::Uint8(buffer[last_read])), 1)
-                }
+        DecodeType::Int128 => {
+            if buffer.len() < last_read + 16 {
+                (Err(DecodeError::IndexOutOfBounds), 16)
+            } else {
+                let decoded_result =
+                    decode_i128(&buffer[last_read..last_read + 16], decode_order.clone());
+                (
+                    decoded_result.map_or_else(
+                        || Err(DecodeError::InvalidData(idx)),
+                        |decoded| Ok(Some(DecodedData::Int128(decoded))),
+                    ),
+                    16,
+                )
             }
+        }
 
-            DecodeType::Uint16 => {
-                if buffer.len() < last_read + 2 {
-                    (Err(DecodeError::IndexOutOfBounds), 2)
-                } else {
-                    let decoded_result =
-                        decode_u16(&buffer[last_read..last_read + 2], decode_order.clone());
-                    (
-                        decoded_result.map_or_else(
-                            || Err(DecodeError::InvalidData(idx)),
-                            |decoded| Ok(DecodedData::Uint16(decoded)),
-                        ),
-                        2,
-                    )
-                }
+        DecodeType::Uint8 => {
+            if buffer.len() < last_read + 1 {
+                (Err(DecodeError::IndexOutOfBounds), 1)
+            } else {
+                (Ok(Some(DecodedData::Uint8(buffer[last_read]))), 1)
             }
+        }
 
-            DecodeType::Uint32 => {
-                if buffer.len() < last_read + 4 {
-                    (Err(DecodeError::IndexOutOfBounds), 4)
-                } else {
-                    let decoded_result =
-                        decode_u32(&buffer[last_read..last_read + 4], decode_order.clone());
-                    (
-                        decoded_result.map_or_else(
-                            || Err(DecodeError::InvalidData(idx)),
-                            |decoded| Ok(DecodedData::Uint32(decoded)),
-                        ),
-                        4,
-                    )
-                }
+        DecodeType::Uint16 => {
+            if buffer.len() < last_read + 2 {
+                (Err(DecodeError::IndexOutOfBounds), 2)
+            } else {
+                let decoded_result =
+                    decode_u16(&buffer[last_read..last_read + 2], decode_order.clone());
+                (
+                    decoded_result.map_or_else(
+                        || Err(DecodeError::InvalidData(idx)),
+                        |decoded| Ok(Some(DecodedData::Uint16(decoded))),
+                    ),
+                    2,
+                )
             }
+        }
 
-            DecodeType::Uint64 => {
-                if buffer.len() < last_read + 8 {
-                    (Err(DecodeError::IndexOutOfBounds), 8)
-                } else {
-                    let decoded_result =
-                        decode_u64(&buffer[last_read..last_read + 8], decode_order.clone());
-                    (
-                        decoded_result.map_or_else(
-                            || Err(DecodeError::InvalidData(idx)),
-                            |decoded| Ok(DecodedData::Uint64(decoded)),
-                        ),
-                        8,
-                    )
-                }
+        DecodeType::Uint32 => {
+            if buffer.len() < last_read + 4 {
+                (Err(DecodeError::IndexOutOfBounds), 4)
+            } else {
+                let decoded_result =
+                    decode_u32(&buffer[last_read..last_read + 4], decode_order.clone());
+                (
+                    decoded_result.map_or_else(
+                        || Err(DecodeError::InvalidData(idx)),
+                        |decoded| Ok(Some(DecodedData::Uint32(decoded))),
+                    ),
+                    4,
+                )
             }
+        }
 
-            DecodeType::Uint128 => {
-                if buffer.len() < last_read + 16 {
-                    (Err(DecodeError::IndexOutOfBounds), 16)
-                } else {
-                    let decoded_result =
-                        decode_u128(&buffer[last_read..last_read + 16], decode_order.clone());
-                    (
-                        decoded_result.map_or_else(
-                            || Err(DecodeError::InvalidData(idx)),
-                            |decoded| Ok(DecodedData::Uint128(decoded)),
-                        ),
-                        16,
-                    )
-                }
+        DecodeType::Uint64 => {
+            if buffer.len() < last_read + 8 {
+                (Err(DecodeError::IndexOutOfBounds), 8)
+            } else {
+                let decoded_result =
+                    decode_u64(&buffer[last_read..last_read + 8], decode_order.clone());
+                (
+                    decoded_result.map_or_else(
+                        || Err(DecodeError::InvalidData(idx)),
+                        |decoded| Ok(Some(DecodedData::Uint64(decoded))),
+                    ),
+                    8,
+                )
+            }
+        }
+
+        DecodeType::Uint128 => {
+            if buffer.len() < last_read + 16 {
+                (Err(DecodeError::IndexOutOfBounds), 16)
+            } else {
+                let decoded_result =
+                    decode_u128(&buffer[last_read..last_read + 16], decode_order.clone());
+                (
+                    decoded_result.map_or_else(
+                        || Err(DecodeError::InvalidData(idx)),
+                        |decoded| Ok(Some(DecodedData::Uint128(decoded))),
+                    ),
+                    16,
+                )
+            }
+        }
+
+        DecodeType::Float32 => {
+            if buffer.len() < last_read + 4 {
+                (Err(DecodeError::IndexOutOfBounds), 4)
+            } else {
+                let decoded_result =
+                    decode_f32(&buffer[last_read..last_read + 4], decode_order.clone());
+                (
+                    decoded_result.map_or_else(
+                        || Err(DecodeError::InvalidData(idx)),
+                        |decoded| Ok(Some(DecodedData::Float32(decoded))),
+                    ),
+                    4,
+                )
+            }
+        }
+
+        DecodeType::Float64 => {
+            if buffer.len() < last_read + 8 {
+                (Err(DecodeError::IndexOutOfBounds), 8)
+            } else {
+                let decoded_result =
+                    decode_f64(&buffer[last_read..last_read + 8], decode_order.clone());
+                (
+                    decoded_result.map_or_else(
+                        || Err(DecodeError::InvalidData(idx)),
+                        |decoded| Ok(Some(DecodedData::Float64(decoded))),
+                    ),
+                    8,
+                )
+            }
+        }
+
+        DecodeType::Bool => {
+            if buffer.len() < last_read + 1 {
+                (Err(DecodeError::IndexOutOfBounds), 1)
+            } else {
+                (Ok(Some(DecodedData::Bool(buffer[last_read] != 0))), 1)
             }
+        }
 
-            DecodeType::Str(size) => {
-                if buffer.len() < last_read + *size {
-                    (Err(DecodeError::IndexOutOfBounds), *size)
-                } else {
-                    let decoded_result = decode_string(&buffer[last_read..last_read + *size]);
+        DecodeType::Str(size) => {
+            if out_of_bounds(buffer, last_read, *size) {
+                (Err(DecodeError::IndexOutOfBounds), *size)
+            } else {
+                let decoded_result = decode_string(&buffer[last_read..last_read + *size]);
+                (
+                    decoded_result.map_or_else(
+                        || Err(DecodeError::InvalidData(idx)),
+                        |decoded| Ok(Some(DecodedData::Str(decoded))),
+                    ),
+                    *size,
+                )
+            }
+        }
+
+        DecodeType::Bytes(size) => {
+            if out_of_bounds(buffer, last_read, *size) {
+                (Err(DecodeError::IndexOutOfBounds), *size)
+            } else {
+                let vec_repr = buffer[last_read..last_read + *size].to_vec();
+                (Ok(Some(DecodedData::Bytes(vec_repr))), *size)
+            }
+        }
+
+        DecodeType::BitGroup(fields, order) => {
+            let bits: usize = fields.iter().map(|field| field.width() as usize).sum();
+            let size = bitfield::group_size(bits);
+            if buffer.len() < last_read + size {
+                (Err(DecodeError::IndexOutOfBounds), size)
+            } else {
+                let decoded_result =
+                    bitfield::unpack_bits(fields, &buffer[last_read..last_read + size], *order);
+                (
+                    decoded_result.map_or_else(
+                        || Err(DecodeError::InvalidData(idx)),
+                        |decoded| Ok(Some(DecodedData::BitGroup(decoded, *order))),
+                    ),
+                    size,
+                )
+            }
+        }
+
+        DecodeType::Checksum(kind, range) => {
+            let size = kind.size();
+            let offsets = scope.map_or(&[][..], |scope| scope.offsets);
+            if range.start > range.end || range.end > idx || range.end >= offsets.len() {
+                (Err(DecodeError::InvalidData(idx)), size)
+            } else if buffer.len() < last_read + size {
+                (Err(DecodeError::IndexOutOfBounds), size)
+            } else {
+                let array = &buffer[last_read..last_read + size];
+                let expected = match size {
+                    1 => Some(array[0] as u32),
+                    2 => decode_u16(array, decode_order.clone()).map(|value| value as u32),
+                    _ => decode_u32(array, decode_order.clone()),
+                };
+                let covered = offsets[range.start]..offsets[range.end];
+                let computed = kind.compute(&buffer[covered]);
+                let result = match expected {
+                    None => Err(DecodeError::InvalidData(idx)),
+                    Some(expected) if expected != computed => Err(DecodeError::ChecksumMismatch {
+                        index: idx,
+                        expected,
+                        computed,
+                    }),
+                    Some(expected) => Ok(Some(match size {
+                        1 => DecodedData::Uint8(expected as u8),
+                        2 => DecodedData::Uint16(expected as u16),
+                        _ => DecodedData::Uint32(expected),
+                    })),
+                };
+                (result, size)
+            }
+        }
+
+        DecodeType::Const(content) => {
+            if buffer.len() < last_read + content.len() {
+                (Err(DecodeError::IndexOutOfBounds), content.len())
+            } else if buffer[last_read..last_read + content.len()] != content[..] {
+                (Err(DecodeError::ConstMismatch(idx)), content.len())
+            } else {
+                (Ok(None), content.len())
+            }
+        }
+
+        DecodeType::Reserved(size) => {
+            if out_of_bounds(buffer, last_read, *size) {
+                (Err(DecodeError::IndexOutOfBounds), *size)
+            } else if buffer[last_read..last_read + *size]
+                .iter()
+                .any(|byte| *byte != 0)
+            {
+                (Err(DecodeError::NonZeroReserved(idx)), *size)
+            } else {
+                (Ok(None), *size)
+            }
+        }
+
+        DecodeType::Padding(size) => {
+            if out_of_bounds(buffer, last_read, *size) {
+                (Err(DecodeError::IndexOutOfBounds), *size)
+            } else {
+                (Ok(None), *size)
+            }
+        }
+
+        DecodeType::StrRef(field) => match scope.and_then(|scope| scope.length(field, idx)) {
+            None => (Err(DecodeError::InvalidData(idx)), 0),
+            Some(size) if buffer.len() - last_read < size => {
+                (Err(DecodeError::IndexOutOfBounds), size)
+            }
+            Some(size) => {
+                let decoded_result = decode_string(&buffer[last_read..last_read + size]);
+                (
+                    decoded_result.map_or_else(
+                        || Err(DecodeError::InvalidData(idx)),
+                        |decoded| Ok(Some(DecodedData::Str(decoded))),
+                    ),
+                    size,
+                )
+            }
+        },
+
+        DecodeType::BytesRef(field) => match scope.and_then(|scope| scope.length(field, idx)) {
+            None => (Err(DecodeError::InvalidData(idx)), 0),
+            Some(size) if buffer.len() - last_read < size => {
+                (Err(DecodeError::IndexOutOfBounds), size)
+            }
+            Some(size) => {
+                let vec_repr = buffer[last_read..last_read + size].to_vec();
+                (Ok(Some(DecodedData::Bytes(vec_repr))), size)
+            }
+        },
+
+        DecodeType::Conditional(field, predicate, types) => {
+            match scope.and_then(|scope| scope.value(field, idx)) {
+                None => (Err(DecodeError::InvalidData(idx)), 0),
+                Some(value) if !predicate.test(value) => (Ok(Some(DecodedData::Optional(None))), 0),
+                Some(_) => {
+                    let (result, size) = decode_list(types, buffer, last_read, decode_order);
                     (
-                        decoded_result.map_or_else(
-                            || Err(DecodeError::InvalidData(idx)),
-                            |decoded| Ok(DecodedData::Str(decoded)),
-                        ),
-                        *size,
+                        result.map(|list| Some(DecodedData::Optional(Some(list.values)))),
+                        size,
                     )
                 }
             }
+        }
 
-            DecodeType::Bytes(size) => {
-                if buffer.len() < last_read + *size {
-                    (Err(DecodeError::IndexOutOfBounds), *size)
-                } else {
-                    let vec_repr = buffer[last_read..last_read + *size].to_vec();
-                    (Ok(DecodedData::Bytes(vec_repr)), *size)
-                }
+        DecodeType::Optional(types) => match types {
+            None => (Ok(Some(DecodedData::Optional(None))), 0),
+            Some(types) => {
+                let (result, size) = decode_list(types, buffer, last_read, decode_order);
+                (
+                    result.map(|list| Some(DecodedData::Optional(Some(list.values)))),
+                    size,
+                )
             }
-        };
+        },
 
-        if let Err(err) = result {
-            return Err(err);
+        DecodeType::Array(item, count) => {
+            decode_array(item, *count, idx, buffer, last_read, decode_order)
         }
 
-        last_read += size_offset;
-        decoded_data.push(result.unwrap());
+        DecodeType::ArrayRef(item, field) => {
+            match scope.and_then(|scope| scope.length(field, idx)) {
+                None => (Err(DecodeError::InvalidData(idx)), 0),
+                // the count comes from the buffer: there cannot be more items of a fixed size than fit in the
+                // remaining bytes, and the count of empty items is bounded by the remaining bytes as well so that
+                // a corrupt count cannot make the decoder allocate without limit
+                Some(count)
+                    if count
+                        > buffer.len().saturating_sub(last_read)
+                            / item.fixed_size().unwrap_or(1).max(1) =>
+                {
+                    (Err(DecodeError::IndexOutOfBounds), count)
+                }
+                Some(count) => decode_array(item, count, idx, buffer, last_read, decode_order),
+            }
+        }
     }
-
-    Ok(decoded_data)
 }
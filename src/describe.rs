@@ -0,0 +1,480 @@
+//! `describe` implements the self-describing mode, where the encoded values are preceded by a compact descriptor of
+//! their types and byte order so that they can be decoded without knowing the layout in advance.
+//!
+//! The descriptor starts with a byte holding the format version in its high nibble and the byte order in its lowest
+//! bit (`0` big, `1` little), followed by the number of elements as an unsigned LEB128 varint and by one entry per
+//! element. Every entry is a tag byte followed by the parameters of the type, sizes, counts and indices are varints:
+//!
+//! - `0x01` to `0x05`: `Int8` to `Int128`
+//! - `0x06` to `0x0a`: `Uint8` to `Uint128`
+//! - `0x0b`, `0x0c`: `Float32`, `Float64`
+//! - `0x0d`: `Bool`
+//! - `0x10` size: `Str`
+//! - `0x11` size: `Bytes`
+//! - `0x12` index of the length element: `StrRef`
+//! - `0x13` index of the length element: `BytesRef`
+//! - `0x14` count, item entry: `Array`
+//! - `0x15` index of the count element, item entry: `ArrayRef`
+//! - `0x16` bit order (`0` msb, `1` lsb), count, one byte per field holding its width and `0x80` when signed:
+//!   `BitGroup`
+//! - `0x17` checksum kind in the order of the `ChecksumKind` variants, start and end of the range: `Checksum`
+//! - `0x18` size, content: `Const`
+//! - `0x19` size: `Reserved`
+//! - `0x1a` size: `Padding`
+//! - `0x1b` `0` when absent, otherwise `1`, count and entries: `Optional`
+//!
+//! Conditional elements and references by name cannot be described.
+
+use crate::bitfield::{BitField, BitOrder, BitValue};
+use crate::checksum::ChecksumKind;
+use crate::decoder::{self, DecodeError, DecodeOrder, DecodeType, DecodedData, FieldRef};
+use crate::encoder::{self, EncodeError, EncodeOrder, EncodeType};
+use crate::framing::{decode_varint, encode_varint};
+use std::fmt;
+
+/// version of the descriptor format written by `encode_descriptor`
+pub const VERSION: u8 = 1;
+
+/// deepest nesting of arrays and optional elements accepted in a descriptor
+const MAX_DEPTH: usize = 32;
+
+#[derive(Debug, Clone)]
+/// `DescribeError` is returned when values cannot be described or when a self-describing buffer cannot be decoded.
+pub enum DescribeError {
+    /// Unsupported occurs when the element at the given index cannot be described, like conditional elements and
+    /// references by name
+    Unsupported(usize),
+    /// Array occurs when the items of the array at the given index do not all have the same type
+    Array(usize),
+    /// Version occurs when the descriptor was written by an unknown version of the format
+    Version(u8),
+    /// Descriptor occurs when the descriptor is invalid or truncated at the given offset
+    Descriptor(usize),
+    /// Encode occurs when the values cannot be encoded
+    Encode(EncodeError),
+    /// Decode occurs when the payload does not match the descriptor
+    Decode(DecodeError),
+}
+
+impl fmt::Display for DescribeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DescribeError::Unsupported(index) => {
+                write!(f, "element {} cannot be described", index)
+            }
+            DescribeError::Array(index) => {
+                write!(f, "items of array {} do not have the same type", index)
+            }
+            DescribeError::Version(version) => {
+                write!(f, "unsupported descriptor version {}", version)
+            }
+            DescribeError::Descriptor(offset) => write!(f, "invalid descriptor at byte {}", offset),
+            DescribeError::Encode(error) => write!(f, "cannot encode values: {:?}", error),
+            DescribeError::Decode(error) => write!(f, "cannot decode values: {:?}", error),
+        }
+    }
+}
+
+impl std::error::Error for DescribeError {}
+
+/// `describe` returns the types that decode the given values, the lengths of strings and byte-arrays are taken from
+/// the values.
+///
+/// # Examples
+/// ```rust
+/// use packed_encoder::decoder::DecodeType;
+/// use packed_encoder::describe;
+/// use packed_encoder::encoder::EncodeType;
+///
+/// let types = describe::describe(&[EncodeType::Uint16(7), EncodeType::Str("abc".to_owned())]).unwrap();
+/// assert!(matches!(types[..], [DecodeType::Uint16, DecodeType::Str(3)]));
+/// ```
+pub fn describe(values: &[EncodeType]) -> Result<Vec<DecodeType>, DescribeError> {
+    values
+        .iter()
+        .enumerate()
+        .map(|(index, value)| describe_value(value, index))
+        .collect()
+}
+
+/// returns the type of a single value, `index` is the position of the top-level element reported in errors
+fn describe_value(value: &EncodeType, index: usize) -> Result<DecodeType, DescribeError> {
+    let index_ref = |field: &FieldRef| match field {
+        FieldRef::Index(position) => Ok(FieldRef::Index(*position)),
+        FieldRef::Name(_) => Err(DescribeError::Unsupported(index)),
+    };
+    let item_type = |items: &[EncodeType]| -> Result<DecodeType, DescribeError> {
+        let kind = match items.first() {
+            Some(item) => describe_value(item, index)?,
+            // the item type of empty arrays does not matter, they decode to an empty array
+            None => DecodeType::Uint8,
+        };
+        for item in items.iter().skip(1) {
+            if !same_type(&describe_value(item, index)?, &kind) {
+                return Err(DescribeError::Array(index));
+            }
+        }
+        Ok(kind)
+    };
+
+    Ok(match value {
+        EncodeType::Int8(_) => DecodeType::Int8,
+        EncodeType::Int16(_) => DecodeType::Int16,
+        EncodeType::Int32(_) => DecodeType::Int32,
+        EncodeType::Int64(_) => DecodeType::Int64,
+        EncodeType::Int128(_) => DecodeType::Int128,
+        EncodeType::Uint8(_) => DecodeType::Uint8,
+        EncodeType::Uint16(_) => DecodeType::Uint16,
+        EncodeType::Uint32(_) => DecodeType::Uint32,
+        EncodeType::Uint64(_) => DecodeType::Uint64,
+        EncodeType::Uint128(_) => DecodeType::Uint128,
+        EncodeType::Float32(_) => DecodeType::Float32,
+        EncodeType::Float64(_) => DecodeType::Float64,
+        EncodeType::Bool(_) => DecodeType::Bool,
+        EncodeType::Str(content) => DecodeType::Str(content.len()),
+        EncodeType::Bytes(content) => DecodeType::Bytes(content.len()),
+        EncodeType::BitGroup(values, order) => DecodeType::BitGroup(
+            values
+                .iter()
+                .map(|value| match value {
+                    BitValue::Unsigned(width, _) => BitField::Unsigned(*width),
+                    BitValue::Signed(width, _) => BitField::Signed(*width),
+                })
+                .collect(),
+            *order,
+        ),
+        EncodeType::Checksum(kind, range) => DecodeType::Checksum(*kind, range.clone()),
+        EncodeType::Const(content) => DecodeType::Const(content.clone()),
+        EncodeType::Reserved(size) => DecodeType::Reserved(*size),
+        EncodeType::StrRef(_, field) => DecodeType::StrRef(index_ref(field)?),
+        EncodeType::BytesRef(_, field) => DecodeType::BytesRef(index_ref(field)?),
+        EncodeType::Array(items) => DecodeType::Array(Box::new(item_type(items)?), items.len()),
+        EncodeType::ArrayRef(items, field) => {
+            DecodeType::ArrayRef(Box::new(item_type(items)?), index_ref(field)?)
+        }
+        EncodeType::Optional(None) => DecodeType::Optional(None),
+        EncodeType::Optional(Some(values)) => DecodeType::Optional(Some(
+            values
+                .iter()
+                .map(|value| describe_value(value, index))
+                .collect::<Result<_, _>>()?,
+        )),
+        EncodeType::Conditional(..) => return Err(DescribeError::Unsupported(index)),
+    })
+}
+
+/// returns true when both types have the same descriptor
+fn same_type(a: &DecodeType, b: &DecodeType) -> bool {
+    let (mut first, mut second) = (vec![], vec![]);
+    match (write_type(a, 0, &mut first), write_type(b, 0, &mut second)) {
+        (Ok(()), Ok(())) => first == second,
+        _ => false,
+    }
+}
+
+/// `encode_descriptor` returns the descriptor of the given types and byte order.
+pub fn encode_descriptor(
+    types: &[DecodeType],
+    order: DecodeOrder,
+) -> Result<Vec<u8>, DescribeError> {
+    let mut descriptor = vec![VERSION << 4 | matches!(order, DecodeOrder::Little) as u8];
+    encode_varint(types.len() as u64, &mut descriptor);
+    for (index, kind) in types.iter().enumerate() {
+        write_type(kind, index, &mut descriptor)?;
+    }
+    Ok(descriptor)
+}
+
+/// appends the entry of a type to the descriptor, `index` is the position of the top-level element reported in errors
+fn write_type(kind: &DecodeType, index: usize, out: &mut Vec<u8>) -> Result<(), DescribeError> {
+    let index_of = |field: &FieldRef| match field {
+        FieldRef::Index(position) => Ok(*position as u64),
+        FieldRef::Name(_) => Err(DescribeError::Unsupported(index)),
+    };
+    match kind {
+        DecodeType::Int8 => out.push(0x01),
+        DecodeType::Int16 => out.push(0x02),
+        DecodeType::Int32 => out.push(0x03),
+        DecodeType::Int64 => out.push(0x04),
+        DecodeType::Int128 => out.push(0x05),
+        DecodeType::Uint8 => out.push(0x06),
+        DecodeType::Uint16 => out.push(0x07),
+        DecodeType::Uint32 => out.push(0x08),
+        DecodeType::Uint64 => out.push(0x09),
+        DecodeType::Uint128 => out.push(0x0a),
+        DecodeType::Float32 => out.push(0x0b),
+        DecodeType::Float64 => out.push(0x0c),
+        DecodeType::Bool => out.push(0x0d),
+        DecodeType::Str(size) => {
+            out.push(0x10);
+            encode_varint(*size as u64, out);
+        }
+        DecodeType::Bytes(size) => {
+            out.push(0x11);
+            encode_varint(*size as u64, out);
+        }
+        DecodeType::StrRef(field) => {
+            out.push(0x12);
+            encode_varint(index_of(field)?, out);
+        }
+        DecodeType::BytesRef(field) => {
+            out.push(0x13);
+            encode_varint(index_of(field)?, out);
+        }
+        DecodeType::Array(item, count) => {
+            out.push(0x14);
+            encode_varint(*count as u64, out);
+            write_type(item, index, out)?;
+        }
+        DecodeType::ArrayRef(item, field) => {
+            out.push(0x15);
+            encode_varint(index_of(field)?, out);
+            write_type(item, index, out)?;
+        }
+        DecodeType::BitGroup(fields, order) => {
+            out.push(0x16);
+            out.push(matches!(order, BitOrder::Lsb) as u8);
+            encode_varint(fields.len() as u64, out);
+            for field in fields {
+                match field {
+                    BitField::Unsigned(width) => out.push(*width & 0x7f),
+                    BitField::Signed(width) => out.push(*width & 0x7f | 0x80),
+                }
+            }
+        }
+        DecodeType::Checksum(kind, range) => {
+            out.push(0x17);
+            out.push(checksum_code(*kind));
+            encode_varint(range.start as u64, out);
+            encode_varint(range.end as u64, out);
+        }
+        DecodeType::Const(content) => {
+            out.push(0x18);
+            encode_varint(content.len() as u64, out);
+            out.extend_from_slice(content);
+        }
+        DecodeType::Reserved(size) => {
+            out.push(0x19);
+            encode_varint(*size as u64, out);
+        }
+        DecodeType::Padding(size) => {
+            out.push(0x1a);
+            encode_varint(*size as u64, out);
+        }
+        DecodeType::Optional(None) => out.extend_from_slice(&[0x1b, 0]),
+        DecodeType::Optional(Some(types)) => {
+            out.extend_from_slice(&[0x1b, 1]);
+            encode_varint(types.len() as u64, out);
+            for kind in types {
+                write_type(kind, index, out)?;
+            }
+        }
+        DecodeType::Conditional(..) => return Err(DescribeError::Unsupported(index)),
+    }
+    Ok(())
+}
+
+const CHECKSUMS: [ChecksumKind; 7] = [
+    ChecksumKind::Crc8,
+    ChecksumKind::Crc16Ccitt,
+    ChecksumKind::Crc32,
+    ChecksumKind::Crc32c,
+    ChecksumKind::Adler32,
+    ChecksumKind::Sum8,
+    ChecksumKind::Xor8,
+];
+
+/// returns the code of a checksum kind in the descriptor
+fn checksum_code(kind: ChecksumKind) -> u8 {
+    CHECKSUMS.iter().position(|known| *known == kind).unwrap() as u8
+}
+
+/// `decode_descriptor` reads the descriptor at the start of the buffer and returns the types and the byte order it
+/// describes, with the number of bytes it uses.
+pub fn decode_descriptor(
+    buffer: &[u8],
+) -> Result<(Vec<DecodeType>, DecodeOrder, usize), DescribeError> {
+    let first = *buffer.first().ok_or(DescribeError::Descriptor(0))?;
+    if first >> 4 != VERSION {
+        return Err(DescribeError::Version(first >> 4));
+    }
+    if first & 0x0e != 0 {
+        return Err(DescribeError::Descriptor(0));
+    }
+    let order = match first & 1 {
+        0 => DecodeOrder::Big,
+        _ => DecodeOrder::Little,
+    };
+    let mut reader = DescriptorReader { buffer, offset: 1 };
+    let types = reader.types(0)?;
+    Ok((types, order, reader.offset))
+}
+
+/// `DescriptorReader` walks through the entries of a descriptor.
+struct DescriptorReader<'a> {
+    buffer: &'a [u8],
+    offset: usize,
+}
+
+impl DescriptorReader<'_> {
+    /// returns an error pointing at the current offset
+    fn invalid(&self) -> DescribeError {
+        DescribeError::Descriptor(self.offset)
+    }
+
+    fn byte(&mut self) -> Result<u8, DescribeError> {
+        let byte = *self.buffer.get(self.offset).ok_or_else(|| self.invalid())?;
+        self.offset += 1;
+        Ok(byte)
+    }
+
+    fn varint(&mut self) -> Result<usize, DescribeError> {
+        match decode_varint(&self.buffer[self.offset..]) {
+            Ok(Some((value, size))) => {
+                let value = usize::try_from(value).map_err(|_| self.invalid())?;
+                self.offset += size;
+                Ok(value)
+            }
+            _ => Err(self.invalid()),
+        }
+    }
+
+    /// reads a count of entries, every entry uses at least one byte of the descriptor
+    fn count(&mut self) -> Result<usize, DescribeError> {
+        let count = self.varint()?;
+        if count > self.buffer.len() - self.offset {
+            return Err(self.invalid());
+        }
+        Ok(count)
+    }
+
+    /// reads the size of an element, elements larger than the whole buffer cannot be decoded from it
+    fn size(&mut self) -> Result<usize, DescribeError> {
+        let start = self.offset;
+        let size = self.varint()?;
+        if size > self.buffer.len() {
+            return Err(DescribeError::Descriptor(start));
+        }
+        Ok(size)
+    }
+
+    fn types(&mut self, depth: usize) -> Result<Vec<DecodeType>, DescribeError> {
+        let count = self.count()?;
+        (0..count).map(|_| self.entry(depth)).collect()
+    }
+
+    fn entry(&mut self, depth: usize) -> Result<DecodeType, DescribeError> {
+        if depth > MAX_DEPTH {
+            return Err(self.invalid());
+        }
+        let start = self.offset;
+        Ok(match self.byte()? {
+            0x01 => DecodeType::Int8,
+            0x02 => DecodeType::Int16,
+            0x03 => DecodeType::Int32,
+            0x04 => DecodeType::Int64,
+            0x05 => DecodeType::Int128,
+            0x06 => DecodeType::Uint8,
+            0x07 => DecodeType::Uint16,
+            0x08 => DecodeType::Uint32,
+            0x09 => DecodeType::Uint64,
+            0x0a => DecodeType::Uint128,
+            0x0b => DecodeType::Float32,
+            0x0c => DecodeType::Float64,
+            0x0d => DecodeType::Bool,
+            0x10 => DecodeType::Str(self.size()?),
+            0x11 => DecodeType::Bytes(self.size()?),
+            0x12 => DecodeType::StrRef(FieldRef::Index(self.varint()?)),
+            0x13 => DecodeType::BytesRef(FieldRef::Index(self.varint()?)),
+            0x14 => {
+                let count = self.varint()?;
+                let item = self.entry(depth + 1)?;
+                // bounds the work of decoding arrays of empty items to the size of the buffer
+                let size = item.fixed_size().unwrap_or(1).max(1);
+                if count.saturating_mul(size) > self.buffer.len() {
+                    return Err(DescribeError::Descriptor(start));
+                }
+                DecodeType::Array(Box::new(item), count)
+            }
+            0x15 => {
+                let field = FieldRef::Index(self.varint()?);
+                DecodeType::ArrayRef(Box::new(self.entry(depth + 1)?), field)
+            }
+            0x16 => {
+                let order = match self.byte()? {
+                    0 => BitOrder::Msb,
+                    1 => BitOrder::Lsb,
+                    _ => return Err(DescribeError::Descriptor(self.offset - 1)),
+                };
+                let count = self.count()?;
+                let fields = (0..count)
+                    .map(|_| {
+                        self.byte().map(|byte| match byte & 0x80 {
+                            0 => BitField::Unsigned(byte),
+                            _ => BitField::Signed(byte & 0x7f),
+                        })
+                    })
+                    .collect::<Result<_, _>>()?;
+                DecodeType::BitGroup(fields, order)
+            }
+            0x17 => {
+                let kind = *CHECKSUMS
+                    .get(self.byte()? as usize)
+                    .ok_or(DescribeError::Descriptor(self.offset - 1))?;
+                let range = self.varint()?..self.varint()?;
+                DecodeType::Checksum(kind, range)
+            }
+            0x18 => {
+                let size = self.count()?;
+                let content = self.buffer[self.offset..self.offset + size].to_vec();
+                self.offset += size;
+                DecodeType::Const(content)
+            }
+            0x19 => DecodeType::Reserved(self.size()?),
+            0x1a => DecodeType::Padding(self.size()?),
+            0x1b => match self.byte()? {
+                0 => DecodeType::Optional(None),
+                1 => DecodeType::Optional(Some(self.types(depth + 1)?)),
+                _ => return Err(DescribeError::Descriptor(self.offset - 1)),
+            },
+            _ => return Err(DescribeError::Descriptor(start)),
+        })
+    }
+}
+
+/// `encode_self_describing` encodes the values like `encode_packed` and prepends the descriptor of their types, the
+/// output can be decoded by `decode_self_describing` alone.
+///
+/// # Examples
+/// ```rust
+/// use packed_encoder::decoder::{DecodedData, FieldRef};
+/// use packed_encoder::describe;
+/// use packed_encoder::encoder::{EncodeOrder, EncodeType};
+///
+/// let values = [
+///     EncodeType::Uint8(0),
+///     EncodeType::StrRef("hello".to_owned(), FieldRef::Index(0)),
+///     EncodeType::Array(vec![EncodeType::Int16(-1), EncodeType::Int16(2)]),
+/// ];
+/// let encoded = describe::encode_self_describing(&values, EncodeOrder::Little).unwrap();
+/// let decoded = describe::decode_self_describing(&encoded).unwrap();
+/// assert_eq!(decoded[1], DecodedData::Str("hello".to_owned()));
+/// assert_eq!(decoded[2], DecodedData::Array(vec![DecodedData::Int16(-1), DecodedData::Int16(2)]));
+/// ```
+pub fn encode_self_describing(
+    values: &[EncodeType],
+    encode_order: EncodeOrder,
+) -> Result<Vec<u8>, DescribeError> {
+    let types = describe(values)?;
+    let mut encoded = encode_descriptor(&types, encode_order.clone().into())?;
+    let payload = encoder::encode_packed(values, encode_order).map_err(DescribeError::Encode)?;
+    encoded.extend_from_slice(&payload);
+    Ok(encoded)
+}
+
+/// `decode_self_describing` decodes a buffer written by `encode_self_describing`, the types and the byte order are
+/// read from its descriptor.
+pub fn decode_self_describing(buffer: &[u8]) -> Result<Vec<DecodedData>, DescribeError> {
+    let (types, order, size) = decode_descriptor(buffer)?;
+    decoder::decode_packed(&types, &buffer[size..], order).map_err(DescribeError::Decode)
+}
@@ -1,11 +1,17 @@
 extern crate byteorder;
 
+use crate::bitfield::{self, BitOrder, BitValue};
+use crate::checksum::ChecksumKind;
+use crate::decoder::{DecodedData, FieldRef, Predicate};
+use crate::schema::Field;
 use byteorder::{BigEndian, LittleEndian, WriteBytesExt};
+use std::ops::Range;
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq)]
 /// `EncodeType` contains various data-types that are supported by packed-encoder.
 /// This enum can be used to tell the encoder how a specific data needs to be encoded.
 /// Example: `EncodeType::Int16(2422)` tells the encoder to encode the value `2422` as a 16-bit signed integer.
+/// `EncodeType` implements `PartialEq` but not `Eq` since it holds floating point values.
 pub enum EncodeType {
     /// Int8 type is a 8-bit signed integer
     Int8(i8),
@@ -29,11 +35,59 @@ pub enum EncodeType {
     /// Uint128 type is a 128-bit unsigned integer
     Uint128(u128),
 
+    /// Float32 type is a 32-bit IEEE 754 floating point number
+    Float32(f32),
+    /// Float64 type is a 64-bit IEEE 754 floating point number
+    Float64(f64),
+
+    /// Bool type is written as a single byte, `1` for true and `0` for false
+    Bool(bool),
+
     // Str type represents a finite string
     Str(String),
 
     // Bytes represents a sequence of finite bytes
     Bytes(Vec<u8>),
+
+    /// BitGroup packs sub-byte fields next to each other using the given bit order, the group is padded
+    /// with zero bits up to the next byte boundary
+    BitGroup(Vec<BitValue>, BitOrder),
+
+    /// Checksum is filled with the checksum of the bytes occupied by the elements in the given index range,
+    /// the range can only cover elements placed before the checksum
+    /// Example: `EncodeType::Checksum(ChecksumKind::Crc32, 0..3)` protects the first three elements.
+    Checksum(ChecksumKind, Range<usize>),
+
+    /// Const writes the given bytes as they are, like magic numbers or version constants
+    Const(Vec<u8>),
+
+    /// Reserved(usize) writes `x` zero bytes
+    Reserved(usize),
+
+    /// StrRef writes a string whose length in bytes is back-filled into the integer element it refers to,
+    /// the length element must be placed before the string
+    /// Example: `&[EncodeType::Uint16(0), EncodeType::StrRef("hello".to_owned(), FieldRef::Index(0))]` writes `5u16` before the string.
+    StrRef(String, FieldRef),
+
+    /// BytesRef writes a byte-array whose length is back-filled into the integer element it refers to
+    BytesRef(Vec<u8>, FieldRef),
+
+    /// Array writes the given items one after the other, the items cannot refer to other elements
+    Array(Vec<EncodeType>),
+
+    /// ArrayRef writes the given items one after the other and back-fills the number of items into the
+    /// integer element it refers to
+    ArrayRef(Vec<EncodeType>, FieldRef),
+
+    /// Conditional writes the nested list of elements only when the value of the element it refers to satisfies
+    /// the predicate, nothing is written otherwise. The predicate is tested against the value written for the element,
+    /// which is the back-filled length for length elements. References inside the nested list refer to the elements
+    /// of the nested list by index, names cannot be resolved there.
+    Conditional(FieldRef, Predicate, Vec<EncodeType>),
+
+    /// Optional writes the nested list of elements when present and nothing otherwise, it is the counterpart of
+    /// `DecodedData::Optional`
+    Optional(Option<Vec<EncodeType>>),
 }
 
 #[derive(Debug, Clone)]
@@ -55,11 +109,33 @@ pub enum EncodeError {
     Uint64(u64),
     Uint128(u128),
 
+    // floating point types
+    Float32(f32),
+    Float64(f64),
+
     // string
     Str(String),
 
     // bytes
     Bytes(Vec<u8>),
+
+    // bit-field that cannot be stored in its declared width
+    BitField(BitValue),
+
+    // checksum whose range does not refer to the elements placed before it
+    Checksum(ChecksumKind, Range<usize>),
+
+    // length that cannot be stored in the integer element it refers to
+    Length(usize),
+
+    // reference that does not point to an integer element placed before the referring element
+    LengthField(FieldRef),
+
+    // reference of a conditional element that does not point to a value placed before it
+    ConditionField(FieldRef),
+
+    // output buffer without room for the given number of encoded bytes
+    BufferFull(usize),
 }
 
 #[derive(Debug, Clone)]
@@ -133,6 +209,7 @@ fn encode_i128(
 // unsigned integer
 
 #[inline]
+#[allow(clippy::unnecessary_cast)]
 fn encode_u8(array: &mut [u8], value: &u8) -> Result<(), EncodeError> {
     array[0] = *value as u8;
     Ok(())
@@ -190,6 +267,48 @@ fn encode_u128(
     .map_or_else(|_| Err(EncodeError::Uint128(*value)), |_| Ok(()))
 }
 
+#[inline]
+fn encode_checksum(
+    array: &mut [u8],
+    kind: ChecksumKind,
+    value: u32,
+    encode_order: EncodeOrder,
+) -> Result<(), EncodeError> {
+    match kind.size() {
+        1 => encode_u8(array, &(value as u8)),
+        2 => encode_u16(array, &(value as u16), encode_order),
+        _ => encode_u32(array, &value, encode_order),
+    }
+}
+
+// floating point
+
+#[inline]
+fn encode_f32(
+    mut array: &mut [u8],
+    value: &f32,
+    encode_order: EncodeOrder,
+) -> Result<(), EncodeError> {
+    match encode_order {
+        EncodeOrder::Big => array.write_f32::<BigEndian>(*value),
+        EncodeOrder::Little => array.write_f32::<LittleEndian>(*value),
+    }
+    .map_or_else(|_| Err(EncodeError::Float32(*value)), |_| Ok(()))
+}
+
+#[inline]
+fn encode_f64(
+    mut array: &mut [u8],
+    value: &f64,
+    encode_order: EncodeOrder,
+) -> Result<(), EncodeError> {
+    match encode_order {
+        EncodeOrder::Big => array.write_f64::<BigEndian>(*value),
+        EncodeOrder::Little => array.write_f64::<LittleEndian>(*value),
+    }
+    .map_or_else(|_| Err(EncodeError::Float64(*value)), |_| Ok(()))
+}
+
 #[inline]
 fn encode_string(array: &mut [u8], value: &str) -> Result<(), EncodeError> {
     let u8_repr = value.as_bytes();
@@ -229,92 +348,299 @@ fn encode_string(array: &mut [u8], value: &str) -> Result<(), EncodeError> {
 /// ```
 pub fn encode_packed(elements: &[EncodeType], endian: EncodeOrder) -> Result<Vec<u8>, EncodeError> {
     let mut buffer: Vec<u8> = Vec::new();
-    let mut last_read = 0;
+    encode_list(elements, &[], &mut buffer, endian)?;
+    Ok(buffer)
+}
 
-    for symbol in elements {
-        let (result, size_offset) = match symbol {
-            EncodeType::Int8(value) => {
-                buffer.extend_from_slice(&[0]);
-                (encode_i8(&mut buffer[last_read..], value), 1)
-            }
-            EncodeType::Int16(value) => {
-                buffer.extend_from_slice(&[0, 0]);
-                (
-                    encode_i16(&mut buffer[last_read..], value, endian.clone()),
-                    2,
-                )
-            }
-            EncodeType::Int32(value) => {
-                buffer.extend_from_slice(&[0, 0, 0, 0]);
-                (
-                    encode_i32(&mut buffer[last_read..], value, endian.clone()),
-                    4,
-                )
-            }
-            EncodeType::Int64(value) => {
-                buffer.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 0]);
-                (
-                    encode_i64(&mut buffer[last_read..], value, endian.clone()),
-                    8,
-                )
-            }
-            EncodeType::Int128(value) => {
-                buffer.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
-                (
-                    encode_i128(&mut buffer[last_read..], value, endian.clone()),
-                    16,
-                )
-            }
-            EncodeType::Uint8(value) => {
-                buffer.extend_from_slice(&[0]);
-                (encode_u8(&mut buffer[last_read..], value), 1)
-            }
-            EncodeType::Uint16(value) => {
-                buffer.extend_from_slice(&[0, 0]);
-                (
-                    encode_u16(&mut buffer[last_read..], value, endian.clone()),
-                    2,
-                )
-            }
-            EncodeType::Uint32(value) => {
-                buffer.extend_from_slice(&[0, 0, 0, 0]);
-                (
-                    encode_u32(&mut buffer[last_read..], value, endian.clone()),
-                    4,
-                )
-            }
-            EncodeType::Uint64(value) => {
-                buffer.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 0]);
-                (
-                    encode_u64(&mut buffer[last_read..], value, endian.clone()),
-                    8,
-                )
+/// appends a list of elements to the buffer, references inside the list refer to the elements of the same list.
+pub(crate) fn encode_list(
+    elements: &[EncodeType],
+    fields: &[Field],
+    buffer: &mut Vec<u8>,
+    endian: EncodeOrder,
+) -> Result<(), EncodeError> {
+    let mut scope = EncodeScope {
+        elements,
+        fields,
+        endian,
+        offsets: Vec::with_capacity(elements.len() + 1),
+        checksums: vec![],
+    };
+
+    for (idx, symbol) in elements.iter().enumerate() {
+        scope.offsets.push(buffer.len());
+        let order = scope.order(idx);
+        encode_element(symbol, idx, buffer, order, Some(&mut scope))?;
+    }
+
+    // checksums are filled once all the elements are in place
+    scope.offsets.push(buffer.len());
+    for (position, kind, range, order) in scope.checksums {
+        let value = kind.compute(&buffer[scope.offsets[range.start]..scope.offsets[range.end]]);
+        encode_checksum(&mut buffer[position..], kind, value, order)?;
+    }
+
+    Ok(())
+}
+
+/// `EncodeScope` keeps track of the top-level elements encoded so far, it is used to back-fill length fields
+/// and to locate the bytes covered by checksums.
+struct EncodeScope<'a> {
+    elements: &'a [EncodeType],
+    fields: &'a [Field],
+    // byte order of the list, used by the elements whose field does not override it
+    endian: EncodeOrder,
+    offsets: Vec<usize>,
+    checksums: Vec<(usize, ChecksumKind, Range<usize>, EncodeOrder)>,
+}
+
+impl EncodeScope<'_> {
+    /// returns the index of the element referred by `field`, the element must be placed before `idx`
+    fn target(&self, field: &FieldRef, idx: usize) -> Option<usize> {
+        let target = match field {
+            FieldRef::Index(target) => *target,
+            FieldRef::Name(name) => self
+                .fields
+                .iter()
+                .take(idx)
+                .position(|entry| entry.name.as_deref() == Some(name.as_str()))?,
+        };
+        (target < idx).then_some(target)
+    }
+
+    /// returns the value of the element at `target` once encoded, lengths back-filled by later elements included,
+    /// it is the value the decoder tests conditions against
+    fn value(&self, target: usize) -> Option<DecodedData> {
+        // the last reference is the one written last
+        let length = self
+            .elements
+            .iter()
+            .enumerate()
+            .rev()
+            .filter_map(|(idx, element)| match element {
+                EncodeType::StrRef(string, field) => Some((idx, field, string.len())),
+                EncodeType::BytesRef(bytes, field) => Some((idx, field, bytes.len())),
+                EncodeType::ArrayRef(items, field) => Some((idx, field, items.len())),
+                _ => None,
+            })
+            .find(|(idx, field, _)| self.target(field, *idx) == Some(target));
+        match length {
+            Some((_, field, length)) => with_length(&self.elements[target], length, field).ok(),
+            None => Some(self.elements[target].clone()),
+        }
+        .and_then(|symbol| DecodedData::try_from(&symbol).ok())
+    }
+
+    /// returns the byte order of the element at `idx`, fields of a schema can override the byte order of the list
+    fn order(&self, idx: usize) -> EncodeOrder {
+        self.fields
+            .get(idx)
+            .and_then(|field| field.order.clone())
+            .map_or_else(|| self.endian.clone(), EncodeOrder::from)
+    }
+}
+
+/// writes `length` into the integer element referred by `field`, the element must be placed before `idx`. The
+/// length is written in the byte order of the referred element, whatever the order of the element at `idx`.
+fn write_length(
+    buffer: &mut [u8],
+    scope: &EncodeScope,
+    field: &FieldRef,
+    idx: usize,
+    length: usize,
+) -> Result<(), EncodeError> {
+    let target = scope
+        .target(field, idx)
+        .ok_or_else(|| EncodeError::LengthField(field.clone()))?;
+
+    let endian = scope.order(target);
+    let array = &mut buffer[scope.offsets[target]..];
+    let overflow = |_| EncodeError::Length(length);
+    match &scope.elements[target] {
+        EncodeType::Int8(_) => encode_i8(array, &i8::try_from(length).map_err(overflow)?),
+        EncodeType::Int16(_) => {
+            encode_i16(array, &i16::try_from(length).map_err(overflow)?, endian)
+        }
+        EncodeType::Int32(_) => {
+            encode_i32(array, &i32::try_from(length).map_err(overflow)?, endian)
+        }
+        EncodeType::Int64(_) => {
+            encode_i64(array, &i64::try_from(length).map_err(overflow)?, endian)
+        }
+        EncodeType::Int128(_) => encode_i128(array, &(length as i128), endian),
+        EncodeType::Uint8(_) => encode_u8(array, &u8::try_from(length).map_err(overflow)?),
+        EncodeType::Uint16(_) => {
+            encode_u16(array, &u16::try_from(length).map_err(overflow)?, endian)
+        }
+        EncodeType::Uint32(_) => {
+            encode_u32(array, &u32::try_from(length).map_err(overflow)?, endian)
+        }
+        EncodeType::Uint64(_) => encode_u64(array, &(length as u64), endian),
+        EncodeType::Uint128(_) => encode_u128(array, &(length as u128), endian),
+        _ => Err(EncodeError::LengthField(field.clone())),
+    }
+}
+
+/// returns the integer element holding the given length
+pub(crate) fn with_length(
+    value: &EncodeType,
+    length: usize,
+    field: &FieldRef,
+) -> Result<EncodeType, EncodeError> {
+    let overflow = |_| EncodeError::Length(length);
+    Ok(match value {
+        EncodeType::Int8(_) => EncodeType::Int8(i8::try_from(length).map_err(overflow)?),
+        EncodeType::Int16(_) => EncodeType::Int16(i16::try_from(length).map_err(overflow)?),
+        EncodeType::Int32(_) => EncodeType::Int32(i32::try_from(length).map_err(overflow)?),
+        EncodeType::Int64(_) => EncodeType::Int64(i64::try_from(length).map_err(overflow)?),
+        EncodeType::Int128(_) => EncodeType::Int128(length as i128),
+        EncodeType::Uint8(_) => EncodeType::Uint8(u8::try_from(length).map_err(overflow)?),
+        EncodeType::Uint16(_) => EncodeType::Uint16(u16::try_from(length).map_err(overflow)?),
+        EncodeType::Uint32(_) => EncodeType::Uint32(u32::try_from(length).map_err(overflow)?),
+        EncodeType::Uint64(_) => EncodeType::Uint64(length as u64),
+        EncodeType::Uint128(_) => EncodeType::Uint128(length as u128),
+        _ => return Err(EncodeError::LengthField(field.clone())),
+    })
+}
+
+/// appends a single element to the buffer. `scope` is `None` for the items of an array, which cannot refer to
+/// other elements.
+fn encode_element(
+    symbol: &EncodeType,
+    idx: usize,
+    buffer: &mut Vec<u8>,
+    endian: EncodeOrder,
+    scope: Option<&mut EncodeScope>,
+) -> Result<(), EncodeError> {
+    let last_read = buffer.len();
+    match symbol {
+        EncodeType::Int8(value) => {
+            buffer.extend_from_slice(&[0]);
+            encode_i8(&mut buffer[last_read..], value)
+        }
+        EncodeType::Int16(value) => {
+            buffer.extend_from_slice(&[0, 0]);
+            encode_i16(&mut buffer[last_read..], value, endian)
+        }
+        EncodeType::Int32(value) => {
+            buffer.extend_from_slice(&[0, 0, 0, 0]);
+            encode_i32(&mut buffer[last_read..], value, endian)
+        }
+        EncodeType::Int64(value) => {
+            buffer.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 0]);
+            encode_i64(&mut buffer[last_read..], value, endian)
+        }
+        EncodeType::Int128(value) => {
+            buffer.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+            encode_i128(&mut buffer[last_read..], value, endian)
+        }
+        EncodeType::Uint8(value) => {
+            buffer.extend_from_slice(&[0]);
+            encode_u8(&mut buffer[last_read..], value)
+        }
+        EncodeType::Uint16(value) => {
+            buffer.extend_from_slice(&[0, 0]);
+            encode_u16(&mut buffer[last_read..], value, endian)
+        }
+        EncodeType::Uint32(value) => {
+            buffer.extend_from_slice(&[0, 0, 0, 0]);
+            encode_u32(&mut buffer[last_read..], value, endian)
+        }
+        EncodeType::Uint64(value) => {
+            buffer.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 0]);
+            encode_u64(&mut buffer[last_read..], value, endian)
+        }
+        EncodeType::Uint128(value) => {
+            buffer.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+            encode_u128(&mut buffer[last_read..], value, endian)
+        }
+        EncodeType::Float32(value) => {
+            buffer.extend_from_slice(&[0, 0, 0, 0]);
+            encode_f32(&mut buffer[last_read..], value, endian)
+        }
+        EncodeType::Float64(value) => {
+            buffer.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 0]);
+            encode_f64(&mut buffer[last_read..], value, endian)
+        }
+        EncodeType::Bool(value) => {
+            buffer.push(*value as u8);
+            Ok(())
+        }
+        #[allow(clippy::slow_vector_initialization)]
+        EncodeType::Str(string) => {
+            let mut temp = Vec::new();
+            temp.resize(string.len(), 0);
+            buffer.extend_from_slice(&temp);
+            encode_string(&mut buffer[last_read..], string)
+        }
+        EncodeType::Bytes(bytes) => {
+            buffer.extend_from_slice(bytes);
+            Ok(())
+        }
+        EncodeType::BitGroup(fields, order) => {
+            let packed = bitfield::pack_bits(fields, *order).map_err(EncodeError::BitField)?;
+            buffer.extend_from_slice(&packed);
+            Ok(())
+        }
+        EncodeType::Checksum(kind, range) => match scope {
+            Some(scope) if range.start <= range.end && range.end <= idx => {
+                buffer.resize(buffer.len() + kind.size(), 0);
+                scope
+                    .checksums
+                    .push((last_read, *kind, range.clone(), endian));
+                Ok(())
             }
-            EncodeType::Uint128(value) => {
-                buffer.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
-                (
-                    encode_u128(&mut buffer[last_read..], value, endian.clone()),
-                    16,
-                )
+            _ => Err(EncodeError::Checksum(*kind, range.clone())),
+        },
+        EncodeType::Const(content) => {
+            buffer.extend_from_slice(content);
+            Ok(())
+        }
+        EncodeType::Reserved(size) => {
+            buffer.resize(buffer.len() + *size, 0);
+            Ok(())
+        }
+        EncodeType::StrRef(string, field) => {
+            let scope = scope.ok_or_else(|| EncodeError::LengthField(field.clone()))?;
+            write_length(buffer, scope, field, idx, string.len())?;
+            buffer.resize(buffer.len() + string.len(), 0);
+            encode_string(&mut buffer[last_read..], string)
+        }
+        EncodeType::BytesRef(bytes, field) => {
+            let scope = scope.ok_or_else(|| EncodeError::LengthField(field.clone()))?;
+            write_length(buffer, scope, field, idx, bytes.len())?;
+            buffer.extend_from_slice(bytes);
+            Ok(())
+        }
+        EncodeType::Conditional(field, predicate, elements) => {
+            let value = scope.and_then(|scope| {
+                scope
+                    .target(field, idx)
+                    .and_then(|target| scope.value(target))
+            });
+            match value {
+                None => Err(EncodeError::ConditionField(field.clone())),
+                Some(value) if predicate.test(&value) => encode_list(elements, &[], buffer, endian),
+                Some(_) => Ok(()),
             }
-            EncodeType::Str(string) => {
-                let mut temp = Vec::new();
-                temp.resize(string.len(), 0);
-                buffer.extend_from_slice(&temp);
-                (
-                    encode_string(&mut buffer[last_read..], string),
-                    string.len(),
-                )
+        }
+        EncodeType::Optional(elements) => match elements {
+            Some(elements) => encode_list(elements, &[], buffer, endian),
+            None => Ok(()),
+        },
+        EncodeType::Array(items) => {
+            for (item_idx, item) in items.iter().enumerate() {
+                encode_element(item, item_idx, buffer, endian.clone(), None)?;
             }
-            EncodeType::Bytes(bytes) => {
-                buffer.extend_from_slice(bytes);
-                (Ok(()), bytes.len())
+            Ok(())
+        }
+        EncodeType::ArrayRef(items, field) => {
+            let scope = scope.ok_or_else(|| EncodeError::LengthField(field.clone()))?;
+            write_length(buffer, scope, field, idx, items.len())?;
+            for (item_idx, item) in items.iter().enumerate() {
+                encode_element(item, item_idx, buffer, endian.clone(), None)?;
             }
-        };
-
-        last_read += size_offset;
-        result?;
+            Ok(())
+        }
     }
-
-    Ok(buffer)
 }
@@ -0,0 +1,401 @@
+//! `evolution` compares the versions of a schema and reads the data written with an older version into the layout
+//! of a newer one.
+//!
+//! The fields of two versions are matched by position, as the encoded layout is positional. The following changes
+//! keep the versions compatible:
+//!
+//! - fields added at the tail: older readers ignore the trailing bytes and newer readers fill in the defaults
+//! - fields removed from the tail: newer readers ignore the trailing bytes, older readers cannot fill them in
+//! - renamed fields: the layout does not change
+//! - widened integers and floating point numbers, and longer strings, byte-arrays and arrays: newer readers convert
+//!   the older values, older readers cannot read the larger values
+//!
+//! Any other change of type, like narrowed integers or a field inserted in the middle, breaks compatibility in both
+//! directions.
+
+use crate::decoder::{self, DecodeError, DecodeOrder, DecodeType, DecodedData};
+use crate::schema::{DecodedRecord, Field, Schema};
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+/// `Change` is a difference between two versions of a schema, `index` is the position of the field.
+pub enum Change {
+    /// Added is a field added at the tail by the newer version
+    Added { index: usize, name: Option<String> },
+    /// Removed is a field of the older version removed from the tail by the newer version
+    Removed { index: usize, name: Option<String> },
+    /// Renamed is a field whose name changed
+    Renamed {
+        index: usize,
+        from: Option<String>,
+        to: Option<String>,
+    },
+    /// Widened is a numeric field whose type can hold every value of its older type
+    Widened {
+        index: usize,
+        from: DecodeType,
+        to: DecodeType,
+    },
+    /// Resized is a string, byte-array or array field whose length changed
+    Resized {
+        index: usize,
+        from: usize,
+        to: usize,
+    },
+    /// Incompatible is a field whose type changed in any other way
+    Incompatible {
+        index: usize,
+        from: DecodeType,
+        to: DecodeType,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// `Compatibility` is the result of `check`, it lists the changes between two versions of a schema and tells in
+/// which directions data can be exchanged.
+pub struct Compatibility {
+    /// version of the older schema
+    pub from_version: Option<u32>,
+    /// version of the newer schema
+    pub to_version: Option<u32>,
+    /// changes made by the newer version, in the order of the fields
+    pub changes: Vec<Change>,
+    /// true when the newer version can read the data written with the older version, using `decode_upgraded`
+    pub backward: bool,
+    /// true when the older version can read the data written with the newer version
+    pub forward: bool,
+}
+
+impl Compatibility {
+    /// returns true when data can be exchanged in both directions
+    pub fn is_full(&self) -> bool {
+        self.backward && self.forward
+    }
+}
+
+#[derive(Debug, Clone)]
+/// `EvolutionError` is returned by `decode_upgraded` when data written with an older version of a schema cannot be
+/// read into the layout of the newer version.
+pub enum EvolutionError {
+    /// Decode occurs when the data does not match the older version
+    Decode(DecodeError),
+    /// Incompatible occurs when the type of the field at the given index changed in an incompatible way
+    Incompatible(usize),
+    /// Value occurs when the value of the field at the given index does not fit in its newer type
+    Value(usize),
+    /// MissingValue occurs when the field at the given index has no default and its type has no zero value
+    MissingValue(usize),
+}
+
+impl fmt::Display for EvolutionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EvolutionError::Decode(error) => write!(f, "cannot decode older data: {:?}", error),
+            EvolutionError::Incompatible(index) => {
+                write!(f, "field {} changed in an incompatible way", index)
+            }
+            EvolutionError::Value(index) => {
+                write!(f, "value of field {} does not fit in its newer type", index)
+            }
+            EvolutionError::MissingValue(index) => {
+                write!(f, "field {} has no default value", index)
+            }
+        }
+    }
+}
+
+impl std::error::Error for EvolutionError {}
+
+/// `check` compares an older and a newer version of a schema.
+///
+/// # Examples
+/// ```rust
+/// use packed_encoder::decoder::DecodeType;
+/// use packed_encoder::evolution::{self, Change};
+/// use packed_encoder::schema::Schema;
+///
+/// let v1 = Schema::named("point").with_version(1).field("x", DecodeType::Int16);
+/// let v2 = Schema::named("point")
+///     .with_version(2)
+///     .field("x", DecodeType::Int32)
+///     .field("y", DecodeType::Int32);
+/// let compatibility = evolution::check(&v1, &v2);
+/// assert!(compatibility.backward && !compatibility.forward);
+/// assert_eq!(compatibility.changes[1], Change::Added { index: 1, name: Some("y".to_owned()) });
+/// ```
+pub fn check(old: &Schema, new: &Schema) -> Compatibility {
+    let mut compatibility = Compatibility {
+        from_version: old.version,
+        to_version: new.version,
+        changes: vec![],
+        backward: true,
+        forward: true,
+    };
+
+    for (index, (before, after)) in old.fields.iter().zip(&new.fields).enumerate() {
+        if before.name != after.name {
+            compatibility.changes.push(Change::Renamed {
+                index,
+                from: before.name.clone(),
+                to: after.name.clone(),
+            });
+        }
+        let Some(change) = field_change(index, before, after) else {
+            continue;
+        };
+        match &change {
+            Change::Resized { from, to, .. } if to < from => compatibility.backward = false,
+            Change::Incompatible { .. } => compatibility.backward = false,
+            _ => {}
+        }
+        // the sizes of the older fields change, so older readers cannot find the fields that follow
+        compatibility.forward = false;
+        compatibility.changes.push(change);
+    }
+
+    for (index, field) in new.fields.iter().enumerate().skip(old.fields.len()) {
+        if has_value(&field.kind) && field.default.is_none() && zero_value(&field.kind).is_none() {
+            compatibility.backward = false;
+        }
+        compatibility.changes.push(Change::Added {
+            index,
+            name: field.name.clone(),
+        });
+    }
+    for (index, field) in old.fields.iter().enumerate().skip(new.fields.len()) {
+        compatibility.forward = false;
+        compatibility.changes.push(Change::Removed {
+            index,
+            name: field.name.clone(),
+        });
+    }
+    compatibility
+}
+
+/// returns the change of type between two versions of the same field, `None` when the type did not change
+fn field_change(index: usize, before: &Field, after: &Field) -> Option<Change> {
+    if before.kind == after.kind && byte_order(before) == byte_order(after) {
+        return None;
+    }
+    match type_change(index, &before.kind, &after.kind) {
+        Some(change) if byte_order(before) == byte_order(after) => Some(change),
+        _ => Some(Change::Incompatible {
+            index,
+            from: before.kind.clone(),
+            to: after.kind.clone(),
+        }),
+    }
+}
+
+/// returns the byte order of a field when it matters for its type
+fn byte_order(field: &Field) -> Option<&DecodeOrder> {
+    field.order.as_ref().filter(|_| {
+        !matches!(
+            field.kind,
+            DecodeType::Bool | DecodeType::Int8 | DecodeType::Uint8 | DecodeType::Str(_)
+        )
+    })
+}
+
+/// returns the change between two different types of the same field, `None` when the change is incompatible
+fn type_change(index: usize, from: &DecodeType, to: &DecodeType) -> Option<Change> {
+    let widened = || Change::Widened {
+        index,
+        from: from.clone(),
+        to: to.clone(),
+    };
+    match (from, to) {
+        (DecodeType::Str(before), DecodeType::Str(after))
+        | (DecodeType::Bytes(before), DecodeType::Bytes(after)) => Some(Change::Resized {
+            index,
+            from: *before,
+            to: *after,
+        }),
+        (DecodeType::Array(item, before), DecodeType::Array(other, after)) if item == other => {
+            Some(Change::Resized {
+                index,
+                from: *before,
+                to: *after,
+            })
+        }
+        (DecodeType::Float32, DecodeType::Float64) => Some(widened()),
+        _ => {
+            let (from_signed, from_bits) = integer(from)?;
+            let (to_signed, to_bits) = integer(to)?;
+            // unsigned values fit in wider signed integers, signed values never fit in unsigned ones
+            let fits = match (from_signed, to_signed) {
+                (false, true) => to_bits > from_bits,
+                (true, false) => false,
+                _ => to_bits > from_bits,
+            };
+            fits.then(widened)
+        }
+    }
+}
+
+/// returns the signedness and the width of an integer type
+fn integer(kind: &DecodeType) -> Option<(bool, u32)> {
+    Some(match kind {
+        DecodeType::Int8 => (true, 8),
+        DecodeType::Int16 => (true, 16),
+        DecodeType::Int32 => (true, 32),
+        DecodeType::Int64 => (true, 64),
+        DecodeType::Int128 => (true, 128),
+        DecodeType::Uint8 => (false, 8),
+        DecodeType::Uint16 => (false, 16),
+        DecodeType::Uint32 => (false, 32),
+        DecodeType::Uint64 => (false, 64),
+        DecodeType::Uint128 => (false, 128),
+        _ => return None,
+    })
+}
+
+/// returns true for the types that produce a decoded value
+fn has_value(kind: &DecodeType) -> bool {
+    !matches!(
+        kind,
+        DecodeType::Const(_) | DecodeType::Reserved(_) | DecodeType::Padding(_)
+    )
+}
+
+/// returns the value decoded from zero bytes, which is used for the fields without default value
+fn zero_value(kind: &DecodeType) -> Option<DecodedData> {
+    match kind {
+        DecodeType::StrRef(_) => Some(DecodedData::Str(String::new())),
+        DecodeType::BytesRef(_) => Some(DecodedData::Bytes(vec![])),
+        DecodeType::ArrayRef(..) => Some(DecodedData::Array(vec![])),
+        DecodeType::Conditional(..) => Some(DecodedData::Optional(None)),
+        _ => {
+            let size = kind.fixed_size()?;
+            decoder::decode_packed(std::slice::from_ref(kind), &vec![0; size], DecodeOrder::Big)
+                .ok()?
+                .pop()
+        }
+    }
+}
+
+/// `decode_upgraded` decodes data written with the older version of a schema and returns it in the layout of the
+/// newer version: the values of renamed fields take their newer names, widened and resized values are converted, the
+/// fields added by the newer version take their default value, or the value decoded from zero bytes, and the fields
+/// removed by the newer version are dropped.
+///
+/// # Examples
+/// ```rust
+/// use packed_encoder::decoder::{DecodeOrder, DecodeType, DecodedData};
+/// use packed_encoder::evolution;
+/// use packed_encoder::schema::{Field, Schema};
+///
+/// let v1 = Schema::new().with_version(1).field("id", DecodeType::Uint16);
+/// let v2 = Schema::new()
+///     .with_version(2)
+///     .field("id", DecodeType::Uint32)
+///     .push(Field::new("flags", DecodeType::Uint8).with_default(DecodedData::Uint8(3)));
+/// let record = evolution::decode_upgraded(&v1, &v2, &[0, 7], DecodeOrder::Big).unwrap();
+/// assert_eq!(record.get_u32("id").unwrap(), 7);
+/// assert_eq!(record.get_u8("flags").unwrap(), 3);
+/// ```
+pub fn decode_upgraded(
+    old: &Schema,
+    new: &Schema,
+    buffer: &[u8],
+    decode_order: DecodeOrder,
+) -> Result<DecodedRecord, EvolutionError> {
+    let list = decoder::decode_list(&old.fields, buffer, 0, decode_order)
+        .0
+        .map_err(EvolutionError::Decode)?;
+
+    let mut values = vec![];
+    let mut positions = vec![];
+    for (index, field) in new.fields.iter().enumerate() {
+        if !has_value(&field.kind) {
+            positions.push(None);
+            continue;
+        }
+        let value = match old.fields.get(index) {
+            Some(before) => {
+                let change = field_change(index, before, field);
+                // fields of the same type, or of compatible types, both have a value
+                let position = match (&change, list.positions[index]) {
+                    (Some(Change::Incompatible { .. }), _) | (_, None) => {
+                        return Err(EvolutionError::Incompatible(index))
+                    }
+                    (_, Some(position)) => position,
+                };
+                let value = list.values[position].clone();
+                match change {
+                    None => value,
+                    Some(_) => convert(value, &field.kind).ok_or(EvolutionError::Value(index))?,
+                }
+            }
+            None => match &field.default {
+                Some(default) => default.clone(),
+                None => zero_value(&field.kind).ok_or(EvolutionError::MissingValue(index))?,
+            },
+        };
+        positions.push(Some(values.len()));
+        values.push(value);
+    }
+    Ok(DecodedRecord::from_positions(new, values, &positions))
+}
+
+/// converts a value to a wider type of the same field, `None` when it does not fit
+fn convert(value: DecodedData, kind: &DecodeType) -> Option<DecodedData> {
+    match (value, kind) {
+        (DecodedData::Float32(value), DecodeType::Float64) => {
+            Some(DecodedData::Float64(value as f64))
+        }
+        (DecodedData::Str(mut content), DecodeType::Str(size)) => {
+            // strings are padded with zero bytes, like the strings decoded from zero bytes
+            (content.len() <= *size).then(|| {
+                content.extend(std::iter::repeat_n('\0', size - content.len()));
+                DecodedData::Str(content)
+            })
+        }
+        (DecodedData::Bytes(mut content), DecodeType::Bytes(size)) => (content.len() <= *size)
+            .then(|| {
+                content.resize(*size, 0);
+                DecodedData::Bytes(content)
+            }),
+        (DecodedData::Array(mut items), DecodeType::Array(item, count)) => {
+            if items.len() > *count {
+                return None;
+            }
+            items.resize(*count, zero_value(item)?);
+            Some(DecodedData::Array(items))
+        }
+        (value, kind) => {
+            // integers go through the widest type of their signedness
+            let signed = match value {
+                DecodedData::Int8(value) => value as i128,
+                DecodedData::Int16(value) => value as i128,
+                DecodedData::Int32(value) => value as i128,
+                DecodedData::Int64(value) => value as i128,
+                DecodedData::Int128(value) => value,
+                DecodedData::Uint8(value) => value as i128,
+                DecodedData::Uint16(value) => value as i128,
+                DecodedData::Uint32(value) => value as i128,
+                DecodedData::Uint64(value) => value as i128,
+                DecodedData::Uint128(value) => {
+                    return match kind {
+                        DecodeType::Uint128 => Some(DecodedData::Uint128(value)),
+                        _ => None,
+                    }
+                }
+                _ => return None,
+            };
+            Some(match kind {
+                DecodeType::Int8 => DecodedData::Int8(signed.try_into().ok()?),
+                DecodeType::Int16 => DecodedData::Int16(signed.try_into().ok()?),
+                DecodeType::Int32 => DecodedData::Int32(signed.try_into().ok()?),
+                DecodeType::Int64 => DecodedData::Int64(signed.try_into().ok()?),
+                DecodeType::Int128 => DecodedData::Int128(signed),
+                DecodeType::Uint8 => DecodedData::Uint8(signed.try_into().ok()?),
+                DecodeType::Uint16 => DecodedData::Uint16(signed.try_into().ok()?),
+                DecodeType::Uint32 => DecodedData::Uint32(signed.try_into().ok()?),
+                DecodeType::Uint64 => DecodedData::Uint64(signed.try_into().ok()?),
+                DecodeType::Uint128 => DecodedData::Uint128(signed.try_into().ok()?),
+                _ => return None,
+            })
+        }
+    }
+}
@@ -0,0 +1,504 @@
+//! `fingerprint` computes short hashes of a layout, so that a receiver can refuse data written with another layout
+//! instead of misreading it.
+//!
+//! Fingerprints are computed over a canonical serialization of the types and of the byte order of every element,
+//! which does not depend on the platform nor on the in-memory representation of the types. Sizes, counts and indices
+//! are written as little-endian 64-bit integers. Names do not change the layout and are left out: references by name
+//! are replaced by the index of the field they refer to, and renaming a field keeps the fingerprint. Default values
+//! and versions are left out as well. `Predicate::Custom` conditions are serialized without their function, which has
+//! no stable representation.
+
+use crate::bitfield::{BitField, BitOrder, BitValue};
+use crate::checksum::ChecksumKind;
+use crate::decoder::{DecodeError, DecodeOrder, DecodeType, DecodedData, FieldRef, Predicate};
+use crate::encoder::{EncodeError, EncodeOrder, EncodeType};
+use crate::schema::{DecodedRecord, Field, Schema};
+use std::fmt;
+
+/// version of the canonical serialization, it is the first byte of the serialization
+pub const CANONICAL_VERSION: u8 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// `FingerprintKind` lists the hash functions that can be used to fingerprint a layout.
+/// Example: `FingerprintKind::Fnv1a32` computes a 32-bit FNV-1a hash of the canonical serialization.
+pub enum FingerprintKind {
+    /// 32-bit FNV-1a, 4 bytes
+    Fnv1a32,
+    /// 64-bit FNV-1a, 8 bytes
+    Fnv1a64,
+}
+
+impl FingerprintKind {
+    /// returns the number of bytes occupied by the fingerprint in a message header
+    pub fn size(&self) -> usize {
+        match self {
+            FingerprintKind::Fnv1a32 => 4,
+            FingerprintKind::Fnv1a64 => 8,
+        }
+    }
+
+    /// computes the hash of the given bytes, 32-bit hashes are returned in the low bits
+    pub fn compute(&self, data: &[u8]) -> u64 {
+        match self {
+            FingerprintKind::Fnv1a32 => fnv1a_32(data) as u64,
+            FingerprintKind::Fnv1a64 => fnv1a_64(data),
+        }
+    }
+}
+
+/// computes the 32-bit FNV-1a hash of the given bytes
+pub fn fnv1a_32(data: &[u8]) -> u32 {
+    data.iter().fold(0x811c_9dc5, |hash, byte| {
+        (hash ^ *byte as u32).wrapping_mul(0x0100_0193)
+    })
+}
+
+/// computes the 64-bit FNV-1a hash of the given bytes
+pub fn fnv1a_64(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+#[derive(Debug, Clone)]
+/// `FingerprintError` is returned when the fingerprint stored at the start of a buffer cannot be checked or does not
+/// match the expected layout.
+pub enum FingerprintError {
+    /// Mismatch occurs when the stored fingerprint differs from the fingerprint of the expected layout
+    Mismatch { expected: u64, found: u64 },
+    /// Truncated occurs when the buffer is shorter than the fingerprint
+    Truncated,
+    /// Encode occurs when the values cannot be encoded
+    Encode(EncodeError),
+    /// Decode occurs when the data following the fingerprint cannot be decoded
+    Decode(DecodeError),
+}
+
+impl fmt::Display for FingerprintError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FingerprintError::Mismatch { expected, found } => write!(
+                f,
+                "layout fingerprint {:#x} does not match the expected {:#x}",
+                found, expected
+            ),
+            FingerprintError::Truncated => write!(f, "buffer is shorter than the fingerprint"),
+            FingerprintError::Encode(error) => write!(f, "cannot encode values: {:?}", error),
+            FingerprintError::Decode(error) => write!(f, "cannot decode values: {:?}", error),
+        }
+    }
+}
+
+impl std::error::Error for FingerprintError {}
+
+/// `canonical` returns the canonical serialization of a list of types decoded with the given byte order.
+pub fn canonical(types: &[DecodeType], decode_order: DecodeOrder) -> Vec<u8> {
+    let mut out = vec![CANONICAL_VERSION];
+    write_size(&mut out, types.len());
+    for kind in types {
+        write_order(&mut out, &decode_order);
+        write_type(&mut out, kind, &[]);
+    }
+    out
+}
+
+/// `canonical_schema` returns the canonical serialization of a schema decoded with the given byte order, it is equal
+/// to the serialization of its types when the fields do not override the byte order nor refer to each other by name.
+pub fn canonical_schema(schema: &Schema, decode_order: DecodeOrder) -> Vec<u8> {
+    let mut out = vec![CANONICAL_VERSION];
+    write_size(&mut out, schema.fields.len());
+    for (index, field) in schema.fields.iter().enumerate() {
+        write_order(&mut out, field.order.as_ref().unwrap_or(&decode_order));
+        write_type(&mut out, &field.kind, &schema.fields[..index]);
+    }
+    out
+}
+
+/// `fingerprint` returns the fingerprint of a list of types decoded with the given byte order.
+pub fn fingerprint(kind: FingerprintKind, types: &[DecodeType], decode_order: DecodeOrder) -> u64 {
+    kind.compute(&canonical(types, decode_order))
+}
+
+/// `schema_fingerprint` returns the fingerprint of a schema decoded with the given byte order.
+///
+/// # Examples
+/// ```rust
+/// use packed_encoder::decoder::{DecodeOrder, DecodeType};
+/// use packed_encoder::fingerprint::{self, FingerprintKind};
+/// use packed_encoder::schema::Schema;
+///
+/// let schema = Schema::new().field("id", DecodeType::Uint32);
+/// let renamed = Schema::new().field("key", DecodeType::Uint32);
+/// let widened = Schema::new().field("id", DecodeType::Uint64);
+/// let print = |schema| fingerprint::schema_fingerprint(FingerprintKind::Fnv1a64, schema, DecodeOrder::Big);
+/// assert_eq!(print(&schema), print(&renamed));
+/// assert_ne!(print(&schema), print(&widened));
+/// ```
+pub fn schema_fingerprint(
+    kind: FingerprintKind,
+    schema: &Schema,
+    decode_order: DecodeOrder,
+) -> u64 {
+    kind.compute(&canonical_schema(schema, decode_order))
+}
+
+fn write_size(out: &mut Vec<u8>, size: usize) {
+    out.extend_from_slice(&(size as u64).to_le_bytes());
+}
+
+fn write_order(out: &mut Vec<u8>, order: &DecodeOrder) {
+    out.push(match order {
+        DecodeOrder::Big => 0,
+        DecodeOrder::Little => 1,
+    });
+}
+
+/// writes a reference, `fields` are the fields placed before the referring element and are used to resolve names
+fn write_ref(out: &mut Vec<u8>, field: &FieldRef, fields: &[Field]) {
+    let index = match field {
+        FieldRef::Index(index) => *index,
+        FieldRef::Name(name) => {
+            match fields
+                .iter()
+                .position(|field| field.name.as_deref() == Some(name.as_str()))
+            {
+                Some(index) => index,
+                // names that cannot be resolved do not decode, they are kept to tell them apart
+                None => {
+                    out.push(1);
+                    write_size(out, name.len());
+                    out.extend_from_slice(name.as_bytes());
+                    return;
+                }
+            }
+        }
+    };
+    out.push(0);
+    write_size(out, index);
+}
+
+fn write_list(out: &mut Vec<u8>, types: &[DecodeType]) {
+    write_size(out, types.len());
+    for kind in types {
+        write_type(out, kind, &[]);
+    }
+}
+
+/// writes a type, `fields` are the fields placed before it and are used to resolve references by name
+fn write_type(out: &mut Vec<u8>, kind: &DecodeType, fields: &[Field]) {
+    match kind {
+        DecodeType::Int8 => out.push(0x01),
+        DecodeType::Int16 => out.push(0x02),
+        DecodeType::Int32 => out.push(0x03),
+        DecodeType::Int64 => out.push(0x04),
+        DecodeType::Int128 => out.push(0x05),
+        DecodeType::Uint8 => out.push(0x06),
+        DecodeType::Uint16 => out.push(0x07),
+        DecodeType::Uint32 => out.push(0x08),
+        DecodeType::Uint64 => out.push(0x09),
+        DecodeType::Uint128 => out.push(0x0a),
+        DecodeType::Float32 => out.push(0x0b),
+        DecodeType::Float64 => out.push(0x0c),
+        DecodeType::Bool => out.push(0x0d),
+        DecodeType::Str(size) => {
+            out.push(0x10);
+            write_size(out, *size);
+        }
+        DecodeType::Bytes(size) => {
+            out.push(0x11);
+            write_size(out, *size);
+        }
+        DecodeType::BitGroup(bits, order) => {
+            out.push(0x12);
+            out.push(bit_order(*order));
+            write_size(out, bits.len());
+            for field in bits {
+                match field {
+                    BitField::Unsigned(width) => out.extend_from_slice(&[0, *width]),
+                    BitField::Signed(width) => out.extend_from_slice(&[1, *width]),
+                }
+            }
+        }
+        DecodeType::Checksum(checksum, range) => {
+            out.push(0x13);
+            out.push(checksum_code(*checksum));
+            write_size(out, range.start);
+            write_size(out, range.end);
+        }
+        DecodeType::Const(content) => {
+            out.push(0x14);
+            write_size(out, content.len());
+            out.extend_from_slice(content);
+        }
+        DecodeType::Reserved(size) => {
+            out.push(0x15);
+            write_size(out, *size);
+        }
+        DecodeType::Padding(size) => {
+            out.push(0x16);
+            write_size(out, *size);
+        }
+        DecodeType::StrRef(field) => {
+            out.push(0x17);
+            write_ref(out, field, fields);
+        }
+        DecodeType::BytesRef(field) => {
+            out.push(0x18);
+            write_ref(out, field, fields);
+        }
+        DecodeType::Array(item, count) => {
+            out.push(0x19);
+            write_size(out, *count);
+            write_type(out, item, &[]);
+        }
+        DecodeType::ArrayRef(item, field) => {
+            out.push(0x1a);
+            write_ref(out, field, fields);
+            write_type(out, item, &[]);
+        }
+        DecodeType::Conditional(field, predicate, types) => {
+            out.push(0x1b);
+            write_ref(out, field, fields);
+            write_predicate(out, predicate);
+            write_list(out, types);
+        }
+        DecodeType::Optional(None) => out.extend_from_slice(&[0x1c, 0]),
+        DecodeType::Optional(Some(types)) => {
+            out.extend_from_slice(&[0x1c, 1]);
+            write_list(out, types);
+        }
+    }
+}
+
+fn write_predicate(out: &mut Vec<u8>, predicate: &Predicate) {
+    match predicate {
+        Predicate::Equals(value) => {
+            out.push(0);
+            write_value(out, value);
+        }
+        Predicate::NotEquals(value) => {
+            out.push(1);
+            write_value(out, value);
+        }
+        Predicate::BitsSet(mask) => {
+            out.push(2);
+            out.extend_from_slice(&mask.to_le_bytes());
+        }
+        Predicate::BitsClear(mask) => {
+            out.push(3);
+            out.extend_from_slice(&mask.to_le_bytes());
+        }
+        Predicate::Custom(_) => out.push(4),
+    }
+}
+
+/// writes a value compared by a predicate, with the tag of its type
+fn write_value(out: &mut Vec<u8>, value: &DecodedData) {
+    match value {
+        DecodedData::Int8(value) => out.extend_from_slice(&[0x01, *value as u8]),
+        DecodedData::Int16(value) => {
+            out.push(0x02);
+            out.extend_from_slice(&value.to_le_bytes());
+        }
+        DecodedData::Int32(value) => {
+            out.push(0x03);
+            out.extend_from_slice(&value.to_le_bytes());
+        }
+        DecodedData::Int64(value) => {
+            out.push(0x04);
+            out.extend_from_slice(&value.to_le_bytes());
+        }
+        DecodedData::Int128(value) => {
+            out.push(0x05);
+            out.extend_from_slice(&value.to_le_bytes());
+        }
+        DecodedData::Uint8(value) => out.extend_from_slice(&[0x06, *value]),
+        DecodedData::Uint16(value) => {
+            out.push(0x07);
+            out.extend_from_slice(&value.to_le_bytes());
+        }
+        DecodedData::Uint32(value) => {
+            out.push(0x08);
+            out.extend_from_slice(&value.to_le_bytes());
+        }
+        DecodedData::Uint64(value) => {
+            out.push(0x09);
+            out.extend_from_slice(&value.to_le_bytes());
+        }
+        DecodedData::Uint128(value) => {
+            out.push(0x0a);
+            out.extend_from_slice(&value.to_le_bytes());
+        }
+        DecodedData::Float32(value) => {
+            out.push(0x0b);
+            out.extend_from_slice(&value.to_bits().to_le_bytes());
+        }
+        DecodedData::Float64(value) => {
+            out.push(0x0c);
+            out.extend_from_slice(&value.to_bits().to_le_bytes());
+        }
+        DecodedData::Bool(value) => out.extend_from_slice(&[0x0d, *value as u8]),
+        DecodedData::Str(content) => {
+            out.push(0x10);
+            write_size(out, content.len());
+            out.extend_from_slice(content.as_bytes());
+        }
+        DecodedData::Bytes(content) => {
+            out.push(0x11);
+            write_size(out, content.len());
+            out.extend_from_slice(content);
+        }
+        DecodedData::BitGroup(values, order) => {
+            out.push(0x12);
+            out.push(bit_order(*order));
+            write_size(out, values.len());
+            for value in values {
+                match value {
+                    BitValue::Unsigned(width, value) => {
+                        out.extend_from_slice(&[0, *width]);
+                        out.extend_from_slice(&value.to_le_bytes());
+                    }
+                    BitValue::Signed(width, value) => {
+                        out.extend_from_slice(&[1, *width]);
+                        out.extend_from_slice(&value.to_le_bytes());
+                    }
+                }
+            }
+        }
+        DecodedData::Array(items) => {
+            out.push(0x19);
+            write_size(out, items.len());
+            for item in items {
+                write_value(out, item);
+            }
+        }
+        DecodedData::Optional(None) => out.extend_from_slice(&[0x1c, 0]),
+        DecodedData::Optional(Some(values)) => {
+            out.extend_from_slice(&[0x1c, 1]);
+            write_size(out, values.len());
+            for value in values {
+                write_value(out, value);
+            }
+        }
+    }
+}
+
+fn bit_order(order: BitOrder) -> u8 {
+    match order {
+        BitOrder::Msb => 0,
+        BitOrder::Lsb => 1,
+    }
+}
+
+/// returns the code of a checksum kind, codes must never change
+fn checksum_code(kind: ChecksumKind) -> u8 {
+    match kind {
+        ChecksumKind::Crc8 => 1,
+        ChecksumKind::Crc16Ccitt => 2,
+        ChecksumKind::Crc32 => 3,
+        ChecksumKind::Crc32c => 4,
+        ChecksumKind::Adler32 => 5,
+        ChecksumKind::Sum8 => 6,
+        ChecksumKind::Xor8 => 7,
+    }
+}
+
+/// `write_fingerprint` appends a fingerprint of the given kind to the buffer in the given byte order, it is meant
+/// for message headers.
+pub fn write_fingerprint(
+    kind: FingerprintKind,
+    fingerprint: u64,
+    buffer: &mut Vec<u8>,
+    encode_order: EncodeOrder,
+) {
+    match (kind, encode_order) {
+        (FingerprintKind::Fnv1a32, EncodeOrder::Big) => {
+            buffer.extend_from_slice(&(fingerprint as u32).to_be_bytes())
+        }
+        (FingerprintKind::Fnv1a32, EncodeOrder::Little) => {
+            buffer.extend_from_slice(&(fingerprint as u32).to_le_bytes())
+        }
+        (FingerprintKind::Fnv1a64, EncodeOrder::Big) => {
+            buffer.extend_from_slice(&fingerprint.to_be_bytes())
+        }
+        (FingerprintKind::Fnv1a64, EncodeOrder::Little) => {
+            buffer.extend_from_slice(&fingerprint.to_le_bytes())
+        }
+    }
+}
+
+/// `check_fingerprint` verifies that the buffer starts with the expected fingerprint, stored in the given byte order,
+/// and returns the bytes following it.
+pub fn check_fingerprint(
+    kind: FingerprintKind,
+    expected: u64,
+    buffer: &[u8],
+    decode_order: DecodeOrder,
+) -> Result<&[u8], FingerprintError> {
+    let stored = buffer
+        .get(..kind.size())
+        .ok_or(FingerprintError::Truncated)?;
+    let found = match decode_order {
+        DecodeOrder::Big => stored
+            .iter()
+            .fold(0u64, |value, byte| value << 8 | *byte as u64),
+        DecodeOrder::Little => stored
+            .iter()
+            .rev()
+            .fold(0u64, |value, byte| value << 8 | *byte as u64),
+    };
+    if found != expected {
+        return Err(FingerprintError::Mismatch { expected, found });
+    }
+    Ok(&buffer[kind.size()..])
+}
+
+/// `encode_fingerprinted` encodes the values with the schema and prepends the fingerprint of the schema.
+pub fn encode_fingerprinted(
+    kind: FingerprintKind,
+    schema: &Schema,
+    values: &[EncodeType],
+    encode_order: EncodeOrder,
+) -> Result<Vec<u8>, FingerprintError> {
+    let fingerprint = schema_fingerprint(kind, schema, encode_order.clone().into());
+    let mut buffer = Vec::with_capacity(kind.size());
+    write_fingerprint(kind, fingerprint, &mut buffer, encode_order.clone());
+    let encoded = schema
+        .encode(values, encode_order)
+        .map_err(FingerprintError::Encode)?;
+    buffer.extend_from_slice(&encoded);
+    Ok(buffer)
+}
+
+/// `decode_fingerprinted` checks that the buffer starts with the fingerprint of the schema before decoding the
+/// record that follows it, data written with another layout is refused with `FingerprintError::Mismatch`.
+///
+/// # Examples
+/// ```rust
+/// use packed_encoder::decoder::{DecodeOrder, DecodeType};
+/// use packed_encoder::encoder::{EncodeOrder, EncodeType};
+/// use packed_encoder::fingerprint::{self, FingerprintError, FingerprintKind};
+/// use packed_encoder::schema::Schema;
+///
+/// let sender = Schema::new().field("id", DecodeType::Uint16).field("value", DecodeType::Int32);
+/// let receiver = Schema::new().field("id", DecodeType::Uint16).field("value", DecodeType::Int16);
+/// let values = [EncodeType::Uint16(1), EncodeType::Int32(-5)];
+/// let encoded = fingerprint::encode_fingerprinted(FingerprintKind::Fnv1a32, &sender, &values, EncodeOrder::Big).unwrap();
+///
+/// let record = fingerprint::decode_fingerprinted(FingerprintKind::Fnv1a32, &sender, &encoded, DecodeOrder::Big).unwrap();
+/// assert_eq!(record.get_i32("value").unwrap(), -5);
+/// let refused = fingerprint::decode_fingerprinted(FingerprintKind::Fnv1a32, &receiver, &encoded, DecodeOrder::Big);
+/// assert!(matches!(refused, Err(FingerprintError::Mismatch { .. })));
+/// ```
+pub fn decode_fingerprinted(
+    kind: FingerprintKind,
+    schema: &Schema,
+    buffer: &[u8],
+    decode_order: DecodeOrder,
+) -> Result<DecodedRecord, FingerprintError> {
+    let expected = schema_fingerprint(kind, schema, decode_order.clone());
+    let payload = check_fingerprint(kind, expected, buffer, decode_order.clone())?;
+    schema
+        .decode(payload, decode_order)
+        .map_err(FingerprintError::Decode)
+}
@@ -0,0 +1,435 @@
+use crate::decoder::DecodeOrder;
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+use std::fmt;
+use std::io::{self, Read, Write};
+
+/// `DEFAULT_MAX_FRAME_SIZE` is the largest payload accepted by a `FrameFormat` unless it is changed with
+/// `FrameFormat::with_max_frame_size`.
+pub const DEFAULT_MAX_FRAME_SIZE: usize = 16 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// `LengthPrefix` selects how the payload length is written in the frame header.
+pub enum LengthPrefix {
+    /// Uint16 writes the length as a `u16`, payloads are limited to 65535 bytes
+    Uint16,
+    /// Uint32 writes the length as a `u32`
+    Uint32,
+    /// Varint writes the length as an unsigned LEB128 varint, like protobuf, using 1 byte for lengths below 128
+    Varint,
+}
+
+#[derive(Debug)]
+/// `FrameError` is returned when a frame cannot be written or read.
+pub enum FrameError {
+    /// TooLarge occurs when a payload, or the length announced by a frame header, is larger than the maximum frame
+    /// size
+    TooLarge { size: u64, max: usize },
+    /// BadMagic occurs when a frame does not start with the magic bytes of the format
+    BadMagic,
+    /// InvalidLength occurs when a varint length is longer than 10 bytes or does not fit in `u64`
+    InvalidLength,
+    /// TypeId occurs when a frame has a type id and the format has none, or the other way round
+    TypeId,
+    /// Truncated occurs when a stream ends in the middle of a frame
+    Truncated,
+    /// Io occurs when the underlying reader or writer fails
+    Io(io::Error),
+}
+
+impl fmt::Display for FrameError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FrameError::TooLarge { size, max } => {
+                write!(
+                    f,
+                    "frame of {} bytes is larger than the maximum of {} bytes",
+                    size, max
+                )
+            }
+            FrameError::BadMagic => write!(f, "frame does not start with the magic bytes"),
+            FrameError::InvalidLength => write!(f, "invalid varint length"),
+            FrameError::TypeId => write!(f, "frame type id does not match the format"),
+            FrameError::Truncated => write!(f, "stream ends in the middle of a frame"),
+            FrameError::Io(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for FrameError {}
+
+impl From<io::Error> for FrameError {
+    fn from(error: io::Error) -> FrameError {
+        FrameError::Io(error)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// `Frame` is a payload, usually the output of `encode_packed`, along with its optional type id.
+pub struct Frame {
+    pub type_id: Option<u16>,
+    pub payload: Vec<u8>,
+}
+
+impl Frame {
+    /// creates a frame without type id
+    pub fn new(payload: Vec<u8>) -> Frame {
+        Frame {
+            type_id: None,
+            payload,
+        }
+    }
+
+    /// creates a frame with a type id, for formats built with `FrameFormat::with_type_id`
+    pub fn typed(type_id: u16, payload: Vec<u8>) -> Frame {
+        Frame {
+            type_id: Some(type_id),
+            payload,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+/// `FrameFormat` describes the header written before each payload: the optional magic bytes, the optional `u16`
+/// type id and the payload length, in this order. Multi-byte fields use the byte order of the format, big endian by
+/// default.
+///
+/// # Examples
+/// ```rust
+/// use packed_encoder::framing::{Frame, FrameFormat, LengthPrefix};
+///
+/// let format = FrameFormat::new(LengthPrefix::Uint16).with_magic(b"PK").with_type_id();
+/// let encoded = format.encode(&Frame::typed(3, vec![1, 2, 3])).unwrap();
+/// assert_eq!(encoded, [b'P', b'K', 0, 3, 0, 3, 1, 2, 3]);
+///
+/// let (frame, consumed) = format.decode(&encoded).unwrap().unwrap();
+/// assert_eq!((frame.type_id, consumed), (Some(3), 9));
+/// ```
+pub struct FrameFormat {
+    pub length: LengthPrefix,
+    pub order: DecodeOrder,
+    pub magic: Vec<u8>,
+    pub type_id: bool,
+    pub max_frame_size: usize,
+}
+
+impl FrameFormat {
+    /// creates a big endian format with the given length prefix, no magic, no type id and the default maximum
+    /// frame size
+    pub fn new(length: LengthPrefix) -> FrameFormat {
+        FrameFormat {
+            length,
+            order: DecodeOrder::Big,
+            magic: vec![],
+            type_id: false,
+            max_frame_size: DEFAULT_MAX_FRAME_SIZE,
+        }
+    }
+
+    /// sets the byte order of the length and the type id
+    pub fn with_order(mut self, order: DecodeOrder) -> FrameFormat {
+        self.order = order;
+        self
+    }
+
+    /// starts every frame with the given bytes, they are checked when reading
+    pub fn with_magic(mut self, magic: &[u8]) -> FrameFormat {
+        self.magic = magic.to_vec();
+        self
+    }
+
+    /// adds a `u16` type id to every frame
+    pub fn with_type_id(mut self) -> FrameFormat {
+        self.type_id = true;
+        self
+    }
+
+    /// sets the largest payload size accepted when writing and reading frames
+    pub fn with_max_frame_size(mut self, max_frame_size: usize) -> FrameFormat {
+        self.max_frame_size = max_frame_size;
+        self
+    }
+
+    fn max_size(&self) -> usize {
+        match self.length {
+            LengthPrefix::Uint16 => self.max_frame_size.min(u16::MAX as usize),
+            LengthPrefix::Uint32 => self.max_frame_size.min(u32::MAX as usize),
+            LengthPrefix::Varint => self.max_frame_size,
+        }
+    }
+
+    /// appends the encoded frame to the buffer
+    pub fn encode_into(&self, frame: &Frame, buffer: &mut Vec<u8>) -> Result<(), FrameError> {
+        if frame.type_id.is_some() != self.type_id {
+            return Err(FrameError::TypeId);
+        }
+        if frame.payload.len() > self.max_size() {
+            return Err(FrameError::TooLarge {
+                size: frame.payload.len() as u64,
+                max: self.max_size(),
+            });
+        }
+
+        buffer.extend_from_slice(&self.magic);
+        if let Some(type_id) = frame.type_id {
+            let mut bytes = [0u8; 2];
+            match self.order {
+                DecodeOrder::Big => BigEndian::write_u16(&mut bytes, type_id),
+                DecodeOrder::Little => LittleEndian::write_u16(&mut bytes, type_id),
+            }
+            buffer.extend_from_slice(&bytes);
+        }
+        let size = frame.payload.len();
+        match self.length {
+            LengthPrefix::Uint16 => {
+                let mut bytes = [0u8; 2];
+                match self.order {
+                    DecodeOrder::Big => BigEndian::write_u16(&mut bytes, size as u16),
+                    DecodeOrder::Little => LittleEndian::write_u16(&mut bytes, size as u16),
+                }
+                buffer.extend_from_slice(&bytes);
+            }
+            LengthPrefix::Uint32 => {
+                let mut bytes = [0u8; 4];
+                match self.order {
+                    DecodeOrder::Big => BigEndian::write_u32(&mut bytes, size as u32),
+                    DecodeOrder::Little => LittleEndian::write_u32(&mut bytes, size as u32),
+                }
+                buffer.extend_from_slice(&bytes);
+            }
+            LengthPrefix::Varint => encode_varint(size as u64, buffer),
+        }
+        buffer.extend_from_slice(&frame.payload);
+        Ok(())
+    }
+
+    /// returns the encoded frame
+    pub fn encode(&self, frame: &Frame) -> Result<Vec<u8>, FrameError> {
+        let mut buffer = Vec::with_capacity(self.magic.len() + 6 + frame.payload.len());
+        self.encode_into(frame, &mut buffer)?;
+        Ok(buffer)
+    }
+
+    /// writes the encoded frame to the writer
+    pub fn write<W: Write>(&self, frame: &Frame, writer: &mut W) -> Result<(), FrameError> {
+        writer.write_all(&self.encode(frame)?)?;
+        Ok(())
+    }
+
+    /// decodes the frame at the start of the buffer and returns it with the number of bytes it uses. `Ok(None)` is
+    /// returned when the buffer does not hold a whole frame yet. The announced length is checked against the maximum
+    /// frame size as soon as it is read, before waiting for the payload.
+    pub fn decode(&self, buffer: &[u8]) -> Result<Option<(Frame, usize)>, FrameError> {
+        let magic_size = self.magic.len().min(buffer.len());
+        if buffer[..magic_size] != self.magic[..magic_size] {
+            return Err(FrameError::BadMagic);
+        }
+        let mut offset = self.magic.len();
+
+        let mut type_id = None;
+        if self.type_id {
+            if buffer.len() < offset + 2 {
+                return Ok(None);
+            }
+            let bytes = &buffer[offset..offset + 2];
+            type_id = Some(match self.order {
+                DecodeOrder::Big => BigEndian::read_u16(bytes),
+                DecodeOrder::Little => LittleEndian::read_u16(bytes),
+            });
+            offset += 2;
+        }
+
+        let size = match self.length {
+            LengthPrefix::Uint16 | LengthPrefix::Uint32 => {
+                let width = if self.length == LengthPrefix::Uint16 {
+                    2
+                } else {
+                    4
+                };
+                if buffer.len() < offset + width {
+                    return Ok(None);
+                }
+                let bytes = &buffer[offset..offset + width];
+                offset += width;
+                match (self.length, &self.order) {
+                    (LengthPrefix::Uint16, DecodeOrder::Big) => BigEndian::read_u16(bytes) as u64,
+                    (LengthPrefix::Uint16, DecodeOrder::Little) => {
+                        LittleEndian::read_u16(bytes) as u64
+                    }
+                    (_, DecodeOrder::Big) => BigEndian::read_u32(bytes) as u64,
+                    (_, DecodeOrder::Little) => LittleEndian::read_u32(bytes) as u64,
+                }
+            }
+            LengthPrefix::Varint => match decode_varint(&buffer[offset.min(buffer.len())..])? {
+                Some((size, used)) => {
+                    offset += used;
+                    size
+                }
+                None => return Ok(None),
+            },
+        };
+        if size > self.max_frame_size as u64 {
+            return Err(FrameError::TooLarge {
+                size,
+                max: self.max_frame_size,
+            });
+        }
+
+        // the maximum can be as large as `usize::MAX`, which leaves no room for the header
+        let end = usize::try_from(size)
+            .ok()
+            .and_then(|size| offset.checked_add(size))
+            .ok_or(FrameError::TooLarge {
+                size,
+                max: self.max_frame_size,
+            })?;
+        if buffer.len() < end {
+            return Ok(None);
+        }
+        let frame = Frame {
+            type_id,
+            payload: buffer[offset..end].to_vec(),
+        };
+        Ok(Some((frame, end)))
+    }
+}
+
+/// appends `value` as an unsigned LEB128 varint
+pub fn encode_varint(mut value: u64, buffer: &mut Vec<u8>) {
+    while value >= 0x80 {
+        buffer.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    buffer.push(value as u8);
+}
+
+/// decodes the unsigned LEB128 varint at the start of the buffer and returns it with the number of bytes it uses,
+/// `Ok(None)` is returned when the buffer ends before the last byte of the varint.
+pub fn decode_varint(buffer: &[u8]) -> Result<Option<(u64, usize)>, FrameError> {
+    let mut value = 0u64;
+    for (index, byte) in buffer.iter().enumerate() {
+        // the tenth byte only holds the highest bit of a u64
+        if index == 9 && *byte > 1 {
+            return Err(FrameError::InvalidLength);
+        }
+        value |= ((byte & 0x7f) as u64) << (7 * index);
+        if byte & 0x80 == 0 {
+            return Ok(Some((value, index + 1)));
+        }
+    }
+    Ok(None)
+}
+
+#[derive(Debug, Clone)]
+/// `FrameSplitter` splits a byte stream received in chunks of any size, like the reads of a TCP socket, back into
+/// frames. After an error the stream cannot be resynchronized and the same error is returned again.
+///
+/// # Examples
+/// ```rust
+/// use packed_encoder::framing::{Frame, FrameFormat, FrameSplitter, LengthPrefix};
+///
+/// let format = FrameFormat::new(LengthPrefix::Varint);
+/// let mut stream = format.encode(&Frame::new(vec![1, 2])).unwrap();
+/// stream.extend(format.encode(&Frame::new(vec![3])).unwrap());
+///
+/// let mut splitter = FrameSplitter::new(format);
+/// splitter.push(&stream[..2]);
+/// assert_eq!(splitter.next_frame().unwrap(), None);
+/// splitter.push(&stream[2..]);
+/// assert_eq!(splitter.next_frame().unwrap(), Some(Frame::new(vec![1, 2])));
+/// assert_eq!(splitter.next_frame().unwrap(), Some(Frame::new(vec![3])));
+/// ```
+pub struct FrameSplitter {
+    format: FrameFormat,
+    buffer: Vec<u8>,
+}
+
+impl FrameSplitter {
+    /// creates a splitter for frames of the given format
+    pub fn new(format: FrameFormat) -> FrameSplitter {
+        FrameSplitter {
+            format,
+            buffer: vec![],
+        }
+    }
+
+    /// appends received bytes
+    pub fn push(&mut self, bytes: &[u8]) {
+        self.buffer.extend_from_slice(bytes);
+    }
+
+    /// returns the number of received bytes that are not part of a returned frame yet
+    pub fn buffered(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// returns the next whole frame, `Ok(None)` when more bytes are needed
+    pub fn next_frame(&mut self) -> Result<Option<Frame>, FrameError> {
+        match self.format.decode(&self.buffer)? {
+            Some((frame, consumed)) => {
+                self.buffer.drain(..consumed);
+                Ok(Some(frame))
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+/// `FrameReader` reads frames from a reader, like a `TcpStream`. As an iterator it stops after the first error.
+pub struct FrameReader<R: Read> {
+    reader: R,
+    splitter: FrameSplitter,
+    // set once the iterator returned an error or the end of the stream
+    done: bool,
+}
+
+impl<R: Read> FrameReader<R> {
+    /// creates a reader of frames of the given format
+    pub fn new(reader: R, format: FrameFormat) -> FrameReader<R> {
+        FrameReader {
+            reader,
+            splitter: FrameSplitter::new(format),
+            done: false,
+        }
+    }
+
+    /// returns the next frame, `Ok(None)` when the stream ends between two frames and `FrameError::Truncated` when it
+    /// ends inside one
+    pub fn read_frame(&mut self) -> Result<Option<Frame>, FrameError> {
+        let mut chunk = [0u8; 4096];
+        loop {
+            if let Some(frame) = self.splitter.next_frame()? {
+                return Ok(Some(frame));
+            }
+            let size = match self.reader.read(&mut chunk) {
+                Ok(size) => size,
+                Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+                Err(error) => return Err(error.into()),
+            };
+            if size == 0 {
+                return match self.splitter.buffered() {
+                    0 => Ok(None),
+                    _ => Err(FrameError::Truncated),
+                };
+            }
+            self.splitter.push(&chunk[..size]);
+        }
+    }
+
+    /// returns the underlying reader
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+impl<R: Read> Iterator for FrameReader<R> {
+    type Item = Result<Frame, FrameError>;
+
+    fn next(&mut self) -> Option<Result<Frame, FrameError>> {
+        if self.done {
+            return None;
+        }
+        let next = self.read_frame().transpose();
+        self.done = !matches!(next, Some(Ok(_)));
+        next
+    }
+}
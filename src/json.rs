@@ -0,0 +1,532 @@
+//! `json` converts decoded values to JSON and builds the values to encode from JSON, so that records can be shown to
+//! people and test records can be written by hand.
+//!
+//! Integers up to 32 bits, floats, booleans and strings map to the JSON types of the same kind. Non-finite floats
+//! are written as `null`. 64-bit and 128-bit integers cannot always be represented by the numbers of JSON readers
+//! such as JavaScript, `IntegerPolicy` tells whether they are written as numbers or as decimal strings; 128-bit
+//! integers that do not fit in 64 bits are always written as strings. Bytes are written as hexadecimal or base64
+//! strings, see `BytesPolicy`. Bit-groups and arrays are written as arrays, optional and conditional elements as
+//! arrays of the nested values or `null` when absent.
+//!
+//! `from_json` accepts both forms of integers whatever the policy. It accepts an array with one value per decoded
+//! value of the schema or an object keyed by field name, unnamed fields being keyed by `#` followed by the index of
+//! their value. Constant, reserved and padding fields take no value, checksums are computed again and the length
+//! fields referred to by other fields are back-filled, so their values can be left out of objects.
+
+use crate::bitfield::{BitField, BitValue};
+use crate::decoder::{DecodeType, DecodedData, FieldRef};
+use crate::encoder::EncodeType;
+use crate::schema::{DecodedRecord, Schema};
+use serde_json::{Map, Value};
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// `IntegerPolicy` tells how 64-bit and 128-bit integers are written, and bit-fields wider than 32 bits.
+pub enum IntegerPolicy {
+    /// Number writes the integers as JSON numbers, 128-bit integers that do not fit in 64 bits are written as strings
+    Number,
+    /// String writes the integers as decimal strings, they are read back without loss by any JSON reader
+    String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// `BytesPolicy` tells how bytes are written and read.
+pub enum BytesPolicy {
+    /// Hex writes two lowercase hexadecimal digits per byte
+    Hex,
+    /// Base64 writes the bytes with the standard base64 alphabet and padding
+    Base64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// `JsonOptions` holds the policies used to convert values to and from JSON.
+///
+/// # Examples
+/// ```rust
+/// use packed_encoder::decoder::DecodedData;
+/// use packed_encoder::json::{self, BytesPolicy, IntegerPolicy, JsonOptions};
+///
+/// let options = JsonOptions::new()
+///     .with_integers(IntegerPolicy::String)
+///     .with_bytes(BytesPolicy::Base64);
+/// let values = [DecodedData::Uint64(7), DecodedData::Bytes(vec![1, 2, 3])];
+/// assert_eq!(json::to_json(&values, &options).to_string(), r#"["7","AQID"]"#);
+/// ```
+pub struct JsonOptions {
+    pub integers: IntegerPolicy,
+    pub bytes: BytesPolicy,
+}
+
+impl JsonOptions {
+    /// creates options that write 64-bit integers as numbers and bytes as hexadecimal strings
+    pub fn new() -> JsonOptions {
+        JsonOptions {
+            integers: IntegerPolicy::Number,
+            bytes: BytesPolicy::Hex,
+        }
+    }
+
+    /// sets how 64-bit and 128-bit integers are written
+    pub fn with_integers(mut self, integers: IntegerPolicy) -> JsonOptions {
+        self.integers = integers;
+        self
+    }
+
+    /// sets how bytes are written and read
+    pub fn with_bytes(mut self, bytes: BytesPolicy) -> JsonOptions {
+        self.bytes = bytes;
+        self
+    }
+}
+
+impl Default for JsonOptions {
+    fn default() -> Self {
+        JsonOptions::new()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// `JsonError` is returned when a JSON document does not hold the values of a schema. Fields are described by their
+/// name, or by `#` followed by the index of their value when unnamed.
+pub enum JsonError {
+    /// Shape occurs when the document is neither an array nor an object
+    Shape,
+    /// Count occurs when an array does not hold one value per decoded value of the schema
+    Count { expected: usize, found: usize },
+    /// MissingField occurs when an object has no value for the given field
+    MissingField(String),
+    /// Value occurs when the value of the given field does not match its type
+    Value {
+        field: String,
+        expected: &'static str,
+    },
+}
+
+impl fmt::Display for JsonError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            JsonError::Shape => write!(f, "document is neither an array nor an object"),
+            JsonError::Count { expected, found } => {
+                write!(f, "array holds {} values, expected {}", found, expected)
+            }
+            JsonError::MissingField(field) => {
+                write!(f, "document has no value for field {}", field)
+            }
+            JsonError::Value { field, expected } => {
+                write!(f, "value of field {} is not {}", field, expected)
+            }
+        }
+    }
+}
+
+impl std::error::Error for JsonError {}
+
+/// `to_json` converts the given values to a JSON array holding one item per value.
+pub fn to_json(values: &[DecodedData], options: &JsonOptions) -> Value {
+    Value::Array(
+        values
+            .iter()
+            .map(|value| value_to_json(value, options))
+            .collect(),
+    )
+}
+
+/// `record_to_json` converts a decoded record to a JSON object keyed by field name, unnamed values are keyed by `#`
+/// followed by their index. Keys keep the order of the fields.
+///
+/// # Examples
+/// ```rust
+/// use packed_encoder::decoder::{DecodeOrder, DecodeType};
+/// use packed_encoder::json::{self, JsonOptions};
+/// use packed_encoder::schema::Schema;
+///
+/// let schema = Schema::new()
+///     .field("id", DecodeType::Uint16)
+///     .unnamed(DecodeType::Bytes(2));
+/// let record = schema.decode(&[0, 7, 0xca, 0xfe], DecodeOrder::Big).unwrap();
+/// let document = json::record_to_json(&record, &JsonOptions::new());
+/// assert_eq!(document.to_string(), r##"{"id":7,"#1":"cafe"}"##);
+/// ```
+pub fn record_to_json(record: &DecodedRecord, options: &JsonOptions) -> Value {
+    let mut object = Map::new();
+    for (idx, (name, value)) in record.iter().enumerate() {
+        let key = name.map_or_else(|| format!("#{}", idx), str::to_owned);
+        object.insert(key, value_to_json(value, options));
+    }
+    Value::Object(object)
+}
+
+/// `value_to_json` converts a single value to JSON.
+pub fn value_to_json(value: &DecodedData, options: &JsonOptions) -> Value {
+    match value {
+        DecodedData::Int8(v) => Value::from(*v),
+        DecodedData::Int16(v) => Value::from(*v),
+        DecodedData::Int32(v) => Value::from(*v),
+        DecodedData::Int64(v) => int_to_json(*v as i128, true, options),
+        DecodedData::Int128(v) => int_to_json(*v, true, options),
+        DecodedData::Uint8(v) => Value::from(*v),
+        DecodedData::Uint16(v) => Value::from(*v),
+        DecodedData::Uint32(v) => Value::from(*v),
+        DecodedData::Uint64(v) => uint_to_json(*v as u128, true, options),
+        DecodedData::Uint128(v) => uint_to_json(*v, true, options),
+        // going through the shortest representation of the f32 keeps 0.1 from becoming 0.10000000149011612
+        DecodedData::Float32(v) => Value::from(v.to_string().parse::<f64>().unwrap_or(f64::NAN)),
+        DecodedData::Float64(v) => Value::from(*v),
+        DecodedData::Bool(v) => Value::Bool(*v),
+        DecodedData::Str(v) => Value::String(v.clone()),
+        DecodedData::Bytes(v) => Value::String(match options.bytes {
+            BytesPolicy::Hex => encode_hex(v),
+            BytesPolicy::Base64 => encode_base64(v),
+        }),
+        DecodedData::BitGroup(values, _) => Value::Array(
+            values
+                .iter()
+                .map(|value| match value {
+                    BitValue::Unsigned(width, v) => uint_to_json(*v as u128, *width > 32, options),
+                    BitValue::Signed(width, v) => int_to_json(*v as i128, *width > 32, options),
+                })
+                .collect(),
+        ),
+        DecodedData::Array(items) => to_json(items, options),
+        DecodedData::Optional(None) => Value::Null,
+        DecodedData::Optional(Some(values)) => to_json(values, options),
+    }
+}
+
+/// `from_json` builds the values to encode with the given schema from a JSON array or object, see the module
+/// documentation for the accepted forms.
+///
+/// # Examples
+/// ```rust
+/// use packed_encoder::decoder::{DecodeType, FieldRef};
+/// use packed_encoder::encoder::EncodeOrder;
+/// use packed_encoder::json::{self, JsonOptions};
+/// use packed_encoder::schema::Schema;
+///
+/// let schema = Schema::new()
+///     .field("len", DecodeType::Uint8)
+///     .field("name", DecodeType::StrRef(FieldRef::Name("len".to_owned())));
+/// let document = serde_json::json!({ "name": "abc" });
+/// let values = json::from_json(&document, &schema, &JsonOptions::new()).unwrap();
+/// assert_eq!(schema.encode(&values, EncodeOrder::Big).unwrap(), [3, b'a', b'b', b'c']);
+/// ```
+pub fn from_json(
+    document: &Value,
+    schema: &Schema,
+    options: &JsonOptions,
+) -> Result<Vec<EncodeType>, JsonError> {
+    let expected = schema
+        .fields
+        .iter()
+        .filter(|field| has_value(&field.kind))
+        .count();
+    match document {
+        Value::Array(items) if items.len() != expected => {
+            return Err(JsonError::Count {
+                expected,
+                found: items.len(),
+            })
+        }
+        Value::Array(_) | Value::Object(_) => {}
+        _ => return Err(JsonError::Shape),
+    }
+
+    let lengths = length_fields(schema);
+    let mut position = 0;
+    let mut values = Vec::with_capacity(schema.fields.len());
+    for (idx, field) in schema.fields.iter().enumerate() {
+        if !has_value(&field.kind) {
+            values.push(fixed_value(&field.kind));
+            continue;
+        }
+        let label = field
+            .name
+            .clone()
+            .unwrap_or_else(|| format!("#{}", position));
+        let item = match document {
+            Value::Array(items) => items.get(position),
+            Value::Object(object) => object.get(&label),
+            _ => None,
+        };
+        position += 1;
+
+        let value = match item {
+            Some(item) => element_from_json(&field.kind, item, options),
+            // back-filled by the encoder or computed again, any placeholder will do
+            None if lengths.contains(&idx) || matches!(field.kind, DecodeType::Checksum(..)) => {
+                element_from_json(&field.kind, &Value::from(0), options)
+            }
+            None => return Err(JsonError::MissingField(label)),
+        };
+        values.push(value.ok_or_else(|| JsonError::Value {
+            expected: expected_name(&field.kind),
+            field: label,
+        })?);
+    }
+    Ok(values)
+}
+
+/// returns false for the elements that are left out of the decoded values
+fn has_value(kind: &DecodeType) -> bool {
+    !matches!(
+        kind,
+        DecodeType::Const(_) | DecodeType::Reserved(_) | DecodeType::Padding(_)
+    )
+}
+
+/// returns the value encoding an element left out of the decoded values, padding is written as zeros
+fn fixed_value(kind: &DecodeType) -> EncodeType {
+    match kind {
+        DecodeType::Const(content) => EncodeType::Const(content.clone()),
+        DecodeType::Reserved(size) | DecodeType::Padding(size) => EncodeType::Reserved(*size),
+        _ => unreachable!("fixed_value is only called for elements without a value"),
+    }
+}
+
+/// returns the indices of the fields whose value is back-filled with the length of another field
+fn length_fields(schema: &Schema) -> Vec<usize> {
+    schema
+        .fields
+        .iter()
+        .filter_map(|field| match &field.kind {
+            DecodeType::StrRef(target)
+            | DecodeType::BytesRef(target)
+            | DecodeType::ArrayRef(_, target) => match target {
+                FieldRef::Index(idx) => Some(*idx),
+                FieldRef::Name(name) => schema
+                    .fields
+                    .iter()
+                    .position(|field| field.name.as_deref() == Some(name.as_str())),
+            },
+            _ => None,
+        })
+        .collect()
+}
+
+/// builds the values of a nested list from the items of a JSON array
+fn list_from_json(
+    types: &[DecodeType],
+    items: &[Value],
+    options: &JsonOptions,
+) -> Option<Vec<EncodeType>> {
+    let mut items = items.iter();
+    let values = types
+        .iter()
+        .map(|kind| match has_value(kind) {
+            true => element_from_json(kind, items.next()?, options),
+            false => Some(fixed_value(kind)),
+        })
+        .collect::<Option<Vec<_>>>()?;
+    items.next().is_none().then_some(values)
+}
+
+fn element_from_json(
+    kind: &DecodeType,
+    value: &Value,
+    options: &JsonOptions,
+) -> Option<EncodeType> {
+    Some(match kind {
+        DecodeType::Int8 => EncodeType::Int8(int_from_json(value)?.try_into().ok()?),
+        DecodeType::Int16 => EncodeType::Int16(int_from_json(value)?.try_into().ok()?),
+        DecodeType::Int32 => EncodeType::Int32(int_from_json(value)?.try_into().ok()?),
+        DecodeType::Int64 => EncodeType::Int64(int_from_json(value)?.try_into().ok()?),
+        DecodeType::Int128 => EncodeType::Int128(int_from_json(value)?),
+        DecodeType::Uint8 => EncodeType::Uint8(uint_from_json(value)?.try_into().ok()?),
+        DecodeType::Uint16 => EncodeType::Uint16(uint_from_json(value)?.try_into().ok()?),
+        DecodeType::Uint32 => EncodeType::Uint32(uint_from_json(value)?.try_into().ok()?),
+        DecodeType::Uint64 => EncodeType::Uint64(uint_from_json(value)?.try_into().ok()?),
+        DecodeType::Uint128 => EncodeType::Uint128(uint_from_json(value)?),
+        DecodeType::Float32 => EncodeType::Float32(value.as_f64()? as f32),
+        DecodeType::Float64 => EncodeType::Float64(value.as_f64()?),
+        DecodeType::Bool => EncodeType::Bool(value.as_bool()?),
+        DecodeType::Str(_) => EncodeType::Str(value.as_str()?.to_owned()),
+        DecodeType::StrRef(field) => EncodeType::StrRef(value.as_str()?.to_owned(), field.clone()),
+        DecodeType::Bytes(_) => EncodeType::Bytes(bytes_from_json(value, options)?),
+        DecodeType::BytesRef(field) => {
+            EncodeType::BytesRef(bytes_from_json(value, options)?, field.clone())
+        }
+        DecodeType::BitGroup(fields, order) => {
+            let items = value
+                .as_array()
+                .filter(|items| items.len() == fields.len())?;
+            let values = fields
+                .iter()
+                .zip(items)
+                .map(|(field, item)| match field {
+                    BitField::Unsigned(width) => Some(BitValue::Unsigned(
+                        *width,
+                        uint_from_json(item)?.try_into().ok()?,
+                    )),
+                    BitField::Signed(width) => Some(BitValue::Signed(
+                        *width,
+                        int_from_json(item)?.try_into().ok()?,
+                    )),
+                })
+                .collect::<Option<Vec<_>>>()?;
+            EncodeType::BitGroup(values, *order)
+        }
+        DecodeType::Checksum(kind, range) => EncodeType::Checksum(*kind, range.clone()),
+        DecodeType::Const(_) | DecodeType::Reserved(_) | DecodeType::Padding(_) => {
+            fixed_value(kind)
+        }
+        DecodeType::Array(item, count) => {
+            let items = value.as_array().filter(|items| items.len() == *count)?;
+            EncodeType::Array(items_from_json(item, items, options)?)
+        }
+        DecodeType::ArrayRef(item, field) => EncodeType::ArrayRef(
+            items_from_json(item, value.as_array()?, options)?,
+            field.clone(),
+        ),
+        DecodeType::Conditional(field, predicate, types) => {
+            let values = match value {
+                Value::Null => vec![],
+                _ => list_from_json(types, value.as_array()?, options)?,
+            };
+            EncodeType::Conditional(field.clone(), predicate.clone(), values)
+        }
+        DecodeType::Optional(types) => match (types, value) {
+            (_, Value::Null) => EncodeType::Optional(None),
+            (Some(types), Value::Array(items)) => {
+                EncodeType::Optional(Some(list_from_json(types, items, options)?))
+            }
+            _ => return None,
+        },
+    })
+}
+
+fn items_from_json(
+    item: &DecodeType,
+    items: &[Value],
+    options: &JsonOptions,
+) -> Option<Vec<EncodeType>> {
+    items
+        .iter()
+        .map(|value| element_from_json(item, value, options))
+        .collect()
+}
+
+/// returns the JSON type expected for the given element, used in error messages
+fn expected_name(kind: &DecodeType) -> &'static str {
+    match kind {
+        DecodeType::Int8
+        | DecodeType::Int16
+        | DecodeType::Int32
+        | DecodeType::Int64
+        | DecodeType::Int128
+        | DecodeType::Uint8
+        | DecodeType::Uint16
+        | DecodeType::Uint32
+        | DecodeType::Uint64
+        | DecodeType::Uint128 => "an integer in range",
+        DecodeType::Float32 | DecodeType::Float64 => "a number",
+        DecodeType::Bool => "a boolean",
+        DecodeType::Str(_) | DecodeType::StrRef(_) => "a string",
+        DecodeType::Bytes(_) | DecodeType::BytesRef(_) => "an encoded byte string",
+        DecodeType::BitGroup(..) => "an array of bit-field values",
+        DecodeType::Array(..) | DecodeType::ArrayRef(..) => "an array of items",
+        DecodeType::Conditional(..) | DecodeType::Optional(_) => "an array of values or null",
+        DecodeType::Checksum(..)
+        | DecodeType::Const(_)
+        | DecodeType::Reserved(_)
+        | DecodeType::Padding(_) => "any value",
+    }
+}
+
+fn int_to_json(value: i128, wide: bool, options: &JsonOptions) -> Value {
+    match i64::try_from(value) {
+        Ok(value) if !wide || options.integers == IntegerPolicy::Number => Value::from(value),
+        _ => Value::String(value.to_string()),
+    }
+}
+
+fn uint_to_json(value: u128, wide: bool, options: &JsonOptions) -> Value {
+    match u64::try_from(value) {
+        Ok(value) if !wide || options.integers == IntegerPolicy::Number => Value::from(value),
+        _ => Value::String(value.to_string()),
+    }
+}
+
+fn int_from_json(value: &Value) -> Option<i128> {
+    match value {
+        Value::Number(number) => number
+            .as_i64()
+            .map(i128::from)
+            .or_else(|| number.as_u64().map(i128::from)),
+        Value::String(text) => text.parse().ok(),
+        _ => None,
+    }
+}
+
+fn uint_from_json(value: &Value) -> Option<u128> {
+    match value {
+        Value::Number(number) => number.as_u64().map(u128::from),
+        Value::String(text) => text.parse().ok(),
+        _ => None,
+    }
+}
+
+fn bytes_from_json(value: &Value, options: &JsonOptions) -> Option<Vec<u8>> {
+    match options.bytes {
+        BytesPolicy::Hex => decode_hex(value.as_str()?),
+        BytesPolicy::Base64 => decode_base64(value.as_str()?),
+    }
+}
+
+fn encode_hex(data: &[u8]) -> String {
+    data.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+// `is_multiple_of` needs rust 1.87
+#[allow(clippy::manual_is_multiple_of)]
+fn decode_hex(text: &str) -> Option<Vec<u8>> {
+    // `from_str_radix` accepts a leading sign, so the digits are checked first
+    if text.len() % 2 != 0 || !text.bytes().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    text.as_bytes()
+        .chunks(2)
+        .map(|pair| {
+            let digits = std::str::from_utf8(pair).ok()?;
+            u8::from_str_radix(digits, 16).ok()
+        })
+        .collect()
+}
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn encode_base64(data: &[u8]) -> String {
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bits = chunk.iter().enumerate().fold(0u32, |bits, (idx, byte)| {
+            bits | (*byte as u32) << (16 - 8 * idx)
+        });
+        for idx in 0..4 {
+            if idx <= chunk.len() {
+                out.push(BASE64[(bits >> (18 - 6 * idx) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+/// decodes standard base64, the padding is optional
+fn decode_base64(text: &str) -> Option<Vec<u8>> {
+    let digits = text.trim_end_matches('=').as_bytes();
+    if text.len() - digits.len() > 2 || digits.len() % 4 == 1 {
+        return None;
+    }
+    let mut out = Vec::with_capacity(digits.len() * 3 / 4);
+    for chunk in digits.chunks(4) {
+        let mut bits = 0u32;
+        for (idx, digit) in chunk.iter().enumerate() {
+            let value = BASE64.iter().position(|c| c == digit)? as u32;
+            bits |= value << (18 - 6 * idx);
+        }
+        for idx in 0..chunk.len() - 1 {
+            out.push((bits >> (16 - 8 * idx)) as u8);
+        }
+    }
+    Some(out)
+}
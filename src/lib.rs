@@ -1,5 +1,34 @@
+// the original tests compare booleans with `assert_eq!`
+#![cfg_attr(test, allow(clippy::bool_assert_comparison))]
+
+pub mod bitfield;
+#[cfg(feature = "bytes")]
+pub mod buf;
+#[cfg(feature = "capi")]
+pub mod capi;
+pub mod cheader;
+pub mod checksum;
+#[cfg(feature = "async")]
+pub mod codec;
+pub mod convert;
 pub mod decoder;
+pub mod describe;
 pub mod encoder;
+pub mod evolution;
+pub mod fingerprint;
+pub mod framing;
+#[cfg(feature = "json")]
+pub mod json;
+#[cfg(feature = "mmap")]
+pub mod mmap;
+pub mod perlpack;
+pub mod pystruct;
+pub mod recordfile;
+pub mod schema;
+pub mod text;
+pub mod tlv;
+#[cfg(feature = "wasm")]
+pub mod wasm;
 
 #[test]
 fn test_encode_numbers_little() {
@@ -250,3 +279,2450 @@ fn test_struct_interoperability() {
         assert_eq!((*sample).z == 256, true);
     }
 }
+
+#[test]
+fn test_bit_groups() {
+    let fields = vec![
+        bitfield::BitValue::Unsigned(3, 5),
+        bitfield::BitValue::Unsigned(5, 19),
+        bitfield::BitValue::Signed(12, -3),
+    ];
+
+    let to_encode = &[
+        encoder::EncodeType::BitGroup(fields.clone(), bitfield::BitOrder::Msb),
+        encoder::EncodeType::BitGroup(fields.clone(), bitfield::BitOrder::Lsb),
+        encoder::EncodeType::Uint8(7),
+    ];
+
+    let encoded_result = encoder::encode_packed(to_encode, encoder::EncodeOrder::Little);
+    assert!(encoded_result.is_ok());
+
+    let encoded_data = encoded_result.unwrap();
+    assert_eq!(encoded_data, vec![0xb3, 0xff, 0xd0, 0x9d, 0xfd, 0x0f, 7]);
+
+    let declared = vec![
+        bitfield::BitField::Unsigned(3),
+        bitfield::BitField::Unsigned(5),
+        bitfield::BitField::Signed(12),
+    ];
+
+    let to_decode = &[
+        decoder::DecodeType::BitGroup(declared.clone(), bitfield::BitOrder::Msb),
+        decoder::DecodeType::BitGroup(declared, bitfield::BitOrder::Lsb),
+        decoder::DecodeType::Uint8,
+    ];
+
+    let decoded_result =
+        decoder::decode_packed(to_decode, &encoded_data, decoder::DecodeOrder::Little);
+    assert!(decoded_result.is_ok());
+
+    let decoded_data = decoded_result.unwrap();
+    assert_eq!(
+        decoded_data[0],
+        decoder::DecodedData::BitGroup(fields.clone(), bitfield::BitOrder::Msb)
+    );
+    assert_eq!(
+        decoded_data[1],
+        decoder::DecodedData::BitGroup(fields, bitfield::BitOrder::Lsb)
+    );
+    assert_eq!(decoded_data[2], decoder::DecodedData::Uint8(7));
+}
+
+#[test]
+fn test_bit_group_overflow() {
+    let to_encode = &[encoder::EncodeType::BitGroup(
+        vec![bitfield::BitValue::Signed(4, 8)],
+        bitfield::BitOrder::Msb,
+    )];
+
+    let encoded_result = encoder::encode_packed(to_encode, encoder::EncodeOrder::Little);
+    assert!(matches!(
+        encoded_result,
+        Err(encoder::EncodeError::BitField(bitfield::BitValue::Signed(
+            4, 8
+        )))
+    ));
+}
+
+#[test]
+fn test_checksum_algorithms() {
+    let data = b"123456789";
+    assert_eq!(checksum::ChecksumKind::Crc8.compute(data), 0xf4);
+    assert_eq!(checksum::ChecksumKind::Crc16Ccitt.compute(data), 0x29b1);
+    assert_eq!(checksum::ChecksumKind::Crc32.compute(data), 0xcbf43926);
+    assert_eq!(checksum::ChecksumKind::Crc32c.compute(data), 0xe3069283);
+    assert_eq!(checksum::ChecksumKind::Adler32.compute(data), 0x091e01de);
+    assert_eq!(checksum::ChecksumKind::Sum8.compute(data), 0xdd);
+    assert_eq!(checksum::ChecksumKind::Xor8.compute(data), 0x31);
+}
+
+#[test]
+fn test_checksum_trailer() {
+    let to_encode = &[
+        encoder::EncodeType::Uint8(0x01),
+        encoder::EncodeType::Str("123456789".to_owned()),
+        encoder::EncodeType::Checksum(checksum::ChecksumKind::Crc16Ccitt, 1..2),
+        encoder::EncodeType::Checksum(checksum::ChecksumKind::Crc32, 0..3),
+    ];
+
+    let encoded_result = encoder::encode_packed(to_encode, encoder::EncodeOrder::Big);
+    assert!(encoded_result.is_ok());
+
+    let mut encoded_data = encoded_result.unwrap();
+    assert_eq!(encoded_data.len(), 16);
+    assert_eq!(encoded_data[10..12], [0x29, 0xb1]);
+
+    let to_decode = &[
+        decoder::DecodeType::Uint8,
+        decoder::DecodeType::Str(9),
+        decoder::DecodeType::Checksum(checksum::ChecksumKind::Crc16Ccitt, 1..2),
+        decoder::DecodeType::Checksum(checksum::ChecksumKind::Crc32, 0..3),
+    ];
+
+    let decoded_result =
+        decoder::decode_packed(to_decode, &encoded_data, decoder::DecodeOrder::Big);
+    assert!(decoded_result.is_ok());
+    assert_eq!(
+        decoded_result.unwrap()[2],
+        decoder::DecodedData::Uint16(0x29b1)
+    );
+
+    // corrupt the payload
+    encoded_data[0] = 0x02;
+    let decoded_result =
+        decoder::decode_packed(to_decode, &encoded_data, decoder::DecodeOrder::Big);
+    assert!(matches!(
+        decoded_result,
+        Err(decoder::DecodeError::ChecksumMismatch { index: 3, .. })
+    ));
+
+    // checksums can only cover the elements placed before them
+    let invalid = &[encoder::EncodeType::Checksum(
+        checksum::ChecksumKind::Crc8,
+        0..1,
+    )];
+    assert!(matches!(
+        encoder::encode_packed(invalid, encoder::EncodeOrder::Big),
+        Err(encoder::EncodeError::Checksum(
+            checksum::ChecksumKind::Crc8,
+            _
+        ))
+    ));
+}
+
+#[test]
+fn test_const_and_reserved() {
+    let to_encode = &[
+        encoder::EncodeType::Const(b"PK".to_vec()),
+        encoder::EncodeType::Uint8(3),
+        encoder::EncodeType::Reserved(3),
+        encoder::EncodeType::Uint16(512),
+    ];
+
+    let encoded_result = encoder::encode_packed(to_encode, encoder::EncodeOrder::Little);
+    assert!(encoded_result.is_ok());
+
+    let mut encoded_data = encoded_result.unwrap();
+    assert_eq!(encoded_data, vec![b'P', b'K', 3, 0, 0, 0, 0, 2]);
+
+    let to_decode = &[
+        decoder::DecodeType::Const(b"PK".to_vec()),
+        decoder::DecodeType::Uint8,
+        decoder::DecodeType::Reserved(3),
+        decoder::DecodeType::Uint16,
+    ];
+
+    // constant and reserved elements are not part of the decoded values
+    let decoded_result =
+        decoder::decode_packed(to_decode, &encoded_data, decoder::DecodeOrder::Little);
+    assert!(decoded_result.is_ok());
+    assert_eq!(
+        decoded_result.unwrap(),
+        vec![
+            decoder::DecodedData::Uint8(3),
+            decoder::DecodedData::Uint16(512)
+        ]
+    );
+
+    encoded_data[3] = 0xff;
+    let decoded_result =
+        decoder::decode_packed(to_decode, &encoded_data, decoder::DecodeOrder::Little);
+    assert!(matches!(
+        decoded_result,
+        Err(decoder::DecodeError::NonZeroReserved(2))
+    ));
+
+    // padding ignores the content of the reserved bytes
+    let lenient = &[
+        decoder::DecodeType::Const(b"PK".to_vec()),
+        decoder::DecodeType::Uint8,
+        decoder::DecodeType::Padding(3),
+        decoder::DecodeType::Uint16,
+    ];
+    let decoded_result =
+        decoder::decode_packed(lenient, &encoded_data, decoder::DecodeOrder::Little);
+    assert!(decoded_result.is_ok());
+
+    encoded_data[0] = b'Z';
+    let decoded_result =
+        decoder::decode_packed(lenient, &encoded_data, decoder::DecodeOrder::Little);
+    assert!(matches!(
+        decoded_result,
+        Err(decoder::DecodeError::ConstMismatch(0))
+    ));
+
+    // sizes that overflow the offset are out of bounds
+    for kind in [
+        decoder::DecodeType::Str(usize::MAX),
+        decoder::DecodeType::Bytes(usize::MAX),
+        decoder::DecodeType::Reserved(usize::MAX),
+        decoder::DecodeType::Padding(usize::MAX),
+    ] {
+        let decoded_result = decoder::decode_packed(
+            &[decoder::DecodeType::Uint8, kind],
+            &encoded_data,
+            decoder::DecodeOrder::Little,
+        );
+        assert!(matches!(
+            decoded_result,
+            Err(decoder::DecodeError::IndexOutOfBounds)
+        ));
+    }
+}
+
+#[test]
+fn test_length_references() {
+    let to_encode = &[
+        encoder::EncodeType::Uint8(0),
+        encoder::EncodeType::Uint16(0),
+        encoder::EncodeType::Int32(-1),
+        encoder::EncodeType::StrRef("hello".to_owned(), decoder::FieldRef::Index(1)),
+        encoder::EncodeType::ArrayRef(
+            vec![
+                encoder::EncodeType::Uint16(1),
+                encoder::EncodeType::Uint16(2),
+                encoder::EncodeType::Uint16(3),
+            ],
+            decoder::FieldRef::Index(0),
+        ),
+        encoder::EncodeType::Array(vec![
+            encoder::EncodeType::Bytes(vec![1, 2]),
+            encoder::EncodeType::Bytes(vec![3, 4]),
+        ]),
+    ];
+
+    let encoded_result = encoder::encode_packed(to_encode, encoder::EncodeOrder::Big);
+    assert!(encoded_result.is_ok());
+
+    // both length fields are back-filled from the payloads
+    let encoded_data = encoded_result.unwrap();
+    assert_eq!(encoded_data[0..3], [3, 0, 5]);
+    assert_eq!(encoded_data.len(), 1 + 2 + 4 + 5 + 6 + 4);
+
+    let to_decode = &[
+        decoder::DecodeType::Uint8,
+        decoder::DecodeType::Uint16,
+        decoder::DecodeType::Int32,
+        decoder::DecodeType::StrRef(decoder::FieldRef::Index(1)),
+        decoder::DecodeType::ArrayRef(
+            Box::new(decoder::DecodeType::Uint16),
+            decoder::FieldRef::Index(0),
+        ),
+        decoder::DecodeType::Array(Box::new(decoder::DecodeType::Bytes(2)), 2),
+    ];
+
+    let decoded_result =
+        decoder::decode_packed(to_decode, &encoded_data, decoder::DecodeOrder::Big);
+    assert!(decoded_result.is_ok());
+
+    let decoded_data = decoded_result.unwrap();
+    assert_eq!(
+        decoded_data[3],
+        decoder::DecodedData::Str("hello".to_owned())
+    );
+    assert_eq!(
+        decoded_data[4],
+        decoder::DecodedData::Array(vec![
+            decoder::DecodedData::Uint16(1),
+            decoder::DecodedData::Uint16(2),
+            decoder::DecodedData::Uint16(3),
+        ])
+    );
+    assert_eq!(
+        decoded_data[5],
+        decoder::DecodedData::Array(vec![
+            decoder::DecodedData::Bytes(vec![1, 2]),
+            decoder::DecodedData::Bytes(vec![3, 4]),
+        ])
+    );
+
+    // lengths must fit in the referred field and references must point to integers placed before
+    let overflow = &[
+        encoder::EncodeType::Uint8(0),
+        encoder::EncodeType::BytesRef(vec![0; 256], decoder::FieldRef::Index(0)),
+    ];
+    assert!(matches!(
+        encoder::encode_packed(overflow, encoder::EncodeOrder::Big),
+        Err(encoder::EncodeError::Length(256))
+    ));
+
+    let forward = &[
+        decoder::DecodeType::BytesRef(decoder::FieldRef::Index(1)),
+        decoder::DecodeType::Uint8,
+    ];
+    assert!(matches!(
+        decoder::decode_packed(forward, &encoded_data, decoder::DecodeOrder::Big),
+        Err(decoder::DecodeError::InvalidData(0))
+    ));
+
+    // counts are bounded by the size of the items, empty items are allowed up to the remaining bytes
+    let items = |item: decoder::DecodeType| {
+        [
+            decoder::DecodeType::Uint8,
+            decoder::DecodeType::ArrayRef(Box::new(item), decoder::FieldRef::Index(0)),
+        ]
+    };
+    assert!(matches!(
+        decoder::decode_packed(
+            &items(decoder::DecodeType::Uint16),
+            &[2, 0, 1, 0],
+            decoder::DecodeOrder::Big
+        ),
+        Err(decoder::DecodeError::IndexOutOfBounds)
+    ));
+    let empty = decoder::DecodeType::Array(Box::new(decoder::DecodeType::Uint8), 0);
+    assert_eq!(
+        decoder::decode_packed(&items(empty), &[2, 0, 0], decoder::DecodeOrder::Big).unwrap()[1],
+        decoder::DecodedData::Array(vec![
+            decoder::DecodedData::Array(vec![]),
+            decoder::DecodedData::Array(vec![]),
+        ])
+    );
+    let names = &[
+        decoder::DecodeType::Uint8,
+        decoder::DecodeType::StrRef(decoder::FieldRef::Name("len".to_owned())),
+    ];
+    assert!(matches!(
+        decoder::decode_packed(names, &[0], decoder::DecodeOrder::Big),
+        Err(decoder::DecodeError::InvalidData(1))
+    ));
+
+    // lengths are written in the byte order of their own field, not in the one of the element referring to them
+    let schema = schema::Schema::new()
+        .field("len", decoder::DecodeType::Uint16)
+        .push(
+            schema::Field::new(
+                "samples",
+                decoder::DecodeType::ArrayRef(
+                    Box::new(decoder::DecodeType::Uint16),
+                    decoder::FieldRef::Name("len".to_owned()),
+                ),
+            )
+            .with_order(decoder::DecodeOrder::Big),
+        )
+        .push(
+            schema::Field::new("size", decoder::DecodeType::Uint16)
+                .with_order(decoder::DecodeOrder::Big),
+        )
+        .field(
+            "data",
+            decoder::DecodeType::BytesRef(decoder::FieldRef::Name("size".to_owned())),
+        );
+    let to_encode = &[
+        encoder::EncodeType::Uint16(0),
+        encoder::EncodeType::ArrayRef(
+            vec![encoder::EncodeType::Uint16(1)],
+            decoder::FieldRef::Name("len".to_owned()),
+        ),
+        encoder::EncodeType::Uint16(0),
+        encoder::EncodeType::BytesRef(vec![7, 8], decoder::FieldRef::Name("size".to_owned())),
+    ];
+    let encoded_data = schema
+        .encode(to_encode, encoder::EncodeOrder::Little)
+        .unwrap();
+    assert_eq!(encoded_data, [1, 0, 0, 1, 0, 2, 7, 8]);
+    let record = schema
+        .decode(&encoded_data, decoder::DecodeOrder::Little)
+        .unwrap();
+    assert_eq!(record.get_u16("len"), Ok(1));
+    assert_eq!(record.get_bytes("data"), Ok(&[7, 8][..]));
+}
+
+#[test]
+fn test_conditional_elements() {
+    fn is_new_version(value: &decoder::DecodedData) -> bool {
+        matches!(value, decoder::DecodedData::Uint8(version) if *version >= 2)
+    }
+
+    let encode_with_flags = |flags: u8| {
+        encoder::encode_packed(
+            &[
+                encoder::EncodeType::Uint8(2),
+                encoder::EncodeType::Uint8(flags),
+                encoder::EncodeType::Conditional(
+                    decoder::FieldRef::Index(1),
+                    decoder::Predicate::BitsSet(0x01),
+                    vec![
+                        encoder::EncodeType::Uint8(0),
+                        encoder::EncodeType::StrRef(
+                            "extra".to_owned(),
+                            decoder::FieldRef::Index(0),
+                        ),
+                    ],
+                ),
+                encoder::EncodeType::Conditional(
+                    decoder::FieldRef::Index(0),
+                    decoder::Predicate::Custom(is_new_version),
+                    vec![encoder::EncodeType::Uint16(0xabcd)],
+                ),
+            ],
+            encoder::EncodeOrder::Little,
+        )
+    };
+
+    let to_decode = &[
+        decoder::DecodeType::Uint8,
+        decoder::DecodeType::Uint8,
+        decoder::DecodeType::Conditional(
+            decoder::FieldRef::Index(1),
+            decoder::Predicate::BitsSet(0x01),
+            vec![
+                decoder::DecodeType::Uint8,
+                decoder::DecodeType::StrRef(decoder::FieldRef::Index(0)),
+            ],
+        ),
+        decoder::DecodeType::Conditional(
+            decoder::FieldRef::Index(0),
+            decoder::Predicate::Custom(is_new_version),
+            vec![decoder::DecodeType::Uint16],
+        ),
+    ];
+
+    // flag set, the nested list is present
+    let encoded_data = encode_with_flags(0x03).unwrap();
+    assert_eq!(
+        encoded_data,
+        vec![2, 3, 5, b'e', b'x', b't', b'r', b'a', 0xcd, 0xab]
+    );
+
+    let decoded_data =
+        decoder::decode_packed(to_decode, &encoded_data, decoder::DecodeOrder::Little).unwrap();
+    assert_eq!(
+        decoded_data[2],
+        decoder::DecodedData::Optional(Some(vec![
+            decoder::DecodedData::Uint8(5),
+            decoder::DecodedData::Str("extra".to_owned()),
+        ]))
+    );
+    assert_eq!(
+        decoded_data[3],
+        decoder::DecodedData::Optional(Some(vec![decoder::DecodedData::Uint16(0xabcd)]))
+    );
+
+    // flag cleared, the nested list is skipped on both sides
+    let encoded_data = encode_with_flags(0x02).unwrap();
+    assert_eq!(encoded_data, vec![2, 2, 0xcd, 0xab]);
+
+    let decoded_data =
+        decoder::decode_packed(to_decode, &encoded_data, decoder::DecodeOrder::Little).unwrap();
+    assert_eq!(decoded_data[2], decoder::DecodedData::Optional(None));
+    assert_eq!(decoded_data.len(), 4);
+
+    // conditions on length elements see the back-filled length, like the decoder
+    let to_encode = &[
+        encoder::EncodeType::Uint8(0),
+        encoder::EncodeType::Conditional(
+            decoder::FieldRef::Index(0),
+            decoder::Predicate::Equals(decoder::DecodedData::Uint8(3)),
+            vec![encoder::EncodeType::Uint8(9)],
+        ),
+        encoder::EncodeType::BytesRef(b"abc".to_vec(), decoder::FieldRef::Index(0)),
+    ];
+    let encoded_data = encoder::encode_packed(to_encode, encoder::EncodeOrder::Little).unwrap();
+    assert_eq!(encoded_data, vec![3, 9, b'a', b'b', b'c']);
+    let to_decode = &[
+        decoder::DecodeType::Uint8,
+        decoder::DecodeType::Conditional(
+            decoder::FieldRef::Index(0),
+            decoder::Predicate::Equals(decoder::DecodedData::Uint8(3)),
+            vec![decoder::DecodeType::Uint8],
+        ),
+        decoder::DecodeType::BytesRef(decoder::FieldRef::Index(0)),
+    ];
+    let decoded_data =
+        decoder::decode_packed(to_decode, &encoded_data, decoder::DecodeOrder::Little).unwrap();
+    assert_eq!(
+        decoded_data[1],
+        decoder::DecodedData::Optional(Some(vec![decoder::DecodedData::Uint8(9)]))
+    );
+}
+
+#[test]
+fn test_named_schema() {
+    let schema = schema::Schema::new()
+        .field("magic", decoder::DecodeType::Const(b"PK".to_vec()))
+        .field("seq", decoder::DecodeType::Uint32)
+        .field("len", decoder::DecodeType::Uint8)
+        .unnamed(decoder::DecodeType::Reserved(1))
+        .field(
+            "name",
+            decoder::DecodeType::StrRef(decoder::FieldRef::Name("len".to_owned())),
+        );
+
+    let encoded_result = schema.encode(
+        &[
+            encoder::EncodeType::Const(b"PK".to_vec()),
+            encoder::EncodeType::Uint32(42),
+            encoder::EncodeType::Uint8(0),
+            encoder::EncodeType::Reserved(1),
+            encoder::EncodeType::StrRef(
+                "sensor".to_owned(),
+                decoder::FieldRef::Name("len".to_owned()),
+            ),
+        ],
+        encoder::EncodeOrder::Big,
+    );
+    assert!(encoded_result.is_ok());
+
+    let encoded_data = encoded_result.unwrap();
+    assert_eq!(encoded_data[6], 6);
+
+    let decoded_result = schema.decode(&encoded_data, decoder::DecodeOrder::Big);
+    assert!(decoded_result.is_ok());
+
+    let record = decoded_result.unwrap();
+    assert_eq!(record.len(), 3);
+    assert_eq!(record.get_u32("seq").unwrap(), 42);
+    assert_eq!(record.get_u32(0).unwrap(), 42);
+    assert_eq!(record.get_str("name").unwrap(), "sensor");
+    assert_eq!(record["len"], decoder::DecodedData::Uint8(6));
+    assert_eq!(record.name(2), Some("name"));
+
+    let mismatch = record.get_u16("seq").unwrap_err();
+    assert_eq!(mismatch.to_string(), "field 'seq' holds Uint32, not u16");
+    assert_eq!(
+        record.get_u8("magic"),
+        Err(schema::RecordError::MissingField("'magic'".to_owned()))
+    );
+}
+
+#[test]
+fn test_decoded_data_conversions() {
+    let decoded_data = decoder::decode_packed(
+        &[
+            decoder::DecodeType::Uint16,
+            decoder::DecodeType::Int8,
+            decoder::DecodeType::Uint64,
+            decoder::DecodeType::Str(2),
+            decoder::DecodeType::Bytes(2),
+        ],
+        &[
+            0x34, 0x12, 0xff, 0, 0, 0, 0, 0, 0, 0, 0x80, b'o', b'k', 1, 2,
+        ],
+        decoder::DecodeOrder::Little,
+    )
+    .unwrap();
+
+    // exact and widening conversions
+    assert_eq!(u16::try_from(&decoded_data[0]), Ok(0x1234));
+    assert_eq!(decoded_data[0].as_u64(), Ok(0x1234));
+    assert_eq!(decoded_data[0].as_i64(), Ok(0x1234));
+    assert_eq!(i32::try_from(decoded_data[0].clone()), Ok(0x1234));
+    assert_eq!(decoded_data[1].as_i64(), Ok(-1));
+    assert_eq!(decoded_data[2].as_u64(), Ok(1 << 63));
+    assert_eq!(decoded_data[3].as_str(), Ok("ok"));
+    assert_eq!(decoded_data[4].as_bytes(), Ok(&[1u8, 2][..]));
+    assert_eq!(
+        String::try_from(decoded_data[3].clone()),
+        Ok("ok".to_owned())
+    );
+    assert_eq!(Vec::<u8>::try_from(decoded_data[4].clone()), Ok(vec![1, 2]));
+
+    // narrowing, sign changes and type mismatches are rejected
+    assert_eq!(
+        u8::try_from(&decoded_data[0]),
+        Err(convert::ConversionError {
+            expected: "u8",
+            found: "Uint16"
+        })
+    );
+    assert!(decoded_data[1].as_u64().is_err());
+    assert!(decoded_data[2].as_i64().is_err());
+    assert_eq!(
+        decoded_data[3].as_bytes().unwrap_err().to_string(),
+        "cannot convert Str into &[u8]"
+    );
+}
+
+#[test]
+fn test_encode_decode_conversions() {
+    let to_encode: Vec<encoder::EncodeType> = vec![
+        7u8.into(),
+        (-2i32).into(),
+        "name".into(),
+        vec![1u8, 2, 3].into(),
+        encoder::EncodeType::Array(vec![1u16.into(), 2u16.into()]),
+        encoder::EncodeType::BitGroup(
+            vec![
+                bitfield::BitValue::Signed(4, -1),
+                bitfield::BitValue::Unsigned(4, 3),
+            ],
+            bitfield::BitOrder::Lsb,
+        ),
+        encoder::EncodeType::Optional(Some(vec![9u64.into()])),
+    ];
+
+    let encoded_data = encoder::encode_packed(&to_encode, encoder::EncodeOrder::Little).unwrap();
+
+    // every value converts into the data the decoder returns, and describes its own type
+    let expected: Vec<decoder::DecodedData> = to_encode
+        .iter()
+        .map(|element| decoder::DecodedData::try_from(element).unwrap())
+        .collect();
+    let types: Vec<decoder::DecodeType> = expected.iter().map(|data| data.decode_type()).collect();
+
+    let decoded_data =
+        decoder::decode_packed(&types, &encoded_data, decoder::DecodeOrder::Little).unwrap();
+    assert_eq!(decoded_data, expected);
+
+    // change one field and encode again
+    let mut modified: Vec<encoder::EncodeType> = decoded_data
+        .into_iter()
+        .map(encoder::EncodeType::from)
+        .collect();
+    assert_eq!(modified, to_encode);
+    modified[0] = 8u8.into();
+
+    let reencoded_data = encoder::encode_packed(&modified, encoder::EncodeOrder::Little).unwrap();
+    assert_eq!(reencoded_data[0], 8);
+    assert_eq!(reencoded_data[1..], encoded_data[1..]);
+
+    // structural elements carry no value of their own
+    assert!(decoder::DecodedData::try_from(encoder::EncodeType::Reserved(2)).is_err());
+}
+
+#[test]
+fn test_schema_text() {
+    use schema::Schema;
+
+    let text = r#"
+        // archive entry header
+        struct Header {
+            magic: const b"PK";
+            version: u8;
+            len: u32be;
+            name: str[len];
+            _: reserved[1];
+            flags: bits(lsb) { u1; u3; i4; };
+            extra: if version & 0x1 { u16; bytes[#0]; };
+            kind: if version == 2 { u16le[2]; };
+            crc: checksum crc16be(1..7);
+        }
+    "#;
+
+    let schema = text::parse_schema(text).unwrap();
+    assert_eq!(schema.name.as_deref(), Some("Header"));
+    assert_eq!(schema.fields.len(), 9);
+    assert!(matches!(
+        schema.fields[2].order,
+        Some(decoder::DecodeOrder::Big)
+    ));
+
+    // printing and parsing again gives the same schema
+    let printed = text::print(&schema);
+    let reparsed: Schema = printed.parse().unwrap();
+    assert_eq!(reparsed.to_string(), printed);
+    assert_eq!(format!("{:?}", reparsed), format!("{:?}", schema));
+
+    // `len` is big endian whatever the byte order of the record
+    let to_encode = &[
+        encoder::EncodeType::Const(b"PK".to_vec()),
+        encoder::EncodeType::Uint8(2),
+        encoder::EncodeType::Uint32(0),
+        encoder::EncodeType::StrRef("ab".to_owned(), decoder::FieldRef::Name("len".to_owned())),
+        encoder::EncodeType::Reserved(1),
+        encoder::EncodeType::BitGroup(
+            vec![
+                bitfield::BitValue::Unsigned(1, 0),
+                bitfield::BitValue::Unsigned(3, 5),
+                bitfield::BitValue::Signed(4, -2),
+            ],
+            bitfield::BitOrder::Lsb,
+        ),
+        encoder::EncodeType::Conditional(
+            decoder::FieldRef::Name("version".to_owned()),
+            decoder::Predicate::BitsSet(1),
+            vec![],
+        ),
+        encoder::EncodeType::Conditional(
+            decoder::FieldRef::Name("version".to_owned()),
+            decoder::Predicate::Equals(decoder::DecodedData::Uint8(2)),
+            vec![encoder::EncodeType::Array(vec![3u16.into(), 4u16.into()])],
+        ),
+        encoder::EncodeType::Checksum(checksum::ChecksumKind::Crc16Ccitt, 1..7),
+    ];
+    let encoded_data = schema
+        .encode(to_encode, encoder::EncodeOrder::Little)
+        .unwrap();
+    assert_eq!(encoded_data[3..7], [0, 0, 0, 2]);
+    assert_eq!(encoded_data[11..15], [3, 0, 4, 0]);
+
+    let record = schema
+        .decode(&encoded_data, decoder::DecodeOrder::Little)
+        .unwrap();
+    assert_eq!(record.get_u32("len").unwrap(), 2);
+    assert_eq!(record.get_str("name").unwrap(), "ab");
+    assert_eq!(record["extra"], decoder::DecodedData::Optional(None));
+
+    // nested elements are written and read back when the mask matches
+    let schema =
+        text::parse_schema("struct A { flags: u8; extra: if flags & 0x1 { u16; }; }").unwrap();
+    let to_encode = &[
+        encoder::EncodeType::Uint8(1),
+        encoder::EncodeType::Conditional(
+            decoder::FieldRef::Name("flags".to_owned()),
+            decoder::Predicate::BitsSet(1),
+            vec![encoder::EncodeType::Uint16(0x1234)],
+        ),
+    ];
+    let encoded_data = schema
+        .encode(to_encode, encoder::EncodeOrder::Little)
+        .unwrap();
+    assert_eq!(encoded_data, [0x01, 0x34, 0x12]);
+    let record = schema
+        .decode(&encoded_data, decoder::DecodeOrder::Little)
+        .unwrap();
+    assert_eq!(
+        record["extra"],
+        decoder::DecodedData::Optional(Some(vec![decoder::DecodedData::Uint16(0x1234)]))
+    );
+
+    // defaults of every kind, floats and names inside nested lists survive the textual representation
+    use bitfield::{BitField, BitOrder, BitValue};
+    use decoder::{DecodeType, DecodedData, FieldRef, Predicate};
+    use schema::Field;
+    let schema = Schema::named("Defaults")
+        .field("kind", DecodeType::Uint8)
+        .push(Field::new("ratio", DecodeType::Float32).with_default(DecodedData::Float32(-1e-7)))
+        .push(
+            Field::new("limit", DecodeType::Float64)
+                .with_default(DecodedData::Float64(f64::INFINITY)),
+        )
+        .push(
+            Field::new("samples", DecodeType::Array(Box::new(DecodeType::Int16), 2)).with_default(
+                DecodedData::Array(vec![DecodedData::Int16(-1), DecodedData::Int16(2)]),
+            ),
+        )
+        .push(
+            Field::new(
+                "flags",
+                DecodeType::BitGroup(
+                    vec![BitField::Unsigned(3), BitField::Signed(5)],
+                    BitOrder::Lsb,
+                ),
+            )
+            .with_default(DecodedData::BitGroup(
+                vec![BitValue::Unsigned(3, 5), BitValue::Signed(5, -3)],
+                BitOrder::Lsb,
+            )),
+        )
+        .push(
+            Field::new(
+                "extra",
+                DecodeType::Conditional(
+                    FieldRef::Name("ratio".to_owned()),
+                    Predicate::Equals(DecodedData::Float32(0.5)),
+                    vec![
+                        DecodeType::Uint8,
+                        DecodeType::StrRef(FieldRef::Name("len".to_owned())),
+                        DecodeType::Conditional(
+                            FieldRef::Name("kind".to_owned()),
+                            Predicate::NotEquals(DecodedData::Uint16(2)),
+                            vec![DecodeType::Bool],
+                        ),
+                    ],
+                ),
+            )
+            .with_default(DecodedData::Optional(Some(vec![
+                DecodedData::Uint8(2),
+                DecodedData::Str("ab".to_owned()),
+                DecodedData::Optional(None),
+            ]))),
+        );
+    let printed = text::print(&schema);
+    assert!(printed.contains("ratio: f32 = -1e-7;"));
+    assert!(printed.contains("if kind != (u16) 2 { bool; }"));
+    let reparsed = text::parse_schema(&printed).unwrap();
+    assert_eq!(format!("{:?}", reparsed), format!("{:?}", schema));
+}
+
+#[test]
+fn test_schema_text_errors() {
+    let error =
+        text::parse_schema("struct A {\n    len: u8;\n    name: str[size];\n}").unwrap_err();
+    assert_eq!((error.line, error.column), (3, 15));
+    assert_eq!(error.to_string(), "line 3, column 15: unknown field 'size'");
+
+    let error = text::parse_schema("struct A {\n  x: u24;\n}").unwrap_err();
+    assert_eq!((error.line, error.column), (2, 6));
+
+    let error = text::parse_schema("struct A { x: u8 }").unwrap_err();
+    assert_eq!((error.line, error.column), (1, 18));
+    assert!(text::parse_schema("struct A { x: u16be[2]; y: if x == 300 { u8; }; }").is_err());
+    // masks never match bit-groups, they are rejected
+    let error =
+        text::parse_schema("struct A { x: bits { u1; u7; }; y: if x & 0x1 { u8; }; }").unwrap_err();
+    assert_eq!(error.message, "masks can only test integer fields");
+}
+
+#[test]
+fn test_python_struct_format() {
+    use encoder::EncodeType;
+
+    // bytes produced by CPython's struct.pack for the same format and values
+    let values = &[
+        EncodeType::Int8(-1),
+        EncodeType::Int16(2),
+        EncodeType::Int32(-3),
+        EncodeType::Int64(4),
+        EncodeType::Bool(true),
+        EncodeType::Float64(1.5),
+        EncodeType::Float32(0.1),
+        EncodeType::Float64(-2.5),
+        EncodeType::Float64(1e300),
+    ];
+    let packed = pystruct::pack("@bhiq?3xe2fd", values).unwrap();
+    assert_eq!(pystruct::calcsize("@bhiq?3xe2fd").unwrap(), packed.len());
+    if cfg!(all(target_endian = "little", target_pointer_width = "64")) {
+        assert_eq!(
+            packed,
+            [
+                255, 0, 2, 0, 253, 255, 255, 255, 4, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 62, 0, 0,
+                205, 204, 204, 61, 0, 0, 32, 192, 156, 117, 0, 136, 60, 228, 55, 126
+            ]
+        );
+    }
+
+    let unpacked = pystruct::unpack("@bhiq?3xe2fd", &packed).unwrap();
+    assert_eq!(unpacked.len(), values.len());
+    assert_eq!(unpacked[4], decoder::DecodedData::Bool(true));
+    assert_eq!(unpacked[5], decoder::DecodedData::Float32(1.5));
+
+    // pascal strings are truncated, fixed strings are padded
+    let packed = pystruct::pack(
+        ">5p2sc",
+        &["hello world".into(), b"abc"[..].into(), "z".into()],
+    )
+    .unwrap();
+    assert_eq!(packed, [4, 104, 101, 108, 108, 97, 98, 122]);
+    assert_eq!(
+        pystruct::unpack(">5p2sc", &packed).unwrap(),
+        vec![
+            decoder::DecodedData::Bytes(b"hell".to_vec()),
+            decoder::DecodedData::Bytes(b"ab".to_vec()),
+            decoder::DecodedData::Bytes(b"z".to_vec()),
+        ]
+    );
+
+    // half-precision rounding and range
+    assert_eq!(
+        pystruct::pack("<e", &[EncodeType::Float64(65504.0)]).unwrap(),
+        [255, 123]
+    );
+    assert_eq!(
+        pystruct::pack("<e", &[EncodeType::Float64(1e-7)]).unwrap(),
+        [2, 0]
+    );
+    assert!(pystruct::pack("<e", &[EncodeType::Float64(65520.0)]).is_err());
+    assert_eq!(
+        pystruct::unpack("<e", &[0x55, 0x35]).unwrap(),
+        vec![decoder::DecodedData::Float32(0.333_251_95)]
+    );
+
+    // alignment, ranges and errors
+    assert_eq!(pystruct::calcsize("@b0i").unwrap(), 4);
+    assert_eq!(pystruct::calcsize("<b0i").unwrap(), 1);
+    assert!(pystruct::pack("<B", &[EncodeType::Int32(256)]).is_err());
+    assert!(matches!(
+        pystruct::calcsize("<n"),
+        Err(pystruct::FormatError::InvalidFormat(1))
+    ));
+    assert!(pystruct::unpack("<I", &[0, 0]).is_err());
+
+    // sizes that overflow are rejected like CPython's `struct.error`
+    for format in [
+        "<3000000000000000000Q",
+        "<9999999999999999999q",
+        "<18446744073709551615x1x",
+        "@b3000000000000000000i",
+    ] {
+        assert!(matches!(
+            pystruct::calcsize(format),
+            Err(pystruct::FormatError::InvalidFormat(_))
+        ));
+        assert!(pystruct::unpack(format, &[]).is_err());
+    }
+}
+
+#[test]
+fn test_perl_pack_template() {
+    use decoder::DecodedData;
+    use encoder::EncodeType;
+
+    // bytes produced by perl's pack for the same template and values
+    let packed = perlpack::pack(
+        "NnA10H*",
+        &[
+            305419896u32.into(),
+            513u16.into(),
+            "hello".into(),
+            "deadbeef".into(),
+        ],
+    )
+    .unwrap();
+    assert_eq!(
+        packed,
+        [18, 52, 86, 120, 2, 1, 104, 101, 108, 108, 111, 32, 32, 32, 32, 32, 222, 173, 190, 239]
+    );
+    assert_eq!(
+        perlpack::unpack("NnA10H*", &packed).unwrap(),
+        vec![
+            DecodedData::Uint32(305419896),
+            DecodedData::Uint16(513),
+            DecodedData::Bytes(b"hello".to_vec()),
+            DecodedData::Str("deadbeef".to_owned()),
+        ]
+    );
+
+    let values = &[
+        EncodeType::Int16(-2),
+        EncodeType::Int32(7),
+        EncodeType::Int64(-1),
+        "abcdef".into(),
+        "xy".into(),
+        "q".into(),
+        "a1f".into(),
+        300u16.into(),
+        16384u32.into(),
+    ];
+    let packed = perlpack::pack("s<l>qZ4Z*a3h3w2x2X@30", values).unwrap();
+    assert_eq!(
+        packed,
+        [
+            254, 255, 0, 0, 0, 7, 255, 255, 255, 255, 255, 255, 255, 255, 97, 98, 99, 0, 120, 121,
+            0, 113, 0, 0, 26, 15, 130, 44, 129, 128
+        ]
+    );
+    let unpacked = perlpack::unpack("s<l>qZ4Z*a3h3w", &packed[..28]).unwrap();
+    assert_eq!(unpacked[3], DecodedData::Bytes(b"abc".to_vec()));
+    assert_eq!(unpacked[4], DecodedData::Bytes(b"xy".to_vec()));
+    assert_eq!(unpacked[6], DecodedData::Str("a1f".to_owned()));
+    assert_eq!(unpacked[7], DecodedData::Uint64(300));
+
+    // counts larger than the remaining bytes are truncated like in Perl
+    assert_eq!(
+        perlpack::unpack("H10a4", &[0x12, 0x34]).unwrap(),
+        [
+            DecodedData::Str("1234".to_owned()),
+            DecodedData::Bytes(vec![])
+        ]
+    );
+    assert_eq!(
+        perlpack::unpack("Ca4", b"\x01ab").unwrap(),
+        [DecodedData::Uint8(1), DecodedData::Bytes(b"ab".to_vec())]
+    );
+
+    // unsupported directives and backward moves are reported with their position
+    assert!(matches!(
+        perlpack::pack("NU", &[1u8.into(), 2u8.into()]),
+        Err(perlpack::TemplateError::Unsupported('U', 1))
+    ));
+    assert!(matches!(
+        perlpack::unpack("C2X", &[1, 2]),
+        Err(perlpack::TemplateError::Backward(2))
+    ));
+    assert!(matches!(
+        perlpack::pack("N", &[]),
+        Err(perlpack::TemplateError::MissingValue(0))
+    ));
+}
+
+#[test]
+fn test_c_header() {
+    use decoder::{DecodeOrder, DecodeType};
+
+    let schema = schema::Schema::named("frame")
+        .field("magic", DecodeType::Const(b"PK".to_vec()))
+        .field("version", DecodeType::Uint8)
+        .push(schema::Field::new("len", DecodeType::Uint32).with_order(DecodeOrder::Big))
+        .field("name", DecodeType::Str(8))
+        .unnamed(DecodeType::Reserved(3))
+        .field("samples", DecodeType::Array(Box::new(DecodeType::Int16), 4))
+        .field(
+            "flags",
+            DecodeType::BitGroup(
+                vec![
+                    bitfield::BitField::Unsigned(3),
+                    bitfield::BitField::Signed(7),
+                ],
+                bitfield::BitOrder::Msb,
+            ),
+        )
+        .field("scale", DecodeType::Float64)
+        .field(
+            "crc",
+            DecodeType::Checksum(checksum::ChecksumKind::Crc16Ccitt, 0..8),
+        );
+
+    let header = cheader::generate(&schema, DecodeOrder::Little, cheader::Packing::Pragma).unwrap();
+    assert!(header.contains("#pragma pack(push, 1)\nstruct frame {\n"));
+    assert!(header.contains("    uint32_t len; /* big endian */\n"));
+    assert!(header.contains("    uint8_t reserved_4[3]; /* reserved, must be zero */\n"));
+    assert!(header.contains("    int16_t samples[4]; /* little endian */\n"));
+
+    // the offsets checked by the header match the bytes written by the encoder
+    let to_encode = &[
+        encoder::EncodeType::Const(b"PK".to_vec()),
+        encoder::EncodeType::Uint8(1),
+        encoder::EncodeType::Uint32(8),
+        encoder::EncodeType::Str("abcdefgh".to_owned()),
+        encoder::EncodeType::Reserved(3),
+        encoder::EncodeType::Array(vec![1i16.into(), 2i16.into(), 3i16.into(), 4i16.into()]),
+        encoder::EncodeType::BitGroup(
+            vec![
+                bitfield::BitValue::Unsigned(3, 1),
+                bitfield::BitValue::Signed(7, -1),
+            ],
+            bitfield::BitOrder::Msb,
+        ),
+        encoder::EncodeType::Float64(0.5),
+        encoder::EncodeType::Checksum(checksum::ChecksumKind::Crc16Ccitt, 0..8),
+    ];
+    let encoded_data = schema
+        .encode(to_encode, encoder::EncodeOrder::Little)
+        .unwrap();
+    assert!(header.contains(&format!(
+        "static_assert(sizeof(struct frame) == {},",
+        encoded_data.len()
+    )));
+    assert!(header.contains("static_assert(offsetof(struct frame, samples) == 18,"));
+    assert_eq!(encoded_data[18..20], [1, 0]);
+    assert!(header.contains("static_assert(offsetof(struct frame, scale) == 28,"));
+    assert_eq!(encoded_data[28..36], 0.5f64.to_le_bytes());
+
+    // fields whose size depends on the content cannot be declared
+    let schema = schema::Schema::new()
+        .field("len", DecodeType::Uint8)
+        .field("name", DecodeType::StrRef(decoder::FieldRef::Index(0)));
+    assert_eq!(
+        cheader::generate(&schema, DecodeOrder::Little, cheader::Packing::Attribute),
+        Err(cheader::HeaderError::VariableSize(1))
+    );
+
+    // C keywords and names used twice cannot be members
+    let keyword = schema::Schema::new().field("int", DecodeType::Uint8);
+    assert_eq!(
+        cheader::generate(&keyword, DecodeOrder::Little, cheader::Packing::Pragma),
+        Err(cheader::HeaderError::InvalidName(Some(0)))
+    );
+    let duplicate = schema::Schema::new()
+        .field("field_1", DecodeType::Uint8)
+        .unnamed(DecodeType::Uint8);
+    assert_eq!(
+        cheader::generate(&duplicate, DecodeOrder::Little, cheader::Packing::Pragma),
+        Err(cheader::HeaderError::DuplicateName(1))
+    );
+}
+
+// the generated headers are compiled, which checks their `static_assert`s, when a C compiler is installed
+#[cfg(not(target_arch = "wasm32"))]
+#[test]
+fn test_c_header_compiles() {
+    use decoder::{DecodeOrder, DecodeType};
+    use std::process::Command;
+
+    let schema = schema::Schema::named("frame")
+        .field("magic", DecodeType::Const(b"PK".to_vec()))
+        .field("len", DecodeType::Uint32)
+        .field("name", DecodeType::Str(5))
+        .unnamed(DecodeType::Padding(1))
+        .field("samples", DecodeType::Array(Box::new(DecodeType::Int16), 3))
+        .field(
+            "flags",
+            DecodeType::BitGroup(
+                vec![bitfield::BitField::Unsigned(12)],
+                bitfield::BitOrder::Lsb,
+            ),
+        )
+        .field("scale", DecodeType::Float64)
+        .field("valid", DecodeType::Bool);
+    let dir = std::env::temp_dir().join(format!("packed-encoder-cheader-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    for (packing, file) in [
+        (cheader::Packing::Pragma, "pragma.c"),
+        (cheader::Packing::Attribute, "attribute.c"),
+    ] {
+        let header = cheader::generate(&schema, DecodeOrder::Big, packing).unwrap();
+        let path = dir.join(file);
+        std::fs::write(&path, format!("{}\nstruct frame frame;\n", header)).unwrap();
+        let Ok(output) = Command::new("cc")
+            .args(["-std=c11", "-Wall", "-Werror", "-fsyntax-only"])
+            .arg(&path)
+            .output()
+        else {
+            // no C compiler
+            break;
+        };
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_c_struct_import() {
+    use decoder::{DecodeOrder, DecodeType, DecodedData};
+
+    let header = r#"
+        #include <stdint.h>
+        #define NAME_LEN 6
+
+        struct point { int16_t x; int16_t y; };
+
+        typedef struct {
+            uint8_t kind; /* followed by 1 byte of padding */
+            struct point origin;
+            uint32_t id;
+            char name[NAME_LEN];
+            double scale;
+            struct point path[2];
+            bool valid;
+            uint8_t grid[2][3];
+        } shape_t;
+
+        struct __attribute__((packed)) wire { uint8_t tag; uint32_t len; struct point at; };
+
+        #pragma pack(push, 2)
+        struct half { uint8_t a; uint64_t b; uint8_t c; };
+        #pragma pack(pop)
+
+        int send(const struct wire *wire);
+    "#;
+    let schemas = cheader::parse(header).unwrap();
+    let names: Vec<_> = schemas
+        .iter()
+        .map(|schema| schema.name.as_deref())
+        .collect();
+    assert_eq!(
+        names,
+        [Some("point"), Some("shape_t"), Some("wire"), Some("half")]
+    );
+
+    // sizes and offsets as laid out by gcc on x86_64
+    let size = |schema: &schema::Schema| -> usize {
+        schema
+            .types()
+            .iter()
+            .map(|kind| kind.fixed_size().unwrap())
+            .sum()
+    };
+    assert_eq!(size(&schemas[1]), 48);
+    assert_eq!(size(&schemas[2]), 9);
+    assert_eq!(size(&schemas[3]), 12);
+    assert!(matches!(schemas[1].fields[1].kind, DecodeType::Padding(1)));
+    assert_eq!(schemas[1].index_of("path[1].y"), Some(12));
+    assert!(matches!(schemas[3].fields[1].kind, DecodeType::Padding(1)));
+
+    let mut buffer = vec![0u8; 48];
+    buffer[0] = 3;
+    buffer[4..6].copy_from_slice(&(-2i16).to_le_bytes());
+    buffer[8..12].copy_from_slice(&7u32.to_le_bytes());
+    buffer[12..18].copy_from_slice(b"square");
+    buffer[24..32].copy_from_slice(&1.5f64.to_le_bytes());
+    buffer[40] = 1;
+    let record = schemas[1].decode(&buffer, DecodeOrder::Little).unwrap();
+    assert_eq!(record.get_u8("kind"), Ok(3));
+    assert_eq!(record.get("origin.y"), Some(&DecodedData::Int16(-2)));
+    assert_eq!(record.get_u32("id"), Ok(7));
+    assert_eq!(record.get_bytes("name"), Ok(&b"square"[..]));
+    assert_eq!(record.get_f64("scale"), Ok(1.5));
+    assert_eq!(record.get_bool("valid"), Ok(true));
+
+    // headers generated by `generate` are read back
+    let schema = schema::Schema::named("frame")
+        .field("version", DecodeType::Uint8)
+        .field("len", DecodeType::Uint32)
+        .field("samples", DecodeType::Array(Box::new(DecodeType::Int16), 4));
+    let header = cheader::generate(&schema, DecodeOrder::Little, cheader::Packing::Pragma).unwrap();
+    let imported = cheader::parse(&header).unwrap();
+    assert_eq!(
+        format!("{:?}", imported[0].types()),
+        format!("{:?}", schema.types())
+    );
+
+    // C strings end at the first NUL, the bytes after it need not be valid UTF-8
+    let schemas = cheader::parse("struct s { char name[4]; };").unwrap();
+    let record = schemas[0]
+        .decode(&[b'a', 0, 0xff, 0xfe], DecodeOrder::Little)
+        .unwrap();
+    assert_eq!(record.get_bytes("name"), Ok(&[b'a', 0, 0xff, 0xfe][..]));
+
+    // a typedef of a struct declared earlier is not a new schema, and neither is a variable of that type
+    let schemas = cheader::parse(
+        "struct point { int16_t x; }; typedef struct point point_t; struct point origin; \
+         struct line { point_t from; point_t to; };",
+    )
+    .unwrap();
+    let names: Vec<_> = schemas
+        .iter()
+        .map(|schema| schema.name.as_deref())
+        .collect();
+    assert_eq!(names, [Some("point"), Some("line")]);
+    assert_eq!(schemas[1].index_of("to.x"), Some(1));
+
+    let error = cheader::parse("struct s { uint8_t flags : 3; };").unwrap_err();
+    assert_eq!((error.line, error.column), (1, 26));
+    assert!(cheader::parse("struct s { long size; };").is_err());
+    assert!(cheader::parse("struct s { struct other inner; };").is_err());
+}
+
+#[cfg(feature = "capi")]
+#[test]
+fn test_capi() {
+    use capi::*;
+
+    unsafe {
+        let encoder = pe_encoder_new();
+        assert_eq!(pe_encoder_push_u8(encoder, 7), PE_OK);
+        assert_eq!(pe_encoder_push_i32(encoder, -2), PE_OK);
+        assert_eq!(pe_encoder_push_str(encoder, b"abc".as_ptr(), 3), PE_OK);
+        assert_eq!(
+            pe_encoder_push_str(encoder, [0xffu8].as_ptr(), 1),
+            PE_ERROR_UTF8
+        );
+
+        let mut buffer = [0u8; 8];
+        let mut written = 0;
+        let code = pe_encoder_encode(
+            encoder,
+            PE_ORDER_LITTLE,
+            buffer.as_mut_ptr(),
+            4,
+            &mut written,
+        );
+        assert_eq!((code, written), (PE_ERROR_BUFFER_TOO_SMALL, 8));
+        let code = pe_encoder_encode(
+            encoder,
+            PE_ORDER_LITTLE,
+            buffer.as_mut_ptr(),
+            8,
+            &mut written,
+        );
+        assert_eq!((code, written), (PE_OK, 8));
+        assert_eq!(buffer, [7, 0xfe, 0xff, 0xff, 0xff, b'a', b'b', b'c']);
+        pe_encoder_free(encoder);
+
+        let text = "struct s { kind: u8; delta: i32; name: str[3]; }";
+        let mut schema = std::ptr::null_mut();
+        assert_eq!(
+            pe_schema_parse(text.as_ptr(), text.len(), &mut schema),
+            PE_OK
+        );
+        let mut record = std::ptr::null_mut();
+        assert_eq!(
+            pe_decode(schema, buffer.as_ptr(), 8, PE_ORDER_LITTLE, &mut record),
+            PE_OK
+        );
+        assert_eq!(
+            pe_decode(schema, buffer.as_ptr(), 8, 5, &mut record),
+            PE_ERROR_ORDER
+        );
+        assert_eq!(pe_record_len(record), 3);
+
+        let mut value = 0i64;
+        assert_eq!(pe_record_get_i64(record, 1, &mut value), PE_OK);
+        assert_eq!(value, -2);
+        let mut unsigned = 0u64;
+        assert_eq!(pe_record_get_u64(record, 1, &mut unsigned), PE_ERROR_TYPE);
+        assert_eq!(pe_record_get_u64(record, 3, &mut unsigned), PE_ERROR_INDEX);
+        let (mut data, mut len) = (std::ptr::null(), 0);
+        assert_eq!(pe_record_get_str(record, 2, &mut data, &mut len), PE_OK);
+        assert_eq!(std::slice::from_raw_parts(data, len), b"abc");
+
+        pe_record_free(record);
+        pe_schema_free(schema);
+        assert_eq!(pe_encoder_push_u8(std::ptr::null_mut(), 1), PE_ERROR_NULL);
+    }
+}
+
+#[cfg(all(test, target_arch = "wasm32", feature = "wasm"))]
+#[wasm_bindgen_test::wasm_bindgen_test]
+fn test_wasm_bindings() {
+    use js_sys::{Array, BigInt, Object, Reflect, Uint8Array};
+    use wasm_bindgen::JsValue;
+
+    let entry = |kind: &str, value: JsValue| -> JsValue {
+        let entry = Object::new();
+        Reflect::set(&entry, &"type".into(), &kind.into()).unwrap();
+        Reflect::set(&entry, &"value".into(), &value).unwrap();
+        entry.into()
+    };
+    let values = Array::of5(
+        &entry("u8", 7.into()),
+        &entry("i64", BigInt::from(-2i64).into()),
+        &entry("u128", BigInt::from(u128::MAX).into()),
+        &entry("str", "abc".into()),
+        &entry("bytes", Uint8Array::from(&[1u8, 2][..]).into()),
+    );
+    let encoded = wasm::encode_packed(values, "big").unwrap().to_vec();
+    assert_eq!(encoded.len(), 1 + 8 + 16 + 3 + 2);
+    assert_eq!(encoded[1..9], (-2i64).to_be_bytes());
+
+    let types = Array::new();
+    for (kind, size) in [("u8", 0), ("i64", 0), ("u128", 0), ("str", 3), ("bytes", 2)] {
+        let kind = entry(kind, JsValue::UNDEFINED);
+        Reflect::set(&kind, &"size".into(), &size.into()).unwrap();
+        types.push(&kind);
+    }
+    let decoded = wasm::decode_packed(types, &encoded, "big").unwrap();
+    let value = |index: u32| Reflect::get(&decoded.get(index), &"value".into()).unwrap();
+    assert_eq!(value(0).as_f64(), Some(7.0));
+    assert_eq!(BigInt::from(value(1)), BigInt::from(-2i64));
+    assert_eq!(BigInt::from(value(2)), BigInt::from(u128::MAX));
+    assert_eq!(value(3).as_string().as_deref(), Some("abc"));
+    assert_eq!(Uint8Array::from(value(4)).to_vec(), [1, 2]);
+
+    // 64-bit values above 2^53 must be BigInt
+    let values = Array::of1(&entry("u64", 9007199254740993.0.into()));
+    assert!(wasm::encode_packed(values, "little").is_err());
+    assert!(wasm::encode_packed(Array::new(), "middle").is_err());
+
+    // bytes are Uint8Arrays or arrays of numbers, not any object
+    let numbers = Array::of2(&1.into(), &255.into());
+    let encoded = wasm::encode_packed(Array::of1(&entry("bytes", numbers.into())), "big").unwrap();
+    assert_eq!(encoded.to_vec(), [1, 255]);
+    let values = Array::of1(&entry("bytes", Object::new().into()));
+    assert!(wasm::encode_packed(values, "big").is_err());
+    let values = Array::of1(&entry("bytes", Array::of1(&256.into()).into()));
+    assert!(wasm::encode_packed(values, "big").is_err());
+}
+
+#[test]
+fn test_framing() {
+    use framing::{Frame, FrameError, FrameFormat, FrameReader, FrameSplitter, LengthPrefix};
+
+    let records: Vec<Vec<u8>> = (0..3u16)
+        .map(|index| {
+            let to_encode = &[
+                encoder::EncodeType::Uint16(index),
+                encoder::EncodeType::Bytes(vec![index as u8; 100 * index as usize]),
+            ];
+            encoder::encode_packed(to_encode, encoder::EncodeOrder::Little).unwrap()
+        })
+        .collect();
+
+    let format = FrameFormat::new(LengthPrefix::Varint)
+        .with_magic(b"PK")
+        .with_type_id()
+        .with_max_frame_size(1024);
+    let mut stream = vec![];
+    for (index, record) in records.iter().enumerate() {
+        format
+            .encode_into(&Frame::typed(index as u16 + 7, record.clone()), &mut stream)
+            .unwrap();
+    }
+    // the 202 byte record needs a 2 byte varint
+    assert_eq!(stream[118..120], [0x80 | (202 & 0x7f), 1]);
+
+    // the stream is split back whatever the size of the received chunks
+    let mut splitter = FrameSplitter::new(format.clone());
+    let mut frames = vec![];
+    for byte in &stream {
+        splitter.push(&[*byte]);
+        while let Some(frame) = splitter.next_frame().unwrap() {
+            frames.push(frame);
+        }
+    }
+    assert_eq!(splitter.buffered(), 0);
+    assert_eq!(frames.len(), 3);
+    assert_eq!(frames[2], Frame::typed(9, records[2].clone()));
+
+    let reader = FrameReader::new(std::io::Cursor::new(stream.clone()), format.clone());
+    let frames: Vec<Frame> = reader.map(Result::unwrap).collect();
+    assert_eq!(frames[1].payload, records[1]);
+
+    let mut reader = FrameReader::new(&stream[..stream.len() - 1], format.clone());
+    assert!(reader.read_frame().unwrap().is_some());
+    assert!(reader.read_frame().unwrap().is_some());
+    assert!(matches!(reader.read_frame(), Err(FrameError::Truncated)));
+
+    // a hostile header is rejected before its payload is received
+    let mut hostile = b"PK\x00\x01".to_vec();
+    framing::encode_varint(1 << 30, &mut hostile);
+    assert!(matches!(
+        format.decode(&hostile),
+        Err(FrameError::TooLarge {
+            size: 1073741824,
+            max: 1024
+        })
+    ));
+    // with no maximum, a length that does not fit after the header is too large as well
+    let unbounded = format.clone().with_max_frame_size(usize::MAX);
+    let mut hostile = b"PK\x00\x01".to_vec();
+    framing::encode_varint(u64::MAX - 1, &mut hostile);
+    assert!(matches!(
+        unbounded.decode(&hostile),
+        Err(FrameError::TooLarge { size, .. }) if size == u64::MAX - 1
+    ));
+    assert!(matches!(format.decode(b"PX"), Err(FrameError::BadMagic)));
+    assert!(matches!(format.decode(b"P"), Ok(None)));
+    assert!(matches!(
+        format.encode(&Frame::new(vec![])),
+        Err(FrameError::TypeId)
+    ));
+
+    let format = FrameFormat::new(LengthPrefix::Uint16).with_order(decoder::DecodeOrder::Little);
+    assert_eq!(format.encode(&Frame::new(vec![9])).unwrap(), [1, 0, 9]);
+    assert!(format.encode(&Frame::new(vec![0; 70000])).is_err());
+}
+
+#[test]
+fn test_decode_prefix() {
+    use decoder::{DecodeError, DecodeOrder, DecodeType, DecodedData};
+
+    let types = &[
+        DecodeType::Uint8,
+        DecodeType::BytesRef(decoder::FieldRef::Index(0)),
+    ];
+    let stream = [2, 10, 11, 1, 12, 3];
+    let (first, consumed) = decoder::decode_prefix(types, &stream, DecodeOrder::Big).unwrap();
+    assert_eq!(consumed, 3);
+    assert_eq!(first[1], DecodedData::Bytes(vec![10, 11]));
+    let (second, consumed) = decoder::decode_prefix(types, &stream[3..], DecodeOrder::Big).unwrap();
+    assert_eq!(
+        (second[1].clone(), consumed),
+        (DecodedData::Bytes(vec![12]), 2)
+    );
+    assert!(matches!(
+        decoder::decode_prefix(types, &stream[5..], DecodeOrder::Big),
+        Err(DecodeError::IndexOutOfBounds)
+    ));
+
+    let schema = schema::Schema::new().field("id", DecodeType::Uint16);
+    let (record, consumed) = schema.decode_prefix(&[0, 7, 0], DecodeOrder::Big).unwrap();
+    assert_eq!((record.get_u16("id").unwrap(), consumed), (7, 2));
+}
+
+// record files need a file system
+#[cfg(not(target_arch = "wasm32"))]
+#[test]
+fn test_record_file() {
+    use decoder::{DecodeOrder, DecodeType, FieldRef};
+    use encoder::EncodeType;
+    use recordfile::{RecordFileError, RecordReader, RecordWriter};
+    use std::fs::{File, OpenOptions};
+
+    let path = std::env::temp_dir().join(format!("packed-encoder-{}.pkrf", std::process::id()));
+    let schema = schema::Schema::named("event")
+        .field("id", DecodeType::Uint32)
+        .field("len", DecodeType::Uint8)
+        .field("name", DecodeType::StrRef(FieldRef::Name("len".to_owned())));
+    let record = |id: u32| {
+        let name = format!("event-{}", id);
+        vec![
+            EncodeType::Uint32(id),
+            EncodeType::Uint8(name.len() as u8),
+            EncodeType::Str(name),
+        ]
+    };
+
+    let mut writer = RecordWriter::create(&path, schema, DecodeOrder::Little).unwrap();
+    for id in 0..100 {
+        assert_eq!(writer.write(&record(id)).unwrap(), id as usize);
+    }
+    assert!(matches!(
+        writer.write_encoded(&[1, 0, 0, 0, 0, 9]),
+        Err(RecordFileError::TrailingBytes(1))
+    ));
+    writer.finish().unwrap();
+
+    // random access and sequential reads
+    let mut reader = RecordReader::new(File::open(&path).unwrap()).unwrap();
+    assert_eq!(reader.len(), 100);
+    assert_eq!(reader.schema().name.as_deref(), Some("event"));
+    assert_eq!(
+        reader.read(57).unwrap().get_str("name").unwrap(),
+        "event-57"
+    );
+    assert!(matches!(
+        reader.read(100),
+        Err(RecordFileError::OutOfRange(100))
+    ));
+    let ids: Vec<u32> = reader
+        .records()
+        .map(|record| record.unwrap().get_u32("id").unwrap())
+        .collect();
+    assert_eq!(ids, (0..100).collect::<Vec<u32>>());
+
+    // appending keeps the previous records
+    let mut writer = RecordWriter::append(&path).unwrap();
+    writer.write(&record(100)).unwrap();
+    writer.finish().unwrap();
+    let mut reader = RecordReader::new(File::open(&path).unwrap()).unwrap();
+    assert_eq!(reader.len(), 101);
+    assert_eq!(reader.read(100).unwrap().get_u32("id").unwrap(), 100);
+
+    // a crash leaves records without index and the last one truncated
+    let mut writer = RecordWriter::append(&path).unwrap();
+    for id in 101..104 {
+        writer.write(&record(id)).unwrap();
+    }
+    drop(writer);
+    let file = OpenOptions::new().write(true).open(&path).unwrap();
+    file.set_len(file.metadata().unwrap().len() - 2).unwrap();
+    drop(file);
+    assert!(matches!(
+        RecordReader::new(File::open(&path).unwrap()),
+        Err(RecordFileError::MissingIndex)
+    ));
+    assert_eq!(recordfile::recover(&path).unwrap(), 103);
+    let mut reader = RecordReader::new(File::open(&path).unwrap()).unwrap();
+    assert_eq!(reader.len(), 103);
+    assert_eq!(reader.read(102).unwrap().get_u32("id").unwrap(), 102);
+
+    // a damaged index is detected
+    let mut content = std::fs::read(&path).unwrap();
+    let index_byte = content.len() - 30;
+    content[index_byte] ^= 1;
+    assert!(matches!(
+        RecordReader::new(std::io::Cursor::new(&content)),
+        Err(RecordFileError::Checksum)
+    ));
+    content[0] = b'X';
+    assert!(matches!(
+        RecordReader::new(std::io::Cursor::new(&content)),
+        Err(RecordFileError::BadMagic)
+    ));
+
+    // invalid headers are refused before allocating the schema text
+    let header = |order: u8, size: u32| {
+        let mut header = recordfile::MAGIC.to_vec();
+        header.extend_from_slice(&recordfile::VERSION.to_le_bytes());
+        header.push(order);
+        header.extend_from_slice(&size.to_le_bytes());
+        header.extend_from_slice(b"struct { id: u8; }");
+        header
+    };
+    assert!(matches!(
+        RecordReader::new(std::io::Cursor::new(header(2, 18))),
+        Err(RecordFileError::BadOrder(2))
+    ));
+    assert!(matches!(
+        RecordReader::new(std::io::Cursor::new(header(1, u32::MAX))),
+        Err(RecordFileError::Io(error)) if error.kind() == std::io::ErrorKind::UnexpectedEof
+    ));
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[cfg(feature = "bytes")]
+#[test]
+fn test_bytes_buffers() {
+    use buf::BufValue;
+    use bytes::{Buf, BufMut, Bytes, BytesMut};
+    use decoder::{DecodeError, DecodeOrder, DecodeType, DecodedData, FieldRef};
+    use encoder::{EncodeOrder, EncodeType};
+
+    let types = &[
+        DecodeType::Uint8,
+        DecodeType::BytesRef(FieldRef::Index(0)),
+        DecodeType::Uint16,
+    ];
+    let record = |content: &[u8]| {
+        vec![
+            EncodeType::Uint8(content.len() as u8),
+            EncodeType::Bytes(content.to_vec()),
+            EncodeType::Uint16(0xbeef),
+        ]
+    };
+
+    let mut stream = BytesMut::new();
+    buf::encode_into(&record(b"abc"), &mut stream, EncodeOrder::Big).unwrap();
+    buf::encode_into(&record(b"de"), &mut stream, EncodeOrder::Big).unwrap();
+    let mut too_small = [0u8; 4];
+    assert!(matches!(
+        buf::encode_into(&record(b"abc"), &mut &mut too_small[..], EncodeOrder::Big),
+        Err(encoder::EncodeError::BufferFull(6))
+    ));
+
+    // the decoded bytes share the memory of the input
+    let input = stream.clone().freeze();
+    let values = buf::decode_bytes(types, &input, DecodeOrder::Big).unwrap();
+    assert_eq!(values[1].as_bytes().unwrap().as_ptr(), input[1..].as_ptr());
+    assert_eq!(values[2], BufValue::Data(DecodedData::Uint16(0xbeef)));
+
+    // records are consumed one after the other, a partial record leaves the buffer untouched
+    let first = buf::decode_buf(types, &mut stream, DecodeOrder::Big).unwrap();
+    assert_eq!(first[1], BufValue::Bytes(Bytes::from_static(b"abc")));
+    stream.truncate(3);
+    assert!(matches!(
+        buf::decode_buf(types, &mut stream, DecodeOrder::Big),
+        Err(DecodeError::IndexOutOfBounds)
+    ));
+    assert_eq!(stream.len(), 3);
+    stream.put_slice(&[0xbe, 0xef]);
+    let second = buf::decode_buf(types, &mut stream, DecodeOrder::Big).unwrap();
+    assert_eq!(
+        second[1].clone().into_data(),
+        DecodedData::Bytes(b"de".to_vec())
+    );
+    assert!(stream.is_empty());
+
+    // records split across chained buffers
+    let encoded = buf::encode_to_bytes(&record(b"xyz"), EncodeOrder::Little).unwrap();
+    let mut chained = encoded.slice(..2).chain(encoded.slice(2..));
+    let values = buf::decode_buf(types, &mut chained, DecodeOrder::Little).unwrap();
+    assert_eq!(values[1], BufValue::Bytes(Bytes::from_static(b"xyz")));
+    assert_eq!(values[2], BufValue::Data(DecodedData::Uint16(0xbeef)));
+    assert_eq!(chained.remaining(), 0);
+
+    // buffers that keep the default `chunks_vectored` only expose their first chunk without being consumed
+    struct Chunks(Vec<Vec<u8>>);
+    impl Buf for Chunks {
+        fn remaining(&self) -> usize {
+            self.0.iter().map(Vec::len).sum()
+        }
+        fn chunk(&self) -> &[u8] {
+            self.0.first().map_or(&[], Vec::as_slice)
+        }
+        fn advance(&mut self, mut count: usize) {
+            while count > 0 {
+                let size = count.min(self.0[0].len());
+                self.0[0].drain(..size);
+                count -= size;
+                if self.0[0].is_empty() {
+                    self.0.remove(0);
+                }
+            }
+        }
+    }
+    let pair = &[DecodeType::Uint16, DecodeType::Uint16];
+    let mut chunks = Chunks(vec![vec![0, 1], vec![0, 2]]);
+    assert!(matches!(
+        buf::decode_buf(pair, &mut chunks, DecodeOrder::Big),
+        Err(DecodeError::NonContiguous)
+    ));
+    assert_eq!(chunks.remaining(), 4);
+    let values = buf::decode_buf(&pair[..1], &mut chunks, DecodeOrder::Big).unwrap();
+    assert_eq!(values, [BufValue::Data(DecodedData::Uint16(1))]);
+    assert_eq!(chunks.remaining(), 2);
+}
+
+#[cfg(all(test, feature = "async"))]
+#[tokio::test]
+async fn test_async_codec() {
+    use codec::{CodecError, PackedCodec};
+    use decoder::{DecodeOrder, DecodeType, DecodedData};
+    use encoder::EncodeType;
+    use futures_util::{SinkExt, StreamExt};
+    use tokio::io::AsyncWriteExt;
+    use tokio_util::codec::{FramedRead, FramedWrite};
+
+    let schema = schema::Schema::new()
+        .field("id", DecodeType::Uint32)
+        .field("len", DecodeType::Uint8)
+        .field(
+            "name",
+            DecodeType::StrRef(decoder::FieldRef::Name("len".to_owned())),
+        );
+    let record = |id: u32, name: &str| {
+        vec![
+            EncodeType::Uint32(id),
+            EncodeType::Uint8(name.len() as u8),
+            EncodeType::Str(name.to_owned()),
+        ]
+    };
+
+    // records written through the codec are read back
+    let (client, server) = tokio::io::duplex(64);
+    let mut writer = FramedWrite::new(client, PackedCodec::new(schema.clone(), DecodeOrder::Big));
+    let mut reader = FramedRead::new(server, PackedCodec::new(schema.clone(), DecodeOrder::Big));
+    writer.send(record(1, "first")).await.unwrap();
+    writer.send(record(2, "second")).await.unwrap();
+    drop(writer);
+    let first = reader.next().await.unwrap().unwrap();
+    assert_eq!(first.get_str("name").unwrap(), "first");
+    let second = reader.next().await.unwrap().unwrap();
+    assert_eq!(second.get("id"), Some(&DecodedData::Uint32(2)));
+    assert!(reader.next().await.is_none());
+
+    // a record split across writes waits for its last byte
+    let (mut client, server) = tokio::io::duplex(64);
+    let mut reader = FramedRead::new(server, PackedCodec::new(schema.clone(), DecodeOrder::Big));
+    let encoded = schema
+        .encode(&record(3, "split"), encoder::EncodeOrder::Big)
+        .unwrap();
+    let task = tokio::spawn(async move {
+        for byte in encoded {
+            client.write_all(&[byte]).await.unwrap();
+            tokio::task::yield_now().await;
+        }
+    });
+    let split = reader.next().await.unwrap().unwrap();
+    assert_eq!(split.get_str("name").unwrap(), "split");
+    task.await.unwrap();
+
+    // framed records
+    let format = framing::FrameFormat::new(framing::LengthPrefix::Varint).with_magic(b"PK");
+    let codec = PackedCodec::new(schema.clone(), DecodeOrder::Little).with_framing(format);
+    let (client, server) = tokio::io::duplex(64);
+    let mut writer = FramedWrite::new(client, codec.clone());
+    let mut reader = FramedRead::new(server, codec);
+    writer.send(record(4, "framed")).await.unwrap();
+    let framed = reader.next().await.unwrap().unwrap();
+    assert_eq!(framed.get_u32("id").unwrap(), 4);
+
+    // the bytes buffered while waiting for a record are bounded
+    let codec = PackedCodec::new(schema, DecodeOrder::Big).with_max_record_size(8);
+    let (mut client, server) = tokio::io::duplex(64);
+    let mut reader = FramedRead::new(server, codec);
+    client
+        .write_all(&[0, 0, 0, 5, 200, b'a', b'b', b'c'])
+        .await
+        .unwrap();
+    assert!(matches!(
+        reader.next().await,
+        Some(Err(CodecError::TooLarge { size: 8, max: 8 }))
+    ));
+}
+
+#[cfg(feature = "mmap")]
+#[test]
+fn test_mmap_views() {
+    use decoder::{DecodeOrder, DecodeType, DecodedData, FieldRef};
+    use encoder::EncodeType;
+    use mmap::{FixedRecords, RecordFileView, RecordView, ViewError};
+    use recordfile::RecordWriter;
+
+    // record files are mapped and read through their index
+    let path = std::env::temp_dir().join(format!("packed-encoder-{}.mmap", std::process::id()));
+    let schema = schema::Schema::named("event")
+        .field("id", DecodeType::Uint32)
+        .field("len", DecodeType::Uint8)
+        .field("name", DecodeType::StrRef(FieldRef::Name("len".to_owned())));
+    let mut writer = RecordWriter::create(&path, schema, DecodeOrder::Little).unwrap();
+    for id in 0..1000u32 {
+        let name = format!("event-{}", id);
+        writer
+            .write(&[
+                EncodeType::Uint32(id),
+                EncodeType::Uint8(name.len() as u8),
+                EncodeType::Str(name),
+            ])
+            .unwrap();
+    }
+    writer.finish().unwrap();
+    let view = RecordFileView::open(&path).unwrap();
+    assert_eq!(view.len(), 1000);
+    assert_eq!(view.decode(999).unwrap().get_u32("id").unwrap(), 999);
+    assert_eq!(
+        view.field(123, 2).unwrap(),
+        DecodedData::Str("event-123".to_owned())
+    );
+    assert!(matches!(
+        view.decode(1000),
+        Err(ViewError::OutOfRange(1000))
+    ));
+    assert!(matches!(view.field(0, 3), Err(ViewError::NoField(3))));
+    let ids = view.par_map(3, |index, record| {
+        (index, record.unwrap().get_u32("id").unwrap())
+    });
+    assert!(ids.iter().all(|(index, id)| *index as u32 == *id));
+    drop(view);
+    std::fs::remove_file(&path).unwrap();
+
+    // a corrupted file is rejected before any record is read
+    assert!(matches!(
+        RecordFileView::new(vec![0u8; 8]),
+        Err(ViewError::File(_))
+    ));
+
+    // arrays of fixed-size records decode single fields in place
+    let schema = schema::Schema::new()
+        .field("id", DecodeType::Uint16)
+        .unnamed(DecodeType::Padding(2))
+        .field("value", DecodeType::Int32)
+        .field("flag", DecodeType::Bool);
+    let mut data = vec![];
+    for id in 0..10u16 {
+        data.extend_from_slice(&id.to_be_bytes());
+        data.extend_from_slice(&[0xff, 0xff]);
+        data.extend_from_slice(&(id as i32 * -3).to_be_bytes());
+        data.push((id % 2) as u8);
+    }
+    let records = FixedRecords::new(&data[..], schema.clone(), DecodeOrder::Big).unwrap();
+    assert_eq!(records.record_size(), 9);
+    assert_eq!(records.len(), 10);
+    assert_eq!(records.field(7, 2).unwrap(), DecodedData::Int32(-21));
+    assert_eq!(records.field(3, 3).unwrap(), DecodedData::Bool(true));
+    assert!(matches!(records.field(3, 1), Err(ViewError::NoField(1))));
+    assert!(matches!(
+        records.record_bytes(10),
+        Err(ViewError::OutOfRange(10))
+    ));
+    assert!(matches!(
+        records.record_bytes(1 << 61),
+        Err(ViewError::OutOfRange(index)) if index == 1 << 61
+    ));
+    let sum: i32 = records
+        .par_map(4, |_, record| record.unwrap().get_i32("value").unwrap())
+        .into_iter()
+        .sum();
+    assert_eq!(sum, -135);
+    assert!(matches!(
+        FixedRecords::new(&data[..8], schema, DecodeOrder::Big),
+        Err(ViewError::Size {
+            size: 8,
+            record_size: 9
+        })
+    ));
+    let variable = schema::Schema::new()
+        .field("len", DecodeType::Uint8)
+        .field("name", DecodeType::StrRef(FieldRef::Index(0)));
+    assert!(matches!(
+        FixedRecords::new(vec![], variable, DecodeOrder::Big),
+        Err(ViewError::VariableSize(1))
+    ));
+}
+
+#[test]
+fn test_self_describing() {
+    use bitfield::{BitOrder, BitValue};
+    use checksum::ChecksumKind;
+    use decoder::{DecodeOrder, DecodedData, FieldRef};
+    use describe::DescribeError;
+    use encoder::{EncodeOrder, EncodeType};
+
+    let values = [
+        EncodeType::Const(b"PK".to_vec()),
+        EncodeType::Uint16(0),
+        EncodeType::StrRef("sensor".to_owned(), FieldRef::Index(1)),
+        EncodeType::Float64(21.5),
+        EncodeType::BitGroup(
+            vec![BitValue::Unsigned(3, 5), BitValue::Signed(5, -2)],
+            BitOrder::Lsb,
+        ),
+        EncodeType::Reserved(2),
+        EncodeType::Array(vec![
+            EncodeType::Bytes(vec![1, 2]),
+            EncodeType::Bytes(vec![3, 4]),
+        ]),
+        EncodeType::Optional(Some(vec![EncodeType::Bool(true)])),
+        EncodeType::Checksum(ChecksumKind::Crc16Ccitt, 0..7),
+    ];
+    let encoded = describe::encode_self_describing(&values, EncodeOrder::Big).unwrap();
+    let payload = encoder::encode_packed(&values, EncodeOrder::Big).unwrap();
+    assert!(encoded.ends_with(&payload));
+
+    // the types and the byte order are read back from the descriptor
+    let (types, order, size) = describe::decode_descriptor(&encoded).unwrap();
+    assert_eq!(size, encoded.len() - payload.len());
+    assert!(matches!(order, DecodeOrder::Big));
+    assert_eq!(
+        format!("{:?}", types),
+        format!("{:?}", describe::describe(&values).unwrap())
+    );
+    let decoded = describe::decode_self_describing(&encoded).unwrap();
+    assert_eq!(decoded[0], DecodedData::Uint16(6));
+    assert_eq!(decoded[1], DecodedData::Str("sensor".to_owned()));
+    assert_eq!(decoded[2], DecodedData::Float64(21.5));
+    assert_eq!(
+        decoded[4],
+        DecodedData::Array(vec![
+            DecodedData::Bytes(vec![1, 2]),
+            DecodedData::Bytes(vec![3, 4])
+        ])
+    );
+    assert_eq!(
+        decoded[5],
+        DecodedData::Optional(Some(vec![DecodedData::Bool(true)]))
+    );
+    assert_eq!(decoded.len(), 7);
+
+    let little =
+        describe::encode_self_describing(&[EncodeType::Uint16(0x0102)], EncodeOrder::Little)
+            .unwrap();
+    assert_eq!(little, vec![0x11, 1, 0x07, 2, 1]);
+    assert_eq!(
+        describe::decode_self_describing(&little).unwrap(),
+        vec![DecodedData::Uint16(0x0102)]
+    );
+
+    // values that cannot be described
+    assert!(matches!(
+        describe::describe(&[EncodeType::Array(vec![
+            EncodeType::Str("a".to_owned()),
+            EncodeType::Str("bc".to_owned()),
+        ])]),
+        Err(DescribeError::Array(0))
+    ));
+    assert!(matches!(
+        describe::describe(&[
+            EncodeType::Uint8(0),
+            EncodeType::BytesRef(vec![], FieldRef::Name("len".to_owned())),
+        ]),
+        Err(DescribeError::Unsupported(1))
+    ));
+
+    // invalid descriptors
+    assert!(matches!(
+        describe::decode_self_describing(&[0x21, 0]),
+        Err(DescribeError::Version(2))
+    ));
+    assert!(matches!(
+        describe::decode_self_describing(&[0x10, 1, 0x7f]),
+        Err(DescribeError::Descriptor(2))
+    ));
+    assert!(matches!(
+        describe::decode_self_describing(&[0x10, 1, 0x14, 0xff, 0xff, 0x03, 0x1a, 0]),
+        Err(DescribeError::Descriptor(2))
+    ));
+    let mut oversized = vec![0x10, 2, 0x06, 0x10];
+    oversized.extend_from_slice(&[0xff; 9]);
+    oversized.extend_from_slice(&[0x01, 7, 8]);
+    assert!(matches!(
+        describe::decode_self_describing(&oversized),
+        Err(DescribeError::Descriptor(4))
+    ));
+    assert!(matches!(
+        describe::decode_self_describing(&encoded[..encoded.len() - 1]),
+        Err(DescribeError::Decode(_))
+    ));
+    let mut corrupted = encoded.clone();
+    *corrupted.last_mut().unwrap() ^= 1;
+    assert!(matches!(
+        describe::decode_self_describing(&corrupted),
+        Err(DescribeError::Decode(
+            decoder::DecodeError::ChecksumMismatch { .. }
+        ))
+    ));
+}
+
+#[test]
+fn test_tlv() {
+    use bitfield::{BitOrder, BitValue};
+    use checksum::ChecksumKind;
+    use decoder::{DecodeError, DecodeOrder, DecodedData, FieldRef, Predicate};
+    use encoder::{EncodeError, EncodeOrder, EncodeType};
+    use tlv::TlvEntry;
+
+    let values = [
+        EncodeType::Const(b"PK".to_vec()),
+        EncodeType::Uint16(0),
+        EncodeType::BytesRef(vec![0xca, 0xfe], FieldRef::Index(1)),
+        EncodeType::Int32(-2),
+        EncodeType::Float64(0.5),
+        EncodeType::Bool(true),
+        EncodeType::Reserved(3),
+        EncodeType::BitGroup(
+            vec![BitValue::Unsigned(3, 5), BitValue::Signed(6, -7)],
+            BitOrder::Msb,
+        ),
+        EncodeType::Array(vec![
+            EncodeType::Str("a".to_owned()),
+            EncodeType::Str("bcd".to_owned()),
+        ]),
+        EncodeType::Conditional(
+            FieldRef::Index(5),
+            Predicate::Equals(DecodedData::Bool(true)),
+            vec![
+                EncodeType::Uint8(0),
+                EncodeType::ArrayRef(vec![EncodeType::Int8(9)], FieldRef::Index(0)),
+            ],
+        ),
+        EncodeType::Optional(None),
+    ];
+    let encoded = tlv::encode_tlv(&values, EncodeOrder::Little).unwrap();
+    assert_eq!(&encoded[..7], &[0x11, 2, 0, 0x35, 2, 0xca, 0xfe]);
+    let entries = tlv::decode_tlv(&encoded, DecodeOrder::Little).unwrap();
+    let decoded: Vec<DecodedData> = entries
+        .iter()
+        .map(|entry| entry.value().unwrap().clone())
+        .collect();
+    assert_eq!(
+        decoded,
+        vec![
+            DecodedData::Uint16(2),
+            DecodedData::Bytes(vec![0xca, 0xfe]),
+            DecodedData::Int32(-2),
+            DecodedData::Float64(0.5),
+            DecodedData::Bool(true),
+            DecodedData::BitGroup(
+                vec![BitValue::Unsigned(3, 5), BitValue::Signed(6, -7)],
+                BitOrder::Msb
+            ),
+            DecodedData::Array(vec![
+                DecodedData::Str("a".to_owned()),
+                DecodedData::Str("bcd".to_owned())
+            ]),
+            DecodedData::Optional(Some(vec![
+                DecodedData::Uint8(1),
+                DecodedData::Array(vec![DecodedData::Int8(9)])
+            ])),
+            DecodedData::Optional(None),
+        ]
+    );
+    assert_eq!(entries[2].type_id(), 1);
+
+    // entries added by newer encoders are returned as unknown, and skipped when nested
+    let mut newer = tlv::encode_tlv(&[EncodeType::Uint32(7)], EncodeOrder::Big).unwrap();
+    newer.extend_from_slice(&[0xf8, 0xaa]);
+    newer.extend_from_slice(&[0x3d, 4, 0x10, 1, 0xf8, 0xbb]);
+    newer.extend_from_slice(&[0xfd, 0x82, 0x01]);
+    newer.extend_from_slice(&[0x42; 130]);
+    let entries = tlv::decode_tlv(&newer, DecodeOrder::Big).unwrap();
+    assert_eq!(entries.len(), 4);
+    assert_eq!(entries[0], TlvEntry::Value(DecodedData::Uint32(7)));
+    assert_eq!(
+        entries[1],
+        TlvEntry::Unknown {
+            tag: 0xf8,
+            content: vec![0xaa]
+        }
+    );
+    assert_eq!(
+        entries[2],
+        TlvEntry::Value(DecodedData::Array(vec![DecodedData::Uint8(1)]))
+    );
+    assert_eq!(entries[3].type_id(), 31);
+    assert!(matches!(&entries[3], TlvEntry::Unknown { content, .. } if content.len() == 130));
+
+    // invalid and truncated entries
+    assert!(matches!(
+        tlv::decode_tlv(&[0x10, 1, 0x16], DecodeOrder::Big),
+        Err(DecodeError::InvalidData(1))
+    ));
+    assert!(matches!(
+        tlv::decode_tlv(&[0x11, 1], DecodeOrder::Big),
+        Err(DecodeError::IndexOutOfBounds)
+    ));
+    assert!(matches!(
+        tlv::decode_tlv(&[0x2d, 2, 0xff, 0xfe], DecodeOrder::Big),
+        Err(DecodeError::InvalidData(0))
+    ));
+    assert!(matches!(
+        tlv::encode_tlv(
+            &[
+                EncodeType::Uint8(1),
+                EncodeType::Checksum(ChecksumKind::Crc8, 0..1)
+            ],
+            EncodeOrder::Big
+        ),
+        Err(EncodeError::Checksum(..))
+    ));
+    assert!(matches!(
+        tlv::encode_tlv(
+            &[EncodeType::StrRef("x".to_owned(), FieldRef::Index(0))],
+            EncodeOrder::Big
+        ),
+        Err(EncodeError::LengthField(_))
+    ));
+}
+
+#[test]
+fn test_schema_evolution() {
+    use decoder::{DecodeOrder, DecodeType, DecodedData, FieldRef};
+    use encoder::{EncodeOrder, EncodeType};
+    use evolution::{Change, EvolutionError};
+    use schema::{Field, Schema};
+
+    let v1 = text::parse_schema(
+        "struct Reading version 1 { id: u16; len: u8; name: str[len]; value: i16; }",
+    )
+    .unwrap();
+    let v2 = text::parse_schema(
+        r#"struct Reading version 2 {
+            id: u16;
+            len: u8;
+            label: str[len];
+            value: i32;
+            unit: str[2] = "mV";
+            scale: u8 = 10;
+            flags: u16;
+        }"#,
+    )
+    .unwrap();
+    assert_eq!(v2.version, Some(2));
+    assert_eq!(
+        v2.fields[4].default,
+        Some(DecodedData::Str("mV".to_owned()))
+    );
+    // versions and defaults survive the textual representation
+    let printed = text::print(&v2);
+    assert!(printed.starts_with("struct Reading version 2 {"));
+    assert!(printed.contains("scale: u8 = 10;"));
+    let reparsed = text::parse_schema(&printed).unwrap();
+    assert_eq!(reparsed.version, Some(2));
+    assert_eq!(reparsed.fields[5].default, Some(DecodedData::Uint8(10)));
+    let unnamed = text::parse_schema("struct version 3 { a: u8; }").unwrap();
+    assert_eq!((unnamed.name, unnamed.version), (None, Some(3)));
+    let float = text::parse_schema("struct A { a: f32 = 1; }").unwrap();
+    assert_eq!(float.fields[0].default, Some(DecodedData::Float32(1.0)));
+    assert!(text::parse_schema("struct A { a: reserved[1] = 1; }").is_err());
+
+    let compatibility = evolution::check(&v1, &v2);
+    assert_eq!(
+        (compatibility.from_version, compatibility.to_version),
+        (Some(1), Some(2))
+    );
+    assert!(compatibility.backward);
+    assert!(!compatibility.forward);
+    assert_eq!(
+        compatibility.changes,
+        vec![
+            Change::Renamed {
+                index: 2,
+                from: Some("name".to_owned()),
+                to: Some("label".to_owned())
+            },
+            Change::Widened {
+                index: 3,
+                from: DecodeType::Int16,
+                to: DecodeType::Int32
+            },
+            Change::Added {
+                index: 4,
+                name: Some("unit".to_owned())
+            },
+            Change::Added {
+                index: 5,
+                name: Some("scale".to_owned())
+            },
+            Change::Added {
+                index: 6,
+                name: Some("flags".to_owned())
+            },
+        ]
+    );
+
+    // older data is read into the newer layout
+    let old = v1
+        .encode(
+            &[
+                EncodeType::Uint16(4),
+                EncodeType::Uint8(0),
+                EncodeType::StrRef("probe".to_owned(), FieldRef::Name("len".to_owned())),
+                EncodeType::Int16(-300),
+            ],
+            EncodeOrder::Little,
+        )
+        .unwrap();
+    let record = evolution::decode_upgraded(&v1, &v2, &old, DecodeOrder::Little).unwrap();
+    assert_eq!(record.get_str("label").unwrap(), "probe");
+    assert_eq!(record.get_i32("value").unwrap(), -300);
+    assert_eq!(record.get_str("unit").unwrap(), "mV");
+    assert_eq!(record.get_u8("scale").unwrap(), 10);
+    assert_eq!(record.get_u16("flags").unwrap(), 0);
+
+    // fields added at the tail only are readable in both directions
+    let v3 = v1
+        .clone()
+        .with_version(3)
+        .push(Field::new("extra", DecodeType::Uint8).with_default(DecodedData::Uint8(1)));
+    assert!(evolution::check(&v1, &v3).is_full());
+    let newer = [old.clone(), vec![9]].concat();
+    assert_eq!(
+        v1.decode(&newer, DecodeOrder::Little)
+            .unwrap()
+            .get_i16("value")
+            .unwrap(),
+        -300
+    );
+    let compatibility = evolution::check(&v3, &v1);
+    assert!(compatibility.backward && !compatibility.forward);
+    assert_eq!(
+        compatibility.changes,
+        vec![Change::Removed {
+            index: 4,
+            name: Some("extra".to_owned())
+        }]
+    );
+
+    // narrowed and changed types
+    let narrow = Schema::new()
+        .field("id", DecodeType::Uint8)
+        .field("name", DecodeType::Str(2));
+    let wide = Schema::new()
+        .field("id", DecodeType::Int16)
+        .field("name", DecodeType::Str(4));
+    let compatibility = evolution::check(&wide, &narrow);
+    assert!(!compatibility.backward && !compatibility.forward);
+    assert_eq!(
+        compatibility.changes[1],
+        Change::Resized {
+            index: 1,
+            from: 4,
+            to: 2
+        }
+    );
+    assert!(matches!(
+        compatibility.changes[0],
+        Change::Incompatible { index: 0, .. }
+    ));
+    let upgraded =
+        evolution::decode_upgraded(&narrow, &wide, &[7, b'o', b'k'], DecodeOrder::Big).unwrap();
+    assert_eq!(upgraded.get_i16("id").unwrap(), 7);
+    assert_eq!(upgraded.get_str("name").unwrap(), "ok\0\0");
+    assert!(matches!(
+        evolution::decode_upgraded(&wide, &narrow, &[0, 7, b'o', b'k', 0, 0], DecodeOrder::Big),
+        Err(EvolutionError::Incompatible(0))
+    ));
+    // a changed byte order moves the bytes of the value
+    let little =
+        Schema::new().push(Field::new("id", DecodeType::Int16).with_order(DecodeOrder::Little));
+    let big = Schema::new().push(Field::new("id", DecodeType::Int16).with_order(DecodeOrder::Big));
+    assert!(!evolution::check(&little, &big).backward);
+}
+
+#[test]
+fn test_fingerprint() {
+    use decoder::{DecodeOrder, DecodeType, DecodedData, FieldRef, Predicate};
+    use encoder::{EncodeOrder, EncodeType};
+    use fingerprint::{FingerprintError, FingerprintKind};
+    use schema::{Field, Schema};
+
+    // reference values of the FNV-1a specification
+    assert_eq!(fingerprint::fnv1a_32(b""), 0x811c_9dc5);
+    assert_eq!(fingerprint::fnv1a_32(b"a"), 0xe40c_292c);
+    assert_eq!(fingerprint::fnv1a_64(b"a"), 0xaf63_dc4c_8601_ec8c);
+    assert_eq!(fingerprint::fnv1a_64(b"foobar"), 0x8594_4171_f739_67e8);
+
+    let schema = Schema::new()
+        .field("kind", DecodeType::Uint8)
+        .field("len", DecodeType::Uint16)
+        .field("name", DecodeType::StrRef(FieldRef::Name("len".to_owned())))
+        .field(
+            "extra",
+            DecodeType::Conditional(
+                FieldRef::Name("kind".to_owned()),
+                Predicate::Equals(DecodedData::Uint8(2)),
+                vec![DecodeType::Float64],
+            ),
+        );
+    let types = [
+        DecodeType::Uint8,
+        DecodeType::Uint16,
+        DecodeType::StrRef(FieldRef::Index(1)),
+        DecodeType::Conditional(
+            FieldRef::Index(0),
+            Predicate::Equals(DecodedData::Uint8(2)),
+            vec![DecodeType::Float64],
+        ),
+    ];
+    // references by name and by index give the same canonical serialization
+    let canonical = fingerprint::canonical_schema(&schema, DecodeOrder::Big);
+    assert_eq!(canonical, fingerprint::canonical(&types, DecodeOrder::Big));
+    assert_eq!(canonical[0], fingerprint::CANONICAL_VERSION);
+    // fingerprints must never change across platforms and versions of the crate
+    let fingerprint32 =
+        fingerprint::schema_fingerprint(FingerprintKind::Fnv1a32, &schema, DecodeOrder::Big);
+    let fingerprint64 =
+        fingerprint::schema_fingerprint(FingerprintKind::Fnv1a64, &schema, DecodeOrder::Big);
+    assert_eq!(fingerprint32, 0xd65f_f6dd);
+    assert_eq!(fingerprint64, 0xfe58_bb14_5a79_a5fd);
+
+    // the byte order, the types and the predicates change the fingerprint
+    let print = |schema: &Schema, order| {
+        fingerprint::schema_fingerprint(FingerprintKind::Fnv1a64, schema, order)
+    };
+    assert_ne!(print(&schema, DecodeOrder::Little), fingerprint64);
+    let mut overridden = schema.clone();
+    overridden.fields[1] = Field::new("len", DecodeType::Uint16).with_order(DecodeOrder::Little);
+    assert_ne!(print(&overridden, DecodeOrder::Big), fingerprint64);
+    let mut changed = schema.clone();
+    changed.fields[3].kind = DecodeType::Conditional(
+        FieldRef::Index(0),
+        Predicate::Equals(DecodedData::Uint8(3)),
+        vec![DecodeType::Float64],
+    );
+    assert_ne!(print(&changed, DecodeOrder::Big), fingerprint64);
+    // names, defaults and versions do not
+    let mut renamed = schema.clone().with_version(4);
+    renamed.fields[0] = Field::new("type", DecodeType::Uint8).with_default(DecodedData::Uint8(1));
+    renamed.fields[3].kind = DecodeType::Conditional(
+        FieldRef::Name("type".to_owned()),
+        Predicate::Equals(DecodedData::Uint8(2)),
+        vec![DecodeType::Float64],
+    );
+    assert_eq!(print(&renamed, DecodeOrder::Big), fingerprint64);
+
+    // messages carry the fingerprint in front of the record
+    let values = [
+        EncodeType::Uint8(1),
+        EncodeType::Uint16(0),
+        EncodeType::StrRef("abc".to_owned(), FieldRef::Name("len".to_owned())),
+        EncodeType::Conditional(
+            FieldRef::Index(0),
+            Predicate::Equals(DecodedData::Uint8(2)),
+            vec![EncodeType::Float64(1.0)],
+        ),
+    ];
+    let encoded = fingerprint::encode_fingerprinted(
+        FingerprintKind::Fnv1a32,
+        &schema,
+        &values,
+        EncodeOrder::Little,
+    )
+    .unwrap();
+    assert_eq!(
+        &encoded[..4],
+        &(fingerprint::schema_fingerprint(FingerprintKind::Fnv1a32, &schema, DecodeOrder::Little)
+            as u32)
+            .to_le_bytes()
+    );
+    let record = fingerprint::decode_fingerprinted(
+        FingerprintKind::Fnv1a32,
+        &schema,
+        &encoded,
+        DecodeOrder::Little,
+    )
+    .unwrap();
+    assert_eq!(record.get_str("name").unwrap(), "abc");
+    assert!(matches!(
+        fingerprint::decode_fingerprinted(
+            FingerprintKind::Fnv1a32,
+            &changed,
+            &encoded,
+            DecodeOrder::Little
+        ),
+        Err(FingerprintError::Mismatch { .. })
+    ));
+    assert!(matches!(
+        fingerprint::decode_fingerprinted(
+            FingerprintKind::Fnv1a64,
+            &schema,
+            &encoded[..4],
+            DecodeOrder::Little
+        ),
+        Err(FingerprintError::Truncated)
+    ));
+    let mut header = vec![];
+    fingerprint::write_fingerprint(
+        FingerprintKind::Fnv1a64,
+        fingerprint64,
+        &mut header,
+        EncodeOrder::Big,
+    );
+    header.push(0xff);
+    assert_eq!(
+        fingerprint::check_fingerprint(
+            FingerprintKind::Fnv1a64,
+            fingerprint64,
+            &header,
+            DecodeOrder::Big
+        )
+        .unwrap(),
+        &[0xff]
+    );
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn test_json() {
+    use bitfield::{BitField, BitOrder};
+    use decoder::{DecodeOrder, DecodeType, FieldRef};
+    use encoder::EncodeOrder;
+    use json::{BytesPolicy, IntegerPolicy, JsonError, JsonOptions};
+    use serde_json::json;
+
+    let schema = schema::Schema::named("packet")
+        .field("magic", DecodeType::Const(vec![0xbe, 0xef]))
+        .field("id", DecodeType::Uint64)
+        .field("len", DecodeType::Uint8)
+        .field(
+            "payload",
+            DecodeType::BytesRef(FieldRef::Name("len".to_owned())),
+        )
+        .field(
+            "flags",
+            DecodeType::BitGroup(
+                vec![BitField::Unsigned(3), BitField::Signed(5)],
+                BitOrder::Msb,
+            ),
+        )
+        .unnamed(DecodeType::Int128)
+        .field("ratio", DecodeType::Float32)
+        .field("samples", DecodeType::Array(Box::new(DecodeType::Int16), 2));
+
+    // named values become the keys of an object, bytes follow the policy and wide integers may be strings
+    let document = json!({
+        "id": "18446744073709551615",
+        "payload": "AAEC/w==",
+        "flags": [5, -3],
+        "#4": i128::MIN.to_string(),
+        "ratio": 0.1,
+        "samples": [-1, 300],
+    });
+    let options = JsonOptions::new()
+        .with_integers(IntegerPolicy::String)
+        .with_bytes(BytesPolicy::Base64);
+    let values = json::from_json(&document, &schema, &options).unwrap();
+    let encoded = schema.encode(&values, EncodeOrder::Big).unwrap();
+    assert_eq!(&encoded[..2], &[0xbe, 0xef]);
+    assert_eq!(encoded[10], 4);
+
+    let record = schema.decode(&encoded, DecodeOrder::Big).unwrap();
+    let expected = json!({
+        "id": "18446744073709551615",
+        "len": 4,
+        "payload": "AAEC/w==",
+        "flags": [5, -3],
+        "#4": "-170141183460469231731687303715884105728",
+        "ratio": 0.1,
+        "samples": [-1, 300],
+    });
+    assert_eq!(json::record_to_json(&record, &options), expected);
+    let values = json::from_json(&expected, &schema, &options).unwrap();
+    assert_eq!(schema.encode(&values, EncodeOrder::Big).unwrap(), encoded);
+
+    // numbers are used when they fit, arrays are matched to the values by position
+    let numbers = json::to_json(record.values(), &JsonOptions::new());
+    assert_eq!(numbers[0], json!(u64::MAX));
+    assert_eq!(numbers[2], json!("000102ff"));
+    assert_eq!(
+        numbers[4],
+        json!("-170141183460469231731687303715884105728")
+    );
+    let values = json::from_json(&numbers, &schema, &JsonOptions::new()).unwrap();
+    assert_eq!(schema.encode(&values, EncodeOrder::Big).unwrap(), encoded);
+    let mut signed = numbers.clone();
+    signed[2] = json!("+f000102");
+    assert!(matches!(
+        json::from_json(&signed, &schema, &JsonOptions::new()),
+        Err(JsonError::Value { field, .. }) if field == "payload"
+    ));
+
+    assert_eq!(
+        json::from_json(&json!({ "id": 1 }), &schema, &options),
+        Err(JsonError::MissingField("payload".to_owned()))
+    );
+    assert_eq!(
+        json::from_json(&json!([1, 2]), &schema, &options),
+        Err(JsonError::Count {
+            expected: 7,
+            found: 2
+        })
+    );
+    let mut invalid = expected.clone();
+    invalid["samples"] = json!([1, 2, 3]);
+    assert!(matches!(
+        json::from_json(&invalid, &schema, &options),
+        Err(JsonError::Value { field, .. }) if field == "samples"
+    ));
+    assert_eq!(
+        json::from_json(&json!("packet"), &schema, &options),
+        Err(JsonError::Shape)
+    );
+}
@@ -0,0 +1,363 @@
+//! `mmap` gives random access to the records of a memory-mapped file without reading them into buffers, it is
+//! enabled by the `mmap` feature. `RecordFileView` reads the files written by `recordfile::RecordWriter` and
+//! `FixedRecords` reads raw arrays of fixed-size records. Both views work over any byte slice as well, like a `Vec`,
+//! and share the `RecordView` trait for decoding and parallel iteration.
+
+use crate::decoder::{self, DecodeError, DecodeOrder, DecodeType, DecodedData};
+use crate::recordfile::{self, RecordFileError};
+use crate::schema::{DecodedRecord, Schema};
+use memmap2::Mmap;
+use std::fmt;
+use std::fs::File;
+use std::io::Cursor;
+use std::path::Path;
+use std::thread;
+
+#[derive(Debug)]
+/// `ViewError` is returned when a view cannot be created or when a record cannot be read from it.
+pub enum ViewError {
+    /// File occurs when the record file cannot be opened or its header and index are invalid
+    File(RecordFileError),
+    /// VariableSize occurs when the field at the given index of the schema of `FixedRecords` does not have a fixed
+    /// size
+    VariableSize(usize),
+    /// Size occurs when the size of the data of `FixedRecords` is not a multiple of the record size
+    Size { size: usize, record_size: usize },
+    /// OutOfRange occurs when the record number is not smaller than the number of records
+    OutOfRange(usize),
+    /// NoField occurs when the schema has no field at the given index, or when the field has no value like padding
+    NoField(usize),
+    /// Decode occurs when a record does not match the schema
+    Decode(DecodeError),
+}
+
+impl fmt::Display for ViewError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ViewError::File(error) => write!(f, "{}", error),
+            ViewError::VariableSize(index) => {
+                write!(f, "field {} does not have a fixed size", index)
+            }
+            ViewError::Size { size, record_size } => write!(
+                f,
+                "{} bytes are not a multiple of the record size of {} bytes",
+                size, record_size
+            ),
+            ViewError::OutOfRange(index) => write!(f, "record {} is out of range", index),
+            ViewError::NoField(index) => write!(f, "field {} has no value", index),
+            ViewError::Decode(error) => write!(f, "cannot decode record: {:?}", error),
+        }
+    }
+}
+
+impl std::error::Error for ViewError {}
+
+impl From<RecordFileError> for ViewError {
+    fn from(error: RecordFileError) -> ViewError {
+        ViewError::File(error)
+    }
+}
+
+impl From<std::io::Error> for ViewError {
+    fn from(error: std::io::Error) -> ViewError {
+        ViewError::File(RecordFileError::Io(error))
+    }
+}
+
+/// maps the whole file in memory
+fn map(path: &Path) -> Result<Mmap, ViewError> {
+    let file = File::open(path)?;
+    // the mapping is only read, modifying the file while it is mapped is not supported
+    let mapped = unsafe { Mmap::map(&file)? };
+    Ok(mapped)
+}
+
+/// `RecordView` gives access to records stored one after the other in memory.
+pub trait RecordView {
+    /// returns the schema of the records
+    fn schema(&self) -> &Schema;
+
+    /// returns the byte order of the records
+    fn order(&self) -> DecodeOrder;
+
+    /// returns the number of records
+    fn len(&self) -> usize;
+
+    /// returns true when there are no records
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// returns the encoded bytes of the record with the given number, without copying them
+    fn record_bytes(&self, index: usize) -> Result<&[u8], ViewError>;
+
+    /// decodes the record with the given number
+    fn decode(&self, index: usize) -> Result<DecodedRecord, ViewError> {
+        self.schema()
+            .decode(self.record_bytes(index)?, self.order())
+            .map_err(ViewError::Decode)
+    }
+
+    /// decodes the field at the given index of the schema in the record with the given number
+    fn field(&self, index: usize, field: usize) -> Result<DecodedData, ViewError> {
+        decode_field(
+            self.schema(),
+            self.record_bytes(index)?,
+            self.order(),
+            field,
+        )
+    }
+
+    /// decodes every record on the given number of threads and returns the results of `f` in the order of the
+    /// records, `f` receives the number of the record and its decoded value
+    fn par_map<T, F>(&self, threads: usize, f: F) -> Vec<T>
+    where
+        Self: Sync,
+        T: Send,
+        F: Fn(usize, Result<DecodedRecord, ViewError>) -> T + Sync,
+    {
+        let count = self.len();
+        let chunk = count.div_ceil(threads.max(1)).max(1);
+        thread::scope(|scope| {
+            let workers: Vec<_> = (0..count)
+                .step_by(chunk)
+                .map(|start| {
+                    let f = &f;
+                    scope.spawn(move || {
+                        (start..(start + chunk).min(count))
+                            .map(|index| f(index, self.decode(index)))
+                            .collect::<Vec<T>>()
+                    })
+                })
+                .collect();
+            workers
+                .into_iter()
+                .flat_map(|worker| worker.join().expect("record view worker panicked"))
+                .collect()
+        })
+    }
+}
+
+/// decodes a whole record and returns the value of the field at the given index of the schema
+fn decode_field(
+    schema: &Schema,
+    bytes: &[u8],
+    order: DecodeOrder,
+    field: usize,
+) -> Result<DecodedData, ViewError> {
+    if field >= schema.fields.len() {
+        return Err(ViewError::NoField(field));
+    }
+    let mut list = decoder::decode_list(&schema.fields, bytes, 0, order)
+        .0
+        .map_err(ViewError::Decode)?;
+    match list.positions[field] {
+        Some(position) => Ok(list.values.swap_remove(position)),
+        None => Err(ViewError::NoField(field)),
+    }
+}
+
+/// `RecordFileView` reads a record file written by `recordfile::RecordWriter`, its records are located through the
+/// index of the file.
+///
+/// # Examples
+/// ```rust
+/// use packed_encoder::decoder::{DecodeOrder, DecodeType};
+/// use packed_encoder::encoder::EncodeType;
+/// use packed_encoder::mmap::{RecordFileView, RecordView};
+/// use packed_encoder::recordfile::RecordWriter;
+/// use packed_encoder::schema::Schema;
+/// use std::io::Cursor;
+///
+/// let schema = Schema::named("sample").field("id", DecodeType::Uint32);
+/// let mut writer = RecordWriter::new(Cursor::new(vec![]), schema, DecodeOrder::Little).unwrap();
+/// for id in 0..100 {
+///     writer.write(&[EncodeType::Uint32(id)]).unwrap();
+/// }
+/// let file = writer.finish().unwrap().into_inner();
+///
+/// let view = RecordFileView::new(file).unwrap();
+/// assert_eq!(view.decode(42).unwrap().get_u32("id").unwrap(), 42);
+/// let sum: u32 = view.par_map(4, |_, record| record.unwrap().get_u32("id").unwrap()).iter().sum();
+/// assert_eq!(sum, 4950);
+/// ```
+pub struct RecordFileView<D: AsRef<[u8]>> {
+    data: D,
+    schema: Schema,
+    order: DecodeOrder,
+    offsets: Vec<u64>,
+}
+
+impl<D: AsRef<[u8]>> RecordFileView<D> {
+    /// reads the header and the index of the record file held by `data`
+    pub fn new(data: D) -> Result<RecordFileView<D>, ViewError> {
+        let mut cursor = Cursor::new(data.as_ref());
+        let (schema, order, data_start) = recordfile::read_header(&mut cursor)?;
+        let (offsets, index_offset) = recordfile::read_index(&mut cursor, data_start)?;
+        // every record must lie before the index so that reading it cannot go out of bounds
+        let bytes = data.as_ref();
+        for offset in &offsets {
+            let corrupt = || ViewError::from(RecordFileError::Corrupt(*offset));
+            if *offset < data_start || offset.saturating_add(4) > index_offset {
+                return Err(corrupt());
+            }
+            let start = *offset as usize;
+            let size = u32::from_le_bytes(bytes[start..start + 4].try_into().unwrap()) as u64;
+            if *offset + 4 + size > index_offset {
+                return Err(corrupt());
+            }
+        }
+        Ok(RecordFileView {
+            data,
+            schema,
+            order,
+            offsets,
+        })
+    }
+}
+
+impl RecordFileView<Mmap> {
+    /// maps the record file at the given path
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<RecordFileView<Mmap>, ViewError> {
+        RecordFileView::new(map(path.as_ref())?)
+    }
+}
+
+impl<D: AsRef<[u8]>> RecordView for RecordFileView<D> {
+    fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    fn order(&self) -> DecodeOrder {
+        self.order.clone()
+    }
+
+    fn len(&self) -> usize {
+        self.offsets.len()
+    }
+
+    fn record_bytes(&self, index: usize) -> Result<&[u8], ViewError> {
+        let offset = *self
+            .offsets
+            .get(index)
+            .ok_or(ViewError::OutOfRange(index))? as usize;
+        let bytes = self.data.as_ref();
+        let size = u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap()) as usize;
+        Ok(&bytes[offset + 4..offset + 4 + size])
+    }
+}
+
+/// `FixedRecords` reads an array of records whose fields all have a fixed size, like a file of C structs. Single
+/// fields are decoded in place without decoding the rest of the record.
+///
+/// # Examples
+/// ```rust
+/// use packed_encoder::decoder::{DecodeOrder, DecodeType, DecodedData};
+/// use packed_encoder::mmap::{FixedRecords, RecordView};
+/// use packed_encoder::schema::Schema;
+///
+/// let schema = Schema::new()
+///     .field("id", DecodeType::Uint16)
+///     .unnamed(DecodeType::Padding(2))
+///     .field("value", DecodeType::Int32);
+/// let data = vec![1, 0, 0, 0, 0xff, 0xff, 0xff, 0xff, 2, 0, 0, 0, 7, 0, 0, 0];
+/// let records = FixedRecords::new(data, schema, DecodeOrder::Little).unwrap();
+/// assert_eq!(records.len(), 2);
+/// assert_eq!(records.field(1, 2).unwrap(), DecodedData::Int32(7));
+/// ```
+pub struct FixedRecords<D: AsRef<[u8]>> {
+    data: D,
+    schema: Schema,
+    order: DecodeOrder,
+    record_size: usize,
+    // offset of every field within a record
+    offsets: Vec<usize>,
+}
+
+impl<D: AsRef<[u8]>> FixedRecords<D> {
+    /// creates a view over `data`, which must hold a whole number of records of the schema
+    pub fn new(data: D, schema: Schema, order: DecodeOrder) -> Result<FixedRecords<D>, ViewError> {
+        let mut offsets = Vec::with_capacity(schema.fields.len());
+        let mut record_size = 0;
+        for (index, field) in schema.fields.iter().enumerate() {
+            offsets.push(record_size);
+            record_size += field
+                .kind
+                .fixed_size()
+                .ok_or(ViewError::VariableSize(index))?;
+        }
+        let size = data.as_ref().len();
+        if record_size == 0 || size % record_size != 0 {
+            return Err(ViewError::Size { size, record_size });
+        }
+        Ok(FixedRecords {
+            data,
+            schema,
+            order,
+            record_size,
+            offsets,
+        })
+    }
+
+    /// returns the size of every record
+    pub fn record_size(&self) -> usize {
+        self.record_size
+    }
+}
+
+impl FixedRecords<Mmap> {
+    /// maps the file at the given path
+    pub fn open<P: AsRef<Path>>(
+        path: P,
+        schema: Schema,
+        order: DecodeOrder,
+    ) -> Result<FixedRecords<Mmap>, ViewError> {
+        FixedRecords::new(map(path.as_ref())?, schema, order)
+    }
+}
+
+impl<D: AsRef<[u8]>> RecordView for FixedRecords<D> {
+    fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    fn order(&self) -> DecodeOrder {
+        self.order.clone()
+    }
+
+    fn len(&self) -> usize {
+        self.data.as_ref().len() / self.record_size
+    }
+
+    fn record_bytes(&self, index: usize) -> Result<&[u8], ViewError> {
+        let start = index
+            .checked_mul(self.record_size)
+            .ok_or(ViewError::OutOfRange(index))?;
+        let end = start
+            .checked_add(self.record_size)
+            .ok_or(ViewError::OutOfRange(index))?;
+        self.data
+            .as_ref()
+            .get(start..end)
+            .ok_or(ViewError::OutOfRange(index))
+    }
+
+    fn field(&self, index: usize, field: usize) -> Result<DecodedData, ViewError> {
+        let record = self.record_bytes(index)?;
+        let entry = self
+            .schema
+            .fields
+            .get(field)
+            .ok_or(ViewError::NoField(field))?;
+        // fields referring to other fields, like checksums, need the whole record
+        if matches!(entry.kind, DecodeType::Checksum(..)) {
+            return decode_field(&self.schema, record, self.order.clone(), field);
+        }
+        let order = entry.order.clone().unwrap_or_else(|| self.order.clone());
+        let bytes = &record[self.offsets[field]..];
+        decoder::decode_packed(std::slice::from_ref(&entry.kind), bytes, order)
+            .map_err(ViewError::Decode)?
+            .pop()
+            .ok_or(ViewError::NoField(field))
+    }
+}
@@ -0,0 +1,662 @@
+use crate::decoder::{DecodeError, DecodeOrder, DecodeType, DecodedData};
+use crate::encoder::{self, EncodeError, EncodeOrder, EncodeType};
+use crate::schema::{Field, Schema};
+use std::fmt;
+
+#[derive(Debug, Clone)]
+/// `TemplateError` is returned when a Perl/Ruby `pack` template cannot be parsed or when the values or the bytes
+/// do not match it.
+pub enum TemplateError {
+    /// Unsupported occurs when the template uses a directive or a modifier that is not supported, like `U`, `(`
+    /// or `!`. Contains the directive and its position in the template.
+    Unsupported(char, usize),
+    /// InvalidTemplate occurs when the template cannot be parsed, contains the position of the offending character
+    InvalidTemplate(usize),
+    /// MissingValue occurs when there are not enough values for the directive at the given position
+    MissingValue(usize),
+    /// ExtraValues occurs when values are left once all the directives are packed, contains their number
+    ExtraValues(usize),
+    /// Value occurs when the value at the given index cannot be packed by its directive
+    Value(usize),
+    /// Backward occurs when the directive at the given position moves before the start of the packed bytes, or
+    /// moves backwards while unpacking, which cannot be expressed as a list of `DecodeType`
+    Backward(usize),
+    /// OutOfBounds occurs when the directive at the given position reads past the end of the buffer
+    OutOfBounds(usize),
+    /// Encode wraps the error returned by the encoder
+    Encode(EncodeError),
+    /// Decode wraps the error returned by the decoder
+    Decode(DecodeError),
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TemplateError::Unsupported(directive, position) => write!(
+                f,
+                "'{}' at position {} is not supported",
+                directive, position
+            ),
+            TemplateError::InvalidTemplate(position) => {
+                write!(f, "invalid template at position {}", position)
+            }
+            TemplateError::MissingValue(position) => {
+                write!(
+                    f,
+                    "not enough values for the directive at position {}",
+                    position
+                )
+            }
+            TemplateError::ExtraValues(count) => write!(f, "{} values left unpacked", count),
+            TemplateError::Value(index) => {
+                write!(f, "value {} cannot be packed by its directive", index)
+            }
+            TemplateError::Backward(position) => {
+                write!(f, "directive at position {} moves backwards", position)
+            }
+            TemplateError::OutOfBounds(position) => write!(
+                f,
+                "directive at position {} reads past the end of the buffer",
+                position
+            ),
+            TemplateError::Encode(err) => write!(f, "encode error: {:?}", err),
+            TemplateError::Decode(err) => write!(f, "decode error: {:?}", err),
+        }
+    }
+}
+
+impl std::error::Error for TemplateError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Count {
+    Number(usize),
+    Star,
+}
+
+#[derive(Debug, Clone)]
+struct Directive {
+    code: char,
+    // byte order given by the directive itself or by a `<`/`>` modifier, `None` for single bytes
+    order: Option<DecodeOrder>,
+    count: Count,
+    position: usize,
+}
+
+#[derive(Debug, Clone)]
+/// `Template` is a parsed Perl/Ruby `pack` template, like `NnA10H*`. It converts the template into a schema of
+/// unnamed fields and a list of `EncodeType` that produce the same bytes as Perl's `pack`, and back.
+///
+/// Supported directives: `c C s S l L q Q n N v V a A Z H h w x X @`, with counts, `*` and the `<`/`>` byte order
+/// modifiers on `s S l L q Q`. `s`, `l` and `q` use the byte order of the target like Perl. Integers are truncated
+/// to the width of their directive like Perl does. `X` and backward `@` are supported when packing only.
+///
+/// # Examples
+/// ```rust
+/// use packed_encoder::encoder::EncodeType;
+/// use packed_encoder::perlpack;
+///
+/// // pack("NnA4H*", 7, 2, "ab", "beef")
+/// let bytes = perlpack::pack("NnA4H*", &[
+///     EncodeType::Uint32(7),
+///     EncodeType::Uint16(2),
+///     EncodeType::Str("ab".to_owned()),
+///     EncodeType::Str("beef".to_owned()),
+/// ]).unwrap();
+/// assert_eq!(bytes, [0, 0, 0, 7, 0, 2, b'a', b'b', b' ', b' ', 0xbe, 0xef]);
+/// ```
+pub struct Template {
+    directives: Vec<Directive>,
+}
+
+/// packs the values according to the given template, like Perl's `pack`
+pub fn pack(template: &str, values: &[EncodeType]) -> Result<Vec<u8>, TemplateError> {
+    Template::parse(template)?.pack(values)
+}
+
+/// unpacks the buffer according to the given template, like Perl's `unpack`
+pub fn unpack(template: &str, buffer: &[u8]) -> Result<Vec<DecodedData>, TemplateError> {
+    Template::parse(template)?.unpack(buffer)
+}
+
+fn native_order() -> DecodeOrder {
+    if cfg!(target_endian = "big") {
+        DecodeOrder::Big
+    } else {
+        DecodeOrder::Little
+    }
+}
+
+/// returns the size, signedness and default byte order of an integer directive
+fn integer_directive(code: char) -> Option<(usize, bool, Option<DecodeOrder>)> {
+    Some(match code {
+        'c' => (1, true, None),
+        'C' => (1, false, None),
+        's' => (2, true, Some(native_order())),
+        'S' => (2, false, Some(native_order())),
+        'l' => (4, true, Some(native_order())),
+        'L' => (4, false, Some(native_order())),
+        'q' => (8, true, Some(native_order())),
+        'Q' => (8, false, Some(native_order())),
+        'n' => (2, false, Some(DecodeOrder::Big)),
+        'N' => (4, false, Some(DecodeOrder::Big)),
+        'v' => (2, false, Some(DecodeOrder::Little)),
+        'V' => (4, false, Some(DecodeOrder::Little)),
+        _ => return None,
+    })
+}
+
+fn integer_type(size: usize, signed: bool) -> DecodeType {
+    match (size, signed) {
+        (1, true) => DecodeType::Int8,
+        (2, true) => DecodeType::Int16,
+        (4, true) => DecodeType::Int32,
+        (8, true) => DecodeType::Int64,
+        (1, false) => DecodeType::Uint8,
+        (2, false) => DecodeType::Uint16,
+        (4, false) => DecodeType::Uint32,
+        _ => DecodeType::Uint64,
+    }
+}
+
+impl Template {
+    /// parses a template, whitespace and `#` comments are ignored like in Perl
+    pub fn parse(template: &str) -> Result<Template, TemplateError> {
+        let chars: Vec<char> = template.chars().collect();
+        let mut directives = vec![];
+        let mut pos = 0;
+
+        while pos < chars.len() {
+            let code = chars[pos];
+            if code.is_whitespace() {
+                pos += 1;
+                continue;
+            }
+            if code == '#' {
+                while pos < chars.len() && chars[pos] != '\n' {
+                    pos += 1;
+                }
+                continue;
+            }
+
+            let position = pos;
+            let mut order = match code {
+                'a' | 'A' | 'Z' | 'H' | 'h' | 'w' | 'x' | 'X' | '@' => None,
+                code => match integer_directive(code) {
+                    Some((_, _, order)) => order,
+                    None => return Err(TemplateError::Unsupported(code, position)),
+                },
+            };
+            pos += 1;
+
+            while let Some(modifier) = chars.get(pos) {
+                match modifier {
+                    '<' | '>' if matches!(code, 's' | 'S' | 'l' | 'L' | 'q' | 'Q') => {
+                        order = Some(if *modifier == '<' {
+                            DecodeOrder::Little
+                        } else {
+                            DecodeOrder::Big
+                        });
+                    }
+                    '<' | '>' | '!' | '_' => {
+                        return Err(TemplateError::Unsupported(*modifier, pos))
+                    }
+                    _ => break,
+                }
+                pos += 1;
+            }
+
+            let count = if chars.get(pos) == Some(&'*') {
+                pos += 1;
+                Count::Star
+            } else if chars.get(pos) == Some(&'[') {
+                return Err(TemplateError::Unsupported('[', pos));
+            } else {
+                let digits = chars[pos..]
+                    .iter()
+                    .take_while(|c| c.is_ascii_digit())
+                    .count();
+                let count = if digits == 0 {
+                    1
+                } else {
+                    chars[pos..pos + digits]
+                        .iter()
+                        .collect::<String>()
+                        .parse()
+                        .map_err(|_| TemplateError::InvalidTemplate(pos))?
+                };
+                pos += digits;
+                Count::Number(count)
+            };
+
+            directives.push(Directive {
+                code,
+                order,
+                count,
+                position,
+            });
+        }
+
+        Ok(Template { directives })
+    }
+
+    /// converts the values into the elements that encode the template, along with the schema holding the byte order
+    /// of every element. `schema.encode(&elements, ...)` produces the packed bytes.
+    pub fn encode_types(
+        &self,
+        values: &[EncodeType],
+    ) -> Result<(Schema, Vec<EncodeType>), TemplateError> {
+        let mut packer = Packer {
+            schema: Schema::new(),
+            elements: vec![],
+            sizes: vec![],
+        };
+        let mut values = values.iter().enumerate().peekable();
+
+        for directive in &self.directives {
+            let position = directive.position;
+            match directive.code {
+                'x' | 'X' | '@' => {
+                    let count = match directive.count {
+                        Count::Number(count) => count,
+                        Count::Star => 0,
+                    };
+                    let size = packer.size();
+                    match directive.code {
+                        'x' => packer.push(
+                            EncodeType::Reserved(count),
+                            DecodeType::Reserved(count),
+                            None,
+                            count,
+                        ),
+                        '@' if count >= size => {
+                            let padding = count - size;
+                            packer.push(
+                                EncodeType::Reserved(padding),
+                                DecodeType::Reserved(padding),
+                                None,
+                                padding,
+                            )
+                        }
+                        '@' => packer.truncate(size - count)?,
+                        _ if count > size => return Err(TemplateError::Backward(position)),
+                        _ => packer.truncate(count)?,
+                    }
+                }
+                'a' | 'A' | 'Z' | 'H' | 'h' => {
+                    let (index, value) =
+                        values.next().ok_or(TemplateError::MissingValue(position))?;
+                    let content = string_value(value).ok_or(TemplateError::Value(index))?;
+                    let content = match directive.code {
+                        'H' | 'h' => pack_hex(content, directive.code == 'H', directive.count)
+                            .ok_or(TemplateError::Value(index))?,
+                        code => pack_string(content, code, directive.count),
+                    };
+                    let size = content.len();
+                    packer.push(
+                        EncodeType::Bytes(content),
+                        DecodeType::Bytes(size),
+                        None,
+                        size,
+                    );
+                }
+                code => {
+                    let count = match directive.count {
+                        Count::Number(count) => count,
+                        Count::Star => values.len(),
+                    };
+                    for _ in 0..count {
+                        let (index, value) =
+                            values.next().ok_or(TemplateError::MissingValue(position))?;
+                        let value = integer_value(value).ok_or(TemplateError::Value(index))?;
+                        if code == 'w' {
+                            let content = pack_ber(value).ok_or(TemplateError::Value(index))?;
+                            let size = content.len();
+                            packer.push(
+                                EncodeType::Bytes(content),
+                                DecodeType::Bytes(size),
+                                None,
+                                size,
+                            );
+                            continue;
+                        }
+
+                        let (size, signed, _) = integer_directive(code).unwrap_or((1, false, None));
+                        // like Perl, values are truncated to the width of the directive
+                        let element = match integer_type(size, signed) {
+                            DecodeType::Int8 => EncodeType::Int8(value as i8),
+                            DecodeType::Int16 => EncodeType::Int16(value as i16),
+                            DecodeType::Int32 => EncodeType::Int32(value as i32),
+                            DecodeType::Int64 => EncodeType::Int64(value as i64),
+                            DecodeType::Uint8 => EncodeType::Uint8(value as u8),
+                            DecodeType::Uint16 => EncodeType::Uint16(value as u16),
+                            DecodeType::Uint32 => EncodeType::Uint32(value as u32),
+                            _ => EncodeType::Uint64(value as u64),
+                        };
+                        packer.push(
+                            element,
+                            integer_type(size, signed),
+                            directive.order.clone(),
+                            size,
+                        );
+                    }
+                }
+            }
+        }
+
+        match values.len() {
+            0 => Ok((packer.schema, packer.elements)),
+            left => Err(TemplateError::ExtraValues(left)),
+        }
+    }
+
+    /// packs the values, like Perl's `pack`
+    pub fn pack(&self, values: &[EncodeType]) -> Result<Vec<u8>, TemplateError> {
+        let (schema, elements) = self.encode_types(values)?;
+        schema
+            .encode(&elements, EncodeOrder::from(native_order()))
+            .map_err(TemplateError::Encode)
+    }
+
+    /// returns the schema that decodes the given buffer. The buffer is needed to resolve `*` counts, `Z*` strings
+    /// and `w` integers, whose size depends on the content. `a`, `A`, `Z`, `H`, `h` and `w` are decoded as
+    /// byte-arrays, `unpack` converts them like Perl does.
+    pub fn schema(&self, buffer: &[u8]) -> Result<Schema, TemplateError> {
+        Ok(self.layout(buffer)?.0)
+    }
+
+    /// returns the schema that decodes the buffer along with the directive that produced every field
+    fn layout<'a>(&'a self, buffer: &[u8]) -> Result<(Schema, Vec<&'a Directive>), TemplateError> {
+        let mut schema = Schema::new();
+        let mut sources = vec![];
+        let mut offset = 0;
+
+        for directive in &self.directives {
+            let position = directive.position;
+            let start = offset;
+            let remaining = buffer.len() - start;
+            let mut push = |kind: DecodeType, order: Option<DecodeOrder>, size: usize| {
+                if size > buffer.len() - offset {
+                    return Err(TemplateError::OutOfBounds(position));
+                }
+                schema.fields.push(Field {
+                    name: None,
+                    kind,
+                    order,
+                    default: None,
+                });
+                sources.push(directive);
+                offset += size;
+                Ok(())
+            };
+
+            match (directive.code, directive.count) {
+                ('X', _) => return Err(TemplateError::Backward(position)),
+                ('x' | '@', Count::Star) => {}
+                ('@', Count::Number(target)) => {
+                    let padding = target
+                        .checked_sub(start)
+                        .ok_or(TemplateError::Backward(position))?;
+                    push(DecodeType::Padding(padding), None, padding)?
+                }
+                ('x', Count::Number(count)) => push(DecodeType::Padding(count), None, count)?,
+                // like Perl, strings and hex digits are truncated at the end of the buffer
+                ('a' | 'A' | 'Z', Count::Number(size)) => {
+                    let size = size.min(remaining);
+                    push(DecodeType::Bytes(size), None, size)?
+                }
+                ('Z', Count::Star) => {
+                    // the string ends at the first null byte, which is consumed along with it
+                    let size = buffer[start..]
+                        .iter()
+                        .position(|byte| *byte == 0)
+                        .map_or(remaining, |end| end + 1);
+                    push(DecodeType::Bytes(size), None, size)?
+                }
+                ('a' | 'A' | 'H' | 'h', Count::Star) => {
+                    push(DecodeType::Bytes(remaining), None, remaining)?
+                }
+                ('H' | 'h', Count::Number(digits)) => {
+                    let size = digits.div_ceil(2).min(remaining);
+                    push(DecodeType::Bytes(size), None, size)?
+                }
+                ('w', count) => {
+                    let items = match count {
+                        Count::Number(items) => items,
+                        Count::Star => usize::MAX,
+                    };
+                    let mut cursor = start;
+                    for _ in 0..items {
+                        if count == Count::Star && cursor == buffer.len() {
+                            break;
+                        }
+                        let size = buffer[cursor..]
+                            .iter()
+                            .position(|byte| byte & 0x80 == 0)
+                            .ok_or(TemplateError::OutOfBounds(position))?
+                            + 1;
+                        push(DecodeType::Bytes(size), None, size)?;
+                        cursor += size;
+                    }
+                }
+                (code, count) => {
+                    let (size, signed, _) = integer_directive(code).unwrap_or((1, false, None));
+                    let items = match count {
+                        Count::Number(items) => items,
+                        Count::Star => remaining / size,
+                    };
+                    for _ in 0..items {
+                        push(integer_type(size, signed), directive.order.clone(), size)?;
+                    }
+                }
+            }
+        }
+        Ok((schema, sources))
+    }
+
+    /// unpacks the buffer, like Perl's `unpack`. Integers are returned as integers of the width of their
+    /// directive, `a`, `A` and `Z` as `Bytes`, `H` and `h` as `Str` and `w` as `Uint64`.
+    pub fn unpack(&self, buffer: &[u8]) -> Result<Vec<DecodedData>, TemplateError> {
+        let (schema, sources) = self.layout(buffer)?;
+        let values = schema
+            .decode(buffer, native_order())
+            .map_err(TemplateError::Decode)?
+            .into_values();
+
+        // padding fields produce no value, the other fields produce one value each
+        let sources = schema
+            .fields
+            .iter()
+            .zip(sources)
+            .filter(|(field, _)| !matches!(field.kind, DecodeType::Padding(_)))
+            .map(|(_, directive)| directive);
+
+        let mut unpacked = vec![];
+        for (value, directive) in values.into_iter().zip(sources) {
+            unpacked.push(match (directive.code, value) {
+                ('A', DecodedData::Bytes(mut content)) => {
+                    while content
+                        .last()
+                        .is_some_and(|byte| *byte == 0 || byte.is_ascii_whitespace())
+                    {
+                        content.pop();
+                    }
+                    DecodedData::Bytes(content)
+                }
+                ('Z', DecodedData::Bytes(mut content)) => {
+                    if let Some(end) = content.iter().position(|byte| *byte == 0) {
+                        content.truncate(end);
+                    }
+                    DecodedData::Bytes(content)
+                }
+                ('H' | 'h', DecodedData::Bytes(content)) => {
+                    let digits = match directive.count {
+                        Count::Number(digits) => digits,
+                        Count::Star => content.len() * 2,
+                    };
+                    DecodedData::Str(unpack_hex(&content, directive.code == 'H', digits))
+                }
+                ('w', DecodedData::Bytes(content)) => DecodedData::Uint64(
+                    unpack_ber(&content).ok_or(TemplateError::Value(unpacked.len()))?,
+                ),
+                (_, value) => value,
+            });
+        }
+        Ok(unpacked)
+    }
+}
+
+/// `Packer` collects the elements of a template being packed along with their size, so that `X` and `@` can
+/// remove bytes from the end
+struct Packer {
+    schema: Schema,
+    elements: Vec<EncodeType>,
+    sizes: Vec<usize>,
+}
+
+impl Packer {
+    fn size(&self) -> usize {
+        self.sizes.iter().sum()
+    }
+
+    fn push(
+        &mut self,
+        element: EncodeType,
+        kind: DecodeType,
+        order: Option<DecodeOrder>,
+        size: usize,
+    ) {
+        self.schema.fields.push(Field {
+            name: None,
+            kind,
+            order,
+            default: None,
+        });
+        self.elements.push(element);
+        self.sizes.push(size);
+    }
+
+    /// removes `count` bytes from the end, an element that is partially removed is replaced by its remaining bytes
+    fn truncate(&mut self, mut count: usize) -> Result<(), TemplateError> {
+        while count > 0 {
+            let (Some(element), Some(size), Some(field)) = (
+                self.elements.pop(),
+                self.sizes.pop(),
+                self.schema.fields.pop(),
+            ) else {
+                break;
+            };
+            if size <= count {
+                count -= size;
+                continue;
+            }
+
+            let order = field.order.unwrap_or_else(native_order);
+            let mut content = encoder::encode_packed(&[element], EncodeOrder::from(order))
+                .map_err(TemplateError::Encode)?;
+            content.truncate(size - count);
+            let size = content.len();
+            self.push(
+                EncodeType::Bytes(content),
+                DecodeType::Bytes(size),
+                None,
+                size,
+            );
+            count = 0;
+        }
+        Ok(())
+    }
+}
+
+/// returns the value of an integer or boolean element
+fn integer_value(value: &EncodeType) -> Option<i128> {
+    match value {
+        EncodeType::Int8(value) => Some(*value as i128),
+        EncodeType::Int16(value) => Some(*value as i128),
+        EncodeType::Int32(value) => Some(*value as i128),
+        EncodeType::Int64(value) => Some(*value as i128),
+        EncodeType::Int128(value) => Some(*value),
+        EncodeType::Uint8(value) => Some(*value as i128),
+        EncodeType::Uint16(value) => Some(*value as i128),
+        EncodeType::Uint32(value) => Some(*value as i128),
+        EncodeType::Uint64(value) => Some(*value as i128),
+        EncodeType::Uint128(value) => i128::try_from(*value).ok(),
+        EncodeType::Bool(value) => Some(*value as i128),
+        _ => None,
+    }
+}
+
+fn string_value(value: &EncodeType) -> Option<&[u8]> {
+    match value {
+        EncodeType::Bytes(value) => Some(value),
+        EncodeType::Str(value) => Some(value.as_bytes()),
+        _ => None,
+    }
+}
+
+/// pads or truncates a string: `a` pads with nulls, `A` with spaces, `Z` always ends with a null
+fn pack_string(content: &[u8], code: char, count: Count) -> Vec<u8> {
+    let mut content = content.to_vec();
+    match (code, count) {
+        ('Z', Count::Star) => content.push(0),
+        (_, Count::Star) => {}
+        ('Z', Count::Number(size)) => {
+            content.truncate(size.saturating_sub(1));
+            content.resize(size, 0);
+        }
+        ('A', Count::Number(size)) => {
+            content.truncate(size);
+            content.resize(size, b' ');
+        }
+        (_, Count::Number(size)) => {
+            content.truncate(size);
+            content.resize(size, 0);
+        }
+    }
+    content
+}
+
+/// packs hexadecimal digits, `H` places the first digit in the high nybble and `h` in the low nybble
+fn pack_hex(content: &[u8], high_first: bool, count: Count) -> Option<Vec<u8>> {
+    let digits = match count {
+        Count::Number(digits) => digits,
+        Count::Star => content.len(),
+    };
+    let mut packed = vec![0; digits.div_ceil(2)];
+    for (index, digit) in content.iter().take(digits).enumerate() {
+        let nybble = (*digit as char).to_digit(16)? as u8;
+        let shift = if (index % 2 == 0) == high_first { 4 } else { 0 };
+        packed[index / 2] |= nybble << shift;
+    }
+    Some(packed)
+}
+
+fn unpack_hex(content: &[u8], high_first: bool, digits: usize) -> String {
+    (0..digits.min(content.len() * 2))
+        .map(|index| {
+            let shift = if (index % 2 == 0) == high_first { 4 } else { 0 };
+            let nybble = (content[index / 2] >> shift) & 0x0f;
+            char::from_digit(nybble as u32, 16).unwrap_or('0')
+        })
+        .collect()
+}
+
+/// packs an unsigned integer as a BER compressed integer: base 128, most significant group first, the high bit is
+/// set on every byte but the last
+fn pack_ber(value: i128) -> Option<Vec<u8>> {
+    let mut value = u128::try_from(value).ok()?;
+    let mut packed = vec![(value & 0x7f) as u8];
+    value >>= 7;
+    while value > 0 {
+        packed.insert(0, (value & 0x7f) as u8 | 0x80);
+        value >>= 7;
+    }
+    Some(packed)
+}
+
+fn unpack_ber(content: &[u8]) -> Option<u64> {
+    content.iter().try_fold(0u64, |value, byte| {
+        value
+            .checked_mul(128)
+            .map(|value| value | (byte & 0x7f) as u64)
+    })
+}
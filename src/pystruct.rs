@@ -0,0 +1,530 @@
+use crate::decoder::{self, DecodeError, DecodeOrder, DecodeType, DecodedData};
+use crate::encoder::{self, EncodeError, EncodeOrder, EncodeType};
+use std::ffi::{c_double, c_float, c_int, c_long, c_longlong, c_short};
+use std::fmt;
+use std::mem::{align_of, size_of};
+
+#[derive(Debug, Clone)]
+/// `FormatError` is returned when a Python `struct` format string cannot be parsed or when the values do not
+/// match it.
+pub enum FormatError {
+    /// InvalidFormat occurs when the format string cannot be parsed or describes more than `isize::MAX` bytes,
+    /// contains the position of the offending character
+    InvalidFormat(usize),
+    /// ValueCount occurs when the number of values does not match the number of values required by the format
+    ValueCount {
+        /// number of values required by the format
+        expected: usize,
+        /// number of values given
+        found: usize,
+    },
+    /// Value occurs when the value at the given index has the wrong type or does not fit in its format code
+    Value(usize),
+    /// BufferSize occurs when the buffer to unpack does not have the size of the format, like `struct.error`
+    BufferSize {
+        /// size of the format
+        expected: usize,
+        /// size of the buffer
+        found: usize,
+    },
+    /// Encode wraps the error returned by the encoder
+    Encode(EncodeError),
+    /// Decode wraps the error returned by the decoder
+    Decode(DecodeError),
+}
+
+impl fmt::Display for FormatError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FormatError::InvalidFormat(position) => {
+                write!(f, "bad char in struct format at position {}", position)
+            }
+            FormatError::ValueCount { expected, found } => {
+                write!(f, "format requires {} values, got {}", expected, found)
+            }
+            FormatError::Value(index) => {
+                write!(f, "value {} does not match its format code", index)
+            }
+            FormatError::BufferSize { expected, found } => {
+                write!(
+                    f,
+                    "unpack requires a buffer of {} bytes, got {}",
+                    expected, found
+                )
+            }
+            FormatError::Encode(err) => write!(f, "encode error: {:?}", err),
+            FormatError::Decode(err) => write!(f, "decode error: {:?}", err),
+        }
+    }
+}
+
+impl std::error::Error for FormatError {}
+
+#[derive(Debug, Clone)]
+/// `StructFormat` is a parsed Python `struct` format string, like `<iHq12s`. It maps the format onto lists of
+/// `EncodeType` and `DecodeType` that produce the same bytes as `struct.pack` and `struct.unpack`.
+///
+/// Supported codes: `x c b B ? h H i I l L q Q n N e f d s p P`, with repeat counts and the byte order prefixes
+/// `@ = < > !`. The native mode (`@` or no prefix) uses the sizes and the alignment of the C types of the target,
+/// like CPython, the other modes use the standard sizes and no alignment.
+///
+/// # Examples
+/// ```rust
+/// use packed_encoder::encoder::EncodeType;
+/// use packed_encoder::pystruct;
+///
+/// // struct.pack('<hI4s', -2, 7, b'ab')
+/// let bytes = pystruct::pack("<hI4s", &[
+///     EncodeType::Int16(-2),
+///     EncodeType::Uint32(7),
+///     EncodeType::Bytes(b"ab".to_vec()),
+/// ]).unwrap();
+/// assert_eq!(bytes, [0xfe, 0xff, 7, 0, 0, 0, b'a', b'b', 0, 0]);
+/// ```
+pub struct StructFormat {
+    native: bool,
+    order: DecodeOrder,
+    items: Vec<(char, usize)>,
+}
+
+/// returns the number of bytes occupied by the given format, like `struct.calcsize`
+pub fn calcsize(format: &str) -> Result<usize, FormatError> {
+    Ok(StructFormat::parse(format)?.size())
+}
+
+/// packs the values according to the given format, like `struct.pack`
+pub fn pack(format: &str, values: &[EncodeType]) -> Result<Vec<u8>, FormatError> {
+    StructFormat::parse(format)?.pack(values)
+}
+
+/// unpacks the buffer according to the given format, like `struct.unpack`
+pub fn unpack(format: &str, buffer: &[u8]) -> Result<Vec<DecodedData>, FormatError> {
+    StructFormat::parse(format)?.unpack(buffer)
+}
+
+impl StructFormat {
+    /// parses a format string
+    pub fn parse(format: &str) -> Result<StructFormat, FormatError> {
+        let chars: Vec<char> = format.chars().collect();
+        let native_order = if cfg!(target_endian = "big") {
+            DecodeOrder::Big
+        } else {
+            DecodeOrder::Little
+        };
+        let (native, order, mut pos) = match chars.first() {
+            Some('@') => (true, native_order, 1),
+            Some('=') => (false, native_order, 1),
+            Some('<') => (false, DecodeOrder::Little, 1),
+            Some('>') | Some('!') => (false, DecodeOrder::Big, 1),
+            _ => (true, native_order, 0),
+        };
+
+        let mut items = vec![];
+        let mut positions = vec![];
+        while pos < chars.len() {
+            if chars[pos].is_whitespace() {
+                pos += 1;
+                continue;
+            }
+
+            let digits = chars[pos..]
+                .iter()
+                .take_while(|c| c.is_ascii_digit())
+                .count();
+            let count = if digits == 0 {
+                1
+            } else {
+                chars[pos..pos + digits]
+                    .iter()
+                    .collect::<String>()
+                    .parse()
+                    .map_err(|_| FormatError::InvalidFormat(pos))?
+            };
+            pos += digits;
+
+            // the count must be immediately followed by the code
+            let code = *chars.get(pos).ok_or(FormatError::InvalidFormat(pos))?;
+            let valid = match code {
+                'x' | 'c' | 'b' | 'B' | '?' | 'h' | 'H' | 'i' | 'I' | 'l' | 'L' | 'q' | 'Q'
+                | 'e' | 'f' | 'd' | 's' | 'p' => true,
+                'n' | 'N' | 'P' => native,
+                _ => false,
+            };
+            if !valid {
+                return Err(FormatError::InvalidFormat(pos));
+            }
+            items.push((code, count));
+            positions.push(pos);
+            pos += 1;
+        }
+
+        let format = StructFormat {
+            native,
+            order,
+            items,
+        };
+        // like CPython, formats larger than `isize::MAX` bytes are rejected, the layout is computed without checks
+        // afterwards
+        let mut offset: usize = 0;
+        for ((code, count), position) in format.items.iter().zip(positions) {
+            let (size, align) = format.code_layout(*code);
+            let bytes = match code {
+                's' | 'p' | 'x' => Some(*count),
+                _ => size.checked_mul(*count),
+            };
+            offset = offset
+                .checked_next_multiple_of(align)
+                .zip(bytes)
+                .and_then(|(offset, bytes)| offset.checked_add(bytes))
+                .filter(|offset| *offset <= isize::MAX as usize)
+                .ok_or(FormatError::InvalidFormat(position))?;
+        }
+        Ok(format)
+    }
+
+    /// returns the byte order used by the format, the native modes use the byte order of the target
+    pub fn decode_order(&self) -> DecodeOrder {
+        self.order.clone()
+    }
+
+    /// returns the byte order used by the format, see `decode_order`
+    pub fn encode_order(&self) -> EncodeOrder {
+        EncodeOrder::from(self.order.clone())
+    }
+
+    /// returns the size and the alignment of a format code
+    fn code_layout(&self, code: char) -> (usize, usize) {
+        macro_rules! native {
+            ($type:ty) => {
+                (size_of::<$type>(), align_of::<$type>())
+            };
+        }
+
+        if !self.native {
+            let size = match code {
+                'h' | 'H' | 'e' => 2,
+                'i' | 'I' | 'l' | 'L' | 'f' => 4,
+                'q' | 'Q' | 'd' => 8,
+                _ => 1,
+            };
+            return (size, 1);
+        }
+
+        match code {
+            'h' | 'H' => native!(c_short),
+            'i' | 'I' => native!(c_int),
+            'l' | 'L' => native!(c_long),
+            'q' | 'Q' => native!(c_longlong),
+            'n' | 'N' => native!(usize),
+            'P' => native!(*const u8),
+            'e' => native!(c_short),
+            'f' => native!(c_float),
+            'd' => native!(c_double),
+            _ => (1, 1),
+        }
+    }
+
+    /// iterates over the items of the format along with the padding placed before each of them to align it
+    fn layout(&self) -> impl Iterator<Item = (usize, char, usize, usize)> + '_ {
+        let mut offset = 0;
+        self.items.iter().map(move |(code, count)| {
+            let (size, align) = self.code_layout(*code);
+            let padding = (align - offset % align) % align;
+            offset += padding;
+            offset += match code {
+                's' | 'p' | 'x' => *count,
+                _ => size * count,
+            };
+            (padding, *code, *count, size)
+        })
+    }
+
+    /// returns the number of bytes occupied by the format, like `struct.calcsize`
+    pub fn size(&self) -> usize {
+        self.layout()
+            .map(|(padding, code, count, size)| match code {
+                's' | 'p' | 'x' => padding + count,
+                _ => padding + size * count,
+            })
+            .sum()
+    }
+
+    /// returns the number of values taken by `pack` and returned by `unpack`
+    pub fn value_count(&self) -> usize {
+        self.items
+            .iter()
+            .map(|(code, count)| match code {
+                'x' => 0,
+                's' | 'p' => 1,
+                _ => *count,
+            })
+            .sum()
+    }
+
+    /// returns the types that decode the format. Padding bytes are skipped, `s` and `p` are decoded as byte-arrays
+    /// and `e` as the raw `Uint16` bits of the half-precision number, `unpack` converts them like Python does.
+    pub fn decode_types(&self) -> Vec<DecodeType> {
+        let mut types = vec![];
+        for (padding, code, count, size) in self.layout() {
+            if padding > 0 {
+                types.push(DecodeType::Padding(padding));
+            }
+            let kind = match code {
+                'x' => DecodeType::Padding(count),
+                's' | 'p' => DecodeType::Bytes(count),
+                'c' => DecodeType::Bytes(1),
+                '?' => DecodeType::Bool,
+                'e' => DecodeType::Uint16,
+                'f' => DecodeType::Float32,
+                'd' => DecodeType::Float64,
+                code => integer_type(size, is_signed(code)),
+            };
+            match code {
+                'x' | 's' | 'p' => types.push(kind),
+                _ => types.extend(std::iter::repeat_n(kind, count)),
+            }
+        }
+        types
+    }
+
+    /// converts the values into the elements that encode the format. Integer values are converted into the width of
+    /// their format code, `s` values are padded or truncated and `p` values are prefixed with their length.
+    pub fn encode_types(&self, values: &[EncodeType]) -> Result<Vec<EncodeType>, FormatError> {
+        let expected = self.value_count();
+        if values.len() != expected {
+            return Err(FormatError::ValueCount {
+                expected,
+                found: values.len(),
+            });
+        }
+
+        let mut elements = vec![];
+        let mut index = 0;
+        for (padding, code, count, size) in self.layout() {
+            if padding > 0 {
+                elements.push(EncodeType::Reserved(padding));
+            }
+            match code {
+                'x' => elements.push(EncodeType::Reserved(count)),
+                's' | 'p' => {
+                    let mut content = byte_value(&values[index])
+                        .ok_or(FormatError::Value(index))?
+                        .to_vec();
+                    if code == 'p' && count > 0 {
+                        // the length byte is followed by at most `count - 1` bytes
+                        content.truncate(count - 1);
+                        content.insert(0, content.len().min(255) as u8);
+                    }
+                    content.resize(count, 0);
+                    elements.push(EncodeType::Bytes(content));
+                    index += 1;
+                }
+                _ => {
+                    for value in &values[index..index + count] {
+                        elements.push(
+                            encode_value(code, size, value).ok_or(FormatError::Value(index))?,
+                        );
+                        index += 1;
+                    }
+                }
+            }
+        }
+        Ok(elements)
+    }
+
+    /// packs the values, like `struct.pack`
+    pub fn pack(&self, values: &[EncodeType]) -> Result<Vec<u8>, FormatError> {
+        let elements = self.encode_types(values)?;
+        encoder::encode_packed(&elements, self.encode_order()).map_err(FormatError::Encode)
+    }
+
+    /// unpacks the buffer, like `struct.unpack` the buffer must have the size of the format. `s`, `p` and `c` are
+    /// returned as `Bytes`, `?` as `Bool`, `e` and `f` as `Float32` and `d` as `Float64`.
+    pub fn unpack(&self, buffer: &[u8]) -> Result<Vec<DecodedData>, FormatError> {
+        let expected = self.size();
+        if buffer.len() != expected {
+            return Err(FormatError::BufferSize {
+                expected,
+                found: buffer.len(),
+            });
+        }
+
+        let decoded = decoder::decode_packed(&self.decode_types(), buffer, self.decode_order())
+            .map_err(FormatError::Decode)?;
+
+        let mut values = decoded.into_iter();
+        let mut unpacked = vec![];
+        for (code, count) in &self.items {
+            let taken = match code {
+                'x' => 0,
+                's' | 'p' => 1,
+                _ => *count,
+            };
+            for value in values.by_ref().take(taken) {
+                unpacked.push(match (code, value) {
+                    ('p', DecodedData::Bytes(content)) if !content.is_empty() => {
+                        let size = (content[0] as usize).min(content.len() - 1);
+                        DecodedData::Bytes(content[1..1 + size].to_vec())
+                    }
+                    ('e', DecodedData::Uint16(bits)) => DecodedData::Float32(f16_to_f32(bits)),
+                    (_, value) => value,
+                });
+            }
+        }
+        Ok(unpacked)
+    }
+}
+
+fn is_signed(code: char) -> bool {
+    matches!(code, 'b' | 'h' | 'i' | 'l' | 'q' | 'n')
+}
+
+fn integer_type(size: usize, signed: bool) -> DecodeType {
+    match (size, signed) {
+        (1, true) => DecodeType::Int8,
+        (2, true) => DecodeType::Int16,
+        (4, true) => DecodeType::Int32,
+        (8, true) => DecodeType::Int64,
+        (_, true) => DecodeType::Int128,
+        (1, false) => DecodeType::Uint8,
+        (2, false) => DecodeType::Uint16,
+        (4, false) => DecodeType::Uint32,
+        (8, false) => DecodeType::Uint64,
+        (_, false) => DecodeType::Uint128,
+    }
+}
+
+/// returns the value of an integer or boolean element, Python accepts booleans wherever integers are expected
+fn integer_value(value: &EncodeType) -> Option<i128> {
+    match value {
+        EncodeType::Int8(value) => Some(*value as i128),
+        EncodeType::Int16(value) => Some(*value as i128),
+        EncodeType::Int32(value) => Some(*value as i128),
+        EncodeType::Int64(value) => Some(*value as i128),
+        EncodeType::Int128(value) => Some(*value),
+        EncodeType::Uint8(value) => Some(*value as i128),
+        EncodeType::Uint16(value) => Some(*value as i128),
+        EncodeType::Uint32(value) => Some(*value as i128),
+        EncodeType::Uint64(value) => Some(*value as i128),
+        EncodeType::Uint128(value) => i128::try_from(*value).ok(),
+        EncodeType::Bool(value) => Some(*value as i128),
+        _ => None,
+    }
+}
+
+fn float_value(value: &EncodeType) -> Option<f64> {
+    match value {
+        EncodeType::Float32(value) => Some(*value as f64),
+        EncodeType::Float64(value) => Some(*value),
+        other => integer_value(other).map(|value| value as f64),
+    }
+}
+
+fn byte_value(value: &EncodeType) -> Option<&[u8]> {
+    match value {
+        EncodeType::Bytes(value) => Some(value),
+        EncodeType::Str(value) => Some(value.as_bytes()),
+        _ => None,
+    }
+}
+
+/// converts a value into the element of a single-value format code, returns `None` when the value does not fit
+fn encode_value(code: char, size: usize, value: &EncodeType) -> Option<EncodeType> {
+    match code {
+        'c' => match byte_value(value)? {
+            [byte] => Some(EncodeType::Bytes(vec![*byte])),
+            _ => None,
+        },
+        '?' => match value {
+            EncodeType::Float32(value) => Some(EncodeType::Bool(*value != 0.0)),
+            EncodeType::Float64(value) => Some(EncodeType::Bool(*value != 0.0)),
+            other => integer_value(other).map(|value| EncodeType::Bool(value != 0)),
+        },
+        'e' => f64_to_f16(float_value(value)?).map(EncodeType::Uint16),
+        'f' => {
+            let value = float_value(value)?;
+            let narrowed = value as f32;
+            // like CPython, finite values that do not fit are rejected instead of becoming infinite
+            (narrowed.is_finite() || !value.is_finite()).then_some(EncodeType::Float32(narrowed))
+        }
+        'd' => float_value(value).map(EncodeType::Float64),
+        code => {
+            let value = integer_value(value)?;
+            Some(match integer_type(size, is_signed(code)) {
+                DecodeType::Int8 => EncodeType::Int8(i8::try_from(value).ok()?),
+                DecodeType::Int16 => EncodeType::Int16(i16::try_from(value).ok()?),
+                DecodeType::Int32 => EncodeType::Int32(i32::try_from(value).ok()?),
+                DecodeType::Int64 => EncodeType::Int64(i64::try_from(value).ok()?),
+                DecodeType::Uint8 => EncodeType::Uint8(u8::try_from(value).ok()?),
+                DecodeType::Uint16 => EncodeType::Uint16(u16::try_from(value).ok()?),
+                DecodeType::Uint32 => EncodeType::Uint32(u32::try_from(value).ok()?),
+                DecodeType::Uint64 => EncodeType::Uint64(u64::try_from(value).ok()?),
+                _ => return None,
+            })
+        }
+    }
+}
+
+/// converts a number into the bits of a half-precision number, rounding half to even like CPython's
+/// `PyFloat_Pack2`. Returns `None` when the value is too large.
+fn f64_to_f16(value: f64) -> Option<u16> {
+    let sign = if value.is_sign_negative() { 0x8000 } else { 0 };
+    if value.is_nan() {
+        return Some(sign | 0x7e00);
+    }
+    if value.is_infinite() {
+        return Some(sign | 0x7c00);
+    }
+
+    let bits = value.abs().to_bits();
+    let biased = ((bits >> 52) & 0x7ff) as i32;
+    if biased == 0 {
+        // zero and subnormal doubles are far below the smallest half-precision number
+        return Some(sign);
+    }
+
+    // value = mantissa * 2^exponent with 1 <= mantissa < 2
+    let mut exponent = biased - 1023;
+    let mut mantissa = f64::from_bits((bits & 0x000f_ffff_ffff_ffff) | (1023 << 52));
+    if exponent >= 16 {
+        return None;
+    } else if exponent < -25 {
+        mantissa = 0.0;
+        exponent = 0;
+    } else if exponent < -14 {
+        // gradual underflow
+        mantissa *= 2f64.powi(14 + exponent);
+        exponent = 0;
+    } else {
+        exponent += 15;
+        mantissa -= 1.0;
+    }
+
+    mantissa *= 1024.0;
+    let mut half = mantissa as u16;
+    let rest = mantissa - half as f64;
+    if rest > 0.5 || (rest == 0.5 && half % 2 == 1) {
+        half += 1;
+        if half == 1024 {
+            half = 0;
+            exponent += 1;
+            if exponent == 31 {
+                return None;
+            }
+        }
+    }
+    Some(sign | ((exponent as u16) << 10) | half)
+}
+
+/// converts the bits of a half-precision number into `f32`, the conversion is exact
+fn f16_to_f32(bits: u16) -> f32 {
+    let sign = if bits & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exponent = ((bits >> 10) & 0x1f) as i32;
+    let mantissa = (bits & 0x3ff) as f32;
+    sign * match exponent {
+        0 => mantissa * 2f32.powi(-24),
+        31 if mantissa == 0.0 => f32::INFINITY,
+        31 => f32::NAN,
+        _ => (1.0 + mantissa / 1024.0) * 2f32.powi(exponent - 15),
+    }
+}
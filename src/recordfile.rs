@@ -0,0 +1,488 @@
+//! `recordfile` stores packed records in a file that can be read sequentially or by record number.
+//!
+//! A record file is laid out as:
+//! - a header: the magic `PKRF`, the format version as `u16`, the byte order of the records (0 for big endian, 1 for
+//!   little endian), the length of the schema text as `u32` and the schema in the schema text language,
+//! - the records, each one prefixed by its length as `u32`,
+//! - the index: the offset of every record as `u64`,
+//! - a footer of `FOOTER_SIZE` bytes: the offset of the index and the number of records as `u64`, the CRC32 of the
+//!   index and of these two fields as `u32`, and the magic `PKIX`.
+//!
+//! The integers of the layout are little endian. The index and the footer are written when the writer is finished, a
+//! file left without them by a crash is made readable again by `recover`.
+
+use crate::checksum::ChecksumKind;
+use crate::decoder::{DecodeError, DecodeOrder};
+use crate::encoder::{EncodeError, EncodeType};
+use crate::schema::{DecodedRecord, Schema};
+use crate::text::{self, ParseError};
+use byteorder::{ByteOrder, LittleEndian, ReadBytesExt, WriteBytesExt};
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+/// `MAGIC` starts every record file.
+pub const MAGIC: &[u8; 4] = b"PKRF";
+/// `FOOTER_MAGIC` ends every complete record file.
+pub const FOOTER_MAGIC: &[u8; 4] = b"PKIX";
+/// `VERSION` is the version of the layout written by `RecordWriter`.
+pub const VERSION: u16 = 1;
+/// `FOOTER_SIZE` is the size of the footer at the end of the file.
+pub const FOOTER_SIZE: u64 = 24;
+
+#[derive(Debug)]
+/// `RecordFileError` is returned when a record file cannot be written or read.
+pub enum RecordFileError {
+    /// Io occurs when the underlying file fails
+    Io(io::Error),
+    /// BadMagic occurs when the file does not start with `MAGIC`
+    BadMagic,
+    /// Version occurs when the file was written with an unknown version of the layout
+    Version(u16),
+    /// BadOrder occurs when the byte order stored in the header is neither 0 nor 1
+    BadOrder(u8),
+    /// Schema occurs when the embedded schema cannot be parsed, or when the schema given to the writer cannot be
+    /// written in the schema text language
+    Schema(ParseError),
+    /// MissingIndex occurs when the file does not end with a valid footer, for example after a crash
+    MissingIndex,
+    /// Checksum occurs when the index does not match the checksum of the footer
+    Checksum,
+    /// Corrupt occurs when the record at the given offset runs past the end of the records
+    Corrupt(u64),
+    /// OutOfRange occurs when the record number is not smaller than the number of records
+    OutOfRange(usize),
+    /// TooLarge occurs when an encoded record does not fit in the `u32` length prefix
+    TooLarge(usize),
+    /// TrailingBytes occurs when the bytes given to `RecordWriter::write_encoded` hold more than one record, the given
+    /// number of bytes follow the record
+    TrailingBytes(usize),
+    /// Encode occurs when the values do not match the schema
+    Encode(EncodeError),
+    /// Decode occurs when a record does not match the schema
+    Decode(DecodeError),
+}
+
+impl fmt::Display for RecordFileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RecordFileError::Io(error) => write!(f, "{}", error),
+            RecordFileError::BadMagic => write!(f, "not a record file"),
+            RecordFileError::Version(version) => {
+                write!(f, "unsupported record file version {}", version)
+            }
+            RecordFileError::BadOrder(order) => write!(f, "invalid byte order {}", order),
+            RecordFileError::Schema(error) => write!(f, "invalid schema: {}", error),
+            RecordFileError::MissingIndex => {
+                write!(f, "record file has no index, it must be recovered")
+            }
+            RecordFileError::Checksum => write!(f, "record file index does not match its checksum"),
+            RecordFileError::Corrupt(offset) => write!(f, "corrupt record at offset {}", offset),
+            RecordFileError::OutOfRange(index) => write!(f, "record {} is out of range", index),
+            RecordFileError::TooLarge(size) => write!(f, "record of {} bytes is too large", size),
+            RecordFileError::TrailingBytes(size) => write!(f, "{} bytes follow the record", size),
+            RecordFileError::Encode(error) => write!(f, "cannot encode record: {:?}", error),
+            RecordFileError::Decode(error) => write!(f, "cannot decode record: {:?}", error),
+        }
+    }
+}
+
+impl std::error::Error for RecordFileError {}
+
+impl From<io::Error> for RecordFileError {
+    fn from(error: io::Error) -> RecordFileError {
+        RecordFileError::Io(error)
+    }
+}
+
+/// returns the header of a file holding records of the given schema
+fn header(schema: &Schema, order: &DecodeOrder) -> Result<Vec<u8>, RecordFileError> {
+    let schema_text = text::print(schema);
+    // the schema must be read back when the file is opened
+    text::parse_schema(&schema_text).map_err(RecordFileError::Schema)?;
+
+    let mut header = MAGIC.to_vec();
+    header.write_u16::<LittleEndian>(VERSION)?;
+    header.push(match order {
+        DecodeOrder::Big => 0,
+        DecodeOrder::Little => 1,
+    });
+    header.write_u32::<LittleEndian>(schema_text.len() as u32)?;
+    header.extend_from_slice(schema_text.as_bytes());
+    Ok(header)
+}
+
+/// reads the header at the current position and returns the schema, the byte order and the size of the header
+pub(crate) fn read_header<R: Read>(
+    reader: &mut R,
+) -> Result<(Schema, DecodeOrder, u64), RecordFileError> {
+    let mut magic = [0u8; 4];
+    reader.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(RecordFileError::BadMagic);
+    }
+    let version = reader.read_u16::<LittleEndian>()?;
+    if version != VERSION {
+        return Err(RecordFileError::Version(version));
+    }
+    let order = match reader.read_u8()? {
+        0 => DecodeOrder::Big,
+        1 => DecodeOrder::Little,
+        order => return Err(RecordFileError::BadOrder(order)),
+    };
+    let size = reader.read_u32::<LittleEndian>()?;
+    // the size comes from the file, the schema text is read as it arrives instead of allocated upfront
+    let mut schema_text = vec![];
+    reader.take(size as u64).read_to_end(&mut schema_text)?;
+    if schema_text.len() != size as usize {
+        return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+    }
+    let schema_text = String::from_utf8_lossy(&schema_text);
+    let schema = text::parse_schema(&schema_text).map_err(RecordFileError::Schema)?;
+    Ok((schema, order, 11 + size as u64))
+}
+
+/// returns the index and the footer of the given record offsets
+fn index_and_footer(offsets: &[u64], index_offset: u64) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(offsets.len() * 8 + FOOTER_SIZE as usize);
+    for offset in offsets {
+        bytes.extend_from_slice(&offset.to_le_bytes());
+    }
+    bytes.extend_from_slice(&index_offset.to_le_bytes());
+    bytes.extend_from_slice(&(offsets.len() as u64).to_le_bytes());
+    let crc = ChecksumKind::Crc32.compute(&bytes);
+    bytes.extend_from_slice(&crc.to_le_bytes());
+    bytes.extend_from_slice(FOOTER_MAGIC);
+    bytes
+}
+
+/// reads the footer and the index, returns the record offsets and the offset of the index
+pub(crate) fn read_index<R: Read + Seek>(
+    reader: &mut R,
+    data_start: u64,
+) -> Result<(Vec<u64>, u64), RecordFileError> {
+    let end = reader.seek(SeekFrom::End(0))?;
+    if end < data_start + FOOTER_SIZE {
+        return Err(RecordFileError::MissingIndex);
+    }
+    reader.seek(SeekFrom::Start(end - FOOTER_SIZE))?;
+    let mut footer = [0u8; FOOTER_SIZE as usize];
+    reader.read_exact(&mut footer)?;
+    if &footer[20..] != FOOTER_MAGIC {
+        return Err(RecordFileError::MissingIndex);
+    }
+    let index_offset = LittleEndian::read_u64(&footer[0..8]);
+    let count = LittleEndian::read_u64(&footer[8..16]);
+    let index_size = (end - FOOTER_SIZE).checked_sub(index_offset);
+    if index_offset < data_start || count.checked_mul(8) != index_size {
+        return Err(RecordFileError::MissingIndex);
+    }
+
+    reader.seek(SeekFrom::Start(index_offset))?;
+    let mut index = vec![0u8; count as usize * 8];
+    reader.read_exact(&mut index)?;
+    index.extend_from_slice(&footer[..16]);
+    if ChecksumKind::Crc32.compute(&index) != LittleEndian::read_u32(&footer[16..20]) {
+        return Err(RecordFileError::Checksum);
+    }
+    let offsets = index[..count as usize * 8]
+        .chunks_exact(8)
+        .map(LittleEndian::read_u64)
+        .collect();
+    Ok((offsets, index_offset))
+}
+
+/// `RecordWriter` writes a record file. Records are appended one after the other and `finish` writes the index, a
+/// file that is not finished can only be read after `recover`.
+///
+/// # Examples
+/// ```rust
+/// use packed_encoder::decoder::{DecodeOrder, DecodeType};
+/// use packed_encoder::encoder::EncodeType;
+/// use packed_encoder::recordfile::{RecordReader, RecordWriter};
+/// use packed_encoder::schema::Schema;
+/// use std::io::Cursor;
+///
+/// let schema = Schema::named("sample").field("id", DecodeType::Uint32);
+/// let mut writer = RecordWriter::new(Cursor::new(vec![]), schema, DecodeOrder::Little).unwrap();
+/// for id in 0..10 {
+///     writer.write(&[EncodeType::Uint32(id)]).unwrap();
+/// }
+/// let file = writer.finish().unwrap();
+///
+/// let mut reader = RecordReader::new(file).unwrap();
+/// assert_eq!(reader.len(), 10);
+/// assert_eq!(reader.read(7).unwrap().get_u32("id").unwrap(), 7);
+/// ```
+pub struct RecordWriter<W: Write> {
+    writer: W,
+    schema: Schema,
+    order: DecodeOrder,
+    position: u64,
+    offsets: Vec<u64>,
+}
+
+impl<W: Write> RecordWriter<W> {
+    /// writes the header of a new record file at the start of the writer
+    pub fn new(
+        mut writer: W,
+        schema: Schema,
+        order: DecodeOrder,
+    ) -> Result<RecordWriter<W>, RecordFileError> {
+        let header = header(&schema, &order)?;
+        writer.write_all(&header)?;
+        Ok(RecordWriter {
+            writer,
+            schema,
+            order,
+            position: header.len() as u64,
+            offsets: vec![],
+        })
+    }
+
+    /// returns the schema of the records
+    pub fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    /// returns the number of records in the file
+    pub fn len(&self) -> usize {
+        self.offsets.len()
+    }
+
+    /// returns true when the file has no records
+    pub fn is_empty(&self) -> bool {
+        self.offsets.is_empty()
+    }
+
+    /// encodes the values with the schema and appends them as a record, returns the number of the record
+    pub fn write(&mut self, values: &[EncodeType]) -> Result<usize, RecordFileError> {
+        let encoded = self
+            .schema
+            .encode(values, self.order.clone().into())
+            .map_err(RecordFileError::Encode)?;
+        self.write_record(&encoded)
+    }
+
+    /// appends bytes that were already encoded, like the output of `encode_packed`. They must hold exactly one
+    /// record of the schema, otherwise `recover` could not tell where the record ends.
+    pub fn write_encoded(&mut self, encoded: &[u8]) -> Result<usize, RecordFileError> {
+        match self.schema.decode_prefix(encoded, self.order.clone()) {
+            Ok((_, consumed)) if consumed == encoded.len() => self.write_record(encoded),
+            Ok((_, consumed)) => Err(RecordFileError::TrailingBytes(encoded.len() - consumed)),
+            Err(error) => Err(RecordFileError::Decode(error)),
+        }
+    }
+
+    fn write_record(&mut self, encoded: &[u8]) -> Result<usize, RecordFileError> {
+        let size =
+            u32::try_from(encoded.len()).map_err(|_| RecordFileError::TooLarge(encoded.len()))?;
+        self.writer.write_u32::<LittleEndian>(size)?;
+        self.writer.write_all(encoded)?;
+        self.offsets.push(self.position);
+        self.position += 4 + encoded.len() as u64;
+        Ok(self.offsets.len() - 1)
+    }
+
+    /// writes the index and the footer, flushes the writer and returns it
+    pub fn finish(mut self) -> Result<W, RecordFileError> {
+        self.writer
+            .write_all(&index_and_footer(&self.offsets, self.position))?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+impl RecordWriter<File> {
+    /// creates a record file, an existing file is replaced
+    pub fn create<P: AsRef<Path>>(
+        path: P,
+        schema: Schema,
+        order: DecodeOrder,
+    ) -> Result<RecordWriter<File>, RecordFileError> {
+        RecordWriter::new(File::create(path)?, schema, order)
+    }
+
+    /// opens a finished record file to append records to it, its index is removed until `finish` writes it again
+    pub fn append<P: AsRef<Path>>(path: P) -> Result<RecordWriter<File>, RecordFileError> {
+        let mut file = OpenOptions::new().read(true).write(true).open(path)?;
+        let (schema, order, data_start) = read_header(&mut file)?;
+        let (offsets, index_offset) = read_index(&mut file, data_start)?;
+        file.set_len(index_offset)?;
+        file.seek(SeekFrom::Start(index_offset))?;
+        Ok(RecordWriter {
+            writer: file,
+            schema,
+            order,
+            position: index_offset,
+            offsets,
+        })
+    }
+}
+
+/// `recover` rebuilds the index of a record file that was not finished, like after a crash. The records are scanned
+/// from the start, the file is truncated after the last complete record that matches the schema and a new index is
+/// written. It returns the number of records kept.
+pub fn recover<P: AsRef<Path>>(path: P) -> Result<usize, RecordFileError> {
+    let mut file = OpenOptions::new().read(true).write(true).open(path)?;
+    let (schema, order, data_start) = read_header(&mut file)?;
+    // the records of a finished file end where its index starts
+    let end = match read_index(&mut file, data_start) {
+        Ok((_, index_offset)) => index_offset,
+        Err(RecordFileError::Io(error)) => return Err(RecordFileError::Io(error)),
+        Err(_) => file.seek(SeekFrom::End(0))?,
+    };
+
+    file.seek(SeekFrom::Start(data_start))?;
+    let mut reader = io::BufReader::new(&mut file);
+    let mut offsets = vec![];
+    let mut position = data_start;
+    let mut record = vec![];
+    while position + 4 <= end {
+        let size = reader.read_u32::<LittleEndian>()? as u64;
+        if position + 4 + size > end {
+            break;
+        }
+        record.resize(size as usize, 0);
+        reader.read_exact(&mut record)?;
+        match schema.decode_prefix(&record, order.clone()) {
+            Ok((_, consumed)) if consumed as u64 == size => {}
+            _ => break,
+        }
+        offsets.push(position);
+        position += 4 + size;
+    }
+    drop(reader);
+
+    file.set_len(position)?;
+    file.seek(SeekFrom::Start(position))?;
+    file.write_all(&index_and_footer(&offsets, position))?;
+    file.sync_all()?;
+    Ok(offsets.len())
+}
+
+/// `RecordReader` reads the records of a finished record file, sequentially with `records` or by number with `read`.
+pub struct RecordReader<R: Read + Seek> {
+    reader: R,
+    schema: Schema,
+    order: DecodeOrder,
+    offsets: Vec<u64>,
+    index_offset: u64,
+}
+
+impl<R: Read + Seek> RecordReader<R> {
+    /// reads the header and the index of the file
+    pub fn new(mut reader: R) -> Result<RecordReader<R>, RecordFileError> {
+        reader.seek(SeekFrom::Start(0))?;
+        let (schema, order, data_start) = read_header(&mut reader)?;
+        let (offsets, index_offset) = read_index(&mut reader, data_start)?;
+        Ok(RecordReader {
+            reader,
+            schema,
+            order,
+            offsets,
+            index_offset,
+        })
+    }
+
+    /// returns the schema embedded in the file
+    pub fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    /// returns the byte order of the records
+    pub fn order(&self) -> DecodeOrder {
+        self.order.clone()
+    }
+
+    /// returns the number of records
+    pub fn len(&self) -> usize {
+        self.offsets.len()
+    }
+
+    /// returns true when the file has no records
+    pub fn is_empty(&self) -> bool {
+        self.offsets.is_empty()
+    }
+
+    /// returns the encoded bytes of the record with the given number
+    pub fn read_encoded(&mut self, index: usize) -> Result<Vec<u8>, RecordFileError> {
+        let offset = *self
+            .offsets
+            .get(index)
+            .ok_or(RecordFileError::OutOfRange(index))?;
+        self.reader.seek(SeekFrom::Start(offset))?;
+        read_record(&mut self.reader, offset, self.index_offset)
+    }
+
+    /// returns the decoded record with the given number
+    pub fn read(&mut self, index: usize) -> Result<DecodedRecord, RecordFileError> {
+        let encoded = self.read_encoded(index)?;
+        self.schema
+            .decode(&encoded, self.order.clone())
+            .map_err(RecordFileError::Decode)
+    }
+
+    /// returns an iterator over the records, from the first one
+    pub fn records(&mut self) -> Records<'_, R> {
+        Records {
+            file: self,
+            next: 0,
+            seeked: false,
+        }
+    }
+
+    /// returns the underlying reader
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+/// reads the record at the current position of the reader
+fn read_record<R: Read>(reader: &mut R, offset: u64, end: u64) -> Result<Vec<u8>, RecordFileError> {
+    let size = reader.read_u32::<LittleEndian>()? as u64;
+    if offset + 4 + size > end {
+        return Err(RecordFileError::Corrupt(offset));
+    }
+    let mut encoded = vec![0u8; size as usize];
+    reader.read_exact(&mut encoded)?;
+    Ok(encoded)
+}
+
+/// `Records` iterates over the records of a `RecordReader`, they are read one after the other without seeking.
+pub struct Records<'a, R: Read + Seek> {
+    file: &'a mut RecordReader<R>,
+    next: usize,
+    seeked: bool,
+}
+
+impl<R: Read + Seek> Iterator for Records<'_, R> {
+    type Item = Result<DecodedRecord, RecordFileError>;
+
+    fn next(&mut self) -> Option<Result<DecodedRecord, RecordFileError>> {
+        let offset = *self.file.offsets.get(self.next)?;
+        self.next += 1;
+        if !self.seeked {
+            if let Err(error) = self.file.reader.seek(SeekFrom::Start(offset)) {
+                return Some(Err(error.into()));
+            }
+            self.seeked = true;
+        }
+        let record = read_record(&mut self.file.reader, offset, self.file.index_offset);
+        // the position is unknown after an error, the next record is found through the index
+        self.seeked = record.is_ok();
+        let record = record.and_then(|encoded| {
+            self.file
+                .schema
+                .decode(&encoded, self.file.order.clone())
+                .map_err(RecordFileError::Decode)
+        });
+        Some(record)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.file.offsets.len() - self.next;
+        (remaining, Some(remaining))
+    }
+}
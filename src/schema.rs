@@ -0,0 +1,427 @@
+use crate::decoder::{self, DecodeError, DecodeOrder, DecodeType, DecodedData, ListEntry};
+use crate::encoder::{self, EncodeError, EncodeOrder, EncodeType};
+use std::fmt;
+use std::ops::Index;
+
+#[derive(Debug, Clone)]
+/// `Field` is an element of a schema, it pairs the type to decode with an optional name.
+/// Example: `Field::new("seq", DecodeType::Uint32)` declares a 32-bit unsigned integer named `seq`.
+pub struct Field {
+    /// name of the field, unnamed fields can only be accessed by index
+    pub name: Option<String>,
+    /// type of the field
+    pub kind: DecodeType,
+    /// byte order of the field, overrides the byte order given to `decode` and `encode` when set
+    pub order: Option<DecodeOrder>,
+    /// value of the field when it is missing from the data of an older version of the schema, see `evolution`
+    pub default: Option<DecodedData>,
+}
+
+impl Field {
+    /// creates a named field
+    pub fn new(name: &str, kind: DecodeType) -> Field {
+        Field {
+            name: Some(name.to_owned()),
+            kind,
+            order: None,
+            default: None,
+        }
+    }
+
+    /// creates an unnamed field
+    pub fn unnamed(kind: DecodeType) -> Field {
+        Field {
+            name: None,
+            kind,
+            order: None,
+            default: None,
+        }
+    }
+
+    /// sets the byte order of the field, example: `Field::new("len", DecodeType::Uint32).with_order(DecodeOrder::Big)`
+    pub fn with_order(mut self, order: DecodeOrder) -> Field {
+        self.order = Some(order);
+        self
+    }
+
+    /// sets the default value of the field, example: `Field::new("flags", DecodeType::Uint8).with_default(DecodedData::Uint8(0))`
+    pub fn with_default(mut self, default: DecodedData) -> Field {
+        self.default = Some(default);
+        self
+    }
+}
+
+impl ListEntry for Field {
+    fn kind(&self) -> &DecodeType {
+        &self.kind
+    }
+
+    fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    fn order(&self) -> Option<&DecodeOrder> {
+        self.order.as_ref()
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+/// `Schema` is a list of fields that can be decoded into a `DecodedRecord`, fields can be referred by name in
+/// `FieldRef::Name` references.
+///
+/// # Examples
+/// ```rust
+/// use packed_encoder::decoder::{DecodeOrder, DecodeType, FieldRef};
+/// use packed_encoder::schema::Schema;
+///
+/// let schema = Schema::new()
+///     .field("seq", DecodeType::Uint32)
+///     .field("len", DecodeType::Uint8)
+///     .field("name", DecodeType::StrRef(FieldRef::Name("len".to_owned())));
+///
+/// let record = schema.decode(&[7, 0, 0, 0, 2, b'o', b'k'], DecodeOrder::Little).unwrap();
+/// assert_eq!(record.get_u32("seq").unwrap(), 7);
+/// assert_eq!(record.get_str("name").unwrap(), "ok");
+/// ```
+pub struct Schema {
+    /// name of the schema, used by the textual representation
+    pub name: Option<String>,
+    /// fields of the schema, in the order they are encoded
+    pub fields: Vec<Field>,
+    /// version of the schema, used to tell the versions of an evolving format apart, see `evolution`
+    pub version: Option<u32>,
+}
+
+impl Schema {
+    /// creates an empty schema
+    pub fn new() -> Schema {
+        Schema {
+            name: None,
+            fields: vec![],
+            version: None,
+        }
+    }
+
+    /// creates an empty schema with the given name
+    pub fn named(name: &str) -> Schema {
+        Schema {
+            name: Some(name.to_owned()),
+            fields: vec![],
+            version: None,
+        }
+    }
+
+    /// sets the version of the schema
+    pub fn with_version(mut self, version: u32) -> Schema {
+        self.version = Some(version);
+        self
+    }
+
+    /// appends a field to the schema, it can be used for fields with their own byte order
+    pub fn push(mut self, field: Field) -> Schema {
+        self.fields.push(field);
+        self
+    }
+
+    /// appends a named field to the schema
+    pub fn field(mut self, name: &str, kind: DecodeType) -> Schema {
+        self.fields.push(Field::new(name, kind));
+        self
+    }
+
+    /// appends an unnamed field to the schema
+    pub fn unnamed(mut self, kind: DecodeType) -> Schema {
+        self.fields.push(Field::unnamed(kind));
+        self
+    }
+
+    /// returns the index of the field with the given name
+    pub fn index_of(&self, name: &str) -> Option<usize> {
+        self.fields
+            .iter()
+            .position(|field| field.name.as_deref() == Some(name))
+    }
+
+    /// returns the types of the fields, in the order they are encoded
+    pub fn types(&self) -> Vec<DecodeType> {
+        self.fields.iter().map(|field| field.kind.clone()).collect()
+    }
+
+    /// decodes the given buffer into a record, references by name are resolved against the fields of the schema.
+    pub fn decode(
+        &self,
+        buffer: &[u8],
+        decode_order: DecodeOrder,
+    ) -> Result<DecodedRecord, DecodeError> {
+        let list = decoder::decode_list(&self.fields, buffer, 0, decode_order).0?;
+        Ok(DecodedRecord::from_positions(
+            self,
+            list.values,
+            &list.positions,
+        ))
+    }
+
+    /// decodes a record from the start of the buffer and returns it with the number of bytes it uses, like
+    /// `decoder::decode_prefix`.
+    pub fn decode_prefix(
+        &self,
+        buffer: &[u8],
+        decode_order: DecodeOrder,
+    ) -> Result<(DecodedRecord, usize), DecodeError> {
+        let (result, consumed) = decoder::decode_list(&self.fields, buffer, 0, decode_order);
+        let list = result?;
+        Ok((
+            DecodedRecord::from_positions(self, list.values, &list.positions),
+            consumed,
+        ))
+    }
+
+    /// encodes the given values, one per field of the schema. References by name used by the values are resolved
+    /// against the fields of the schema.
+    pub fn encode(
+        &self,
+        values: &[EncodeType],
+        encode_order: EncodeOrder,
+    ) -> Result<Vec<u8>, EncodeError> {
+        let mut buffer = vec![];
+        encoder::encode_list(values, &self.fields, &mut buffer, encode_order)?;
+        Ok(buffer)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// `RecordError` is returned by the getters of `DecodedRecord`.
+pub enum RecordError {
+    /// MissingField occurs when the record has no field with the given name or index
+    MissingField(String),
+    /// TypeMismatch occurs when the field holds a value of a different type than the requested one
+    TypeMismatch {
+        /// name or index of the field
+        field: String,
+        /// requested type
+        expected: &'static str,
+        /// type of the value held by the field
+        found: &'static str,
+    },
+}
+
+impl fmt::Display for RecordError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RecordError::MissingField(field) => write!(f, "record has no field {}", field),
+            RecordError::TypeMismatch {
+                field,
+                expected,
+                found,
+            } => write!(f, "field {} holds {}, not {}", field, found, expected),
+        }
+    }
+}
+
+impl std::error::Error for RecordError {}
+
+/// `FieldKey` is implemented by the types that can be used to look up a field of a `DecodedRecord`: names (`&str`)
+/// and indices (`usize`).
+pub trait FieldKey {
+    /// returns the index of the value among the values of the record
+    fn locate(&self, record: &DecodedRecord) -> Option<usize>;
+    /// describes the key in error messages
+    fn describe(&self) -> String;
+}
+
+impl FieldKey for usize {
+    fn locate(&self, record: &DecodedRecord) -> Option<usize> {
+        (*self < record.values.len()).then_some(*self)
+    }
+
+    fn describe(&self) -> String {
+        format!("#{}", self)
+    }
+}
+
+impl FieldKey for &str {
+    fn locate(&self, record: &DecodedRecord) -> Option<usize> {
+        record
+            .names
+            .iter()
+            .position(|name| name.as_deref() == Some(*self))
+    }
+
+    fn describe(&self) -> String {
+        format!("'{}'", self)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// `DecodedRecord` holds the values decoded using a `Schema`, values can be looked up by name or by index.
+/// Constant and reserved fields are left out of the values, like in `decode_packed`. Like `DecodedData`, it implements
+/// `PartialEq` but not `Eq`.
+pub struct DecodedRecord {
+    names: Vec<Option<String>>,
+    values: Vec<DecodedData>,
+}
+
+macro_rules! typed_getter {
+    ($(#[$doc:meta])* $name:ident, $type:ty) => {
+        $(#[$doc])*
+        pub fn $name<K: FieldKey>(&self, key: K) -> Result<$type, RecordError> {
+            <$type>::try_from(self.lookup(&key)?).map_err(|err| RecordError::TypeMismatch {
+                field: key.describe(),
+                expected: err.expected,
+                found: err.found,
+            })
+        }
+    };
+}
+
+impl DecodedRecord {
+    pub(crate) fn from_positions(
+        schema: &Schema,
+        values: Vec<DecodedData>,
+        positions: &[Option<usize>],
+    ) -> DecodedRecord {
+        let mut names = vec![None; values.len()];
+        for (field, position) in schema.fields.iter().zip(positions) {
+            if let Some(position) = position {
+                names[*position] = field.name.clone();
+            }
+        }
+        DecodedRecord { names, values }
+    }
+
+    /// returns the number of values in the record
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// returns true if the record holds no values
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// returns the value of the field with the given name or index
+    pub fn get<K: FieldKey>(&self, key: K) -> Option<&DecodedData> {
+        key.locate(self).map(|position| &self.values[position])
+    }
+
+    /// returns the name of the value at the given index
+    pub fn name(&self, index: usize) -> Option<&str> {
+        self.names.get(index).and_then(|name| name.as_deref())
+    }
+
+    /// returns the values of the record, in the order they were decoded
+    pub fn values(&self) -> &[DecodedData] {
+        &self.values
+    }
+
+    /// consumes the record and returns its values
+    pub fn into_values(self) -> Vec<DecodedData> {
+        self.values
+    }
+
+    /// iterates over the names and the values of the record
+    pub fn iter(&self) -> impl Iterator<Item = (Option<&str>, &DecodedData)> {
+        self.names
+            .iter()
+            .map(|name| name.as_deref())
+            .zip(self.values.iter())
+    }
+
+    fn lookup<K: FieldKey>(&self, key: &K) -> Result<&DecodedData, RecordError> {
+        key.locate(self)
+            .map(|position| &self.values[position])
+            .ok_or_else(|| RecordError::MissingField(key.describe()))
+    }
+
+    typed_getter!(
+        /// returns the value of an integer field as `i8`, see `TryFrom<&DecodedData>` for the accepted types
+        get_i8, i8
+    );
+    typed_getter!(
+        /// returns the value of an integer field as `i16`, see `TryFrom<&DecodedData>` for the accepted types
+        get_i16, i16
+    );
+    typed_getter!(
+        /// returns the value of an integer field as `i32`, see `TryFrom<&DecodedData>` for the accepted types
+        get_i32, i32
+    );
+    typed_getter!(
+        /// returns the value of an integer field as `i64`, see `TryFrom<&DecodedData>` for the accepted types
+        get_i64, i64
+    );
+    typed_getter!(
+        /// returns the value of an integer field as `i128`, see `TryFrom<&DecodedData>` for the accepted types
+        get_i128, i128
+    );
+    typed_getter!(
+        /// returns the value of an integer field as `u8`, see `TryFrom<&DecodedData>` for the accepted types
+        get_u8, u8
+    );
+    typed_getter!(
+        /// returns the value of an integer field as `u16`, see `TryFrom<&DecodedData>` for the accepted types
+        get_u16, u16
+    );
+    typed_getter!(
+        /// returns the value of an integer field as `u32`, see `TryFrom<&DecodedData>` for the accepted types
+        get_u32, u32
+    );
+    typed_getter!(
+        /// returns the value of an integer field as `u64`, see `TryFrom<&DecodedData>` for the accepted types
+        get_u64, u64
+    );
+    typed_getter!(
+        /// returns the value of an integer field as `u128`, see `TryFrom<&DecodedData>` for the accepted types
+        get_u128, u128
+    );
+    typed_getter!(
+        /// returns the value of a `Float32` field
+        get_f32, f32
+    );
+    typed_getter!(
+        /// returns the value of a `Float32` or `Float64` field as `f64`
+        get_f64, f64
+    );
+    typed_getter!(
+        /// returns the value of a `Bool` field
+        get_bool, bool
+    );
+
+    /// returns the content of a `Str` field
+    pub fn get_str<K: FieldKey>(&self, key: K) -> Result<&str, RecordError> {
+        self.lookup(&key)?
+            .as_str()
+            .map_err(|err| RecordError::TypeMismatch {
+                field: key.describe(),
+                expected: err.expected,
+                found: err.found,
+            })
+    }
+
+    /// returns the content of a `Bytes` field
+    pub fn get_bytes<K: FieldKey>(&self, key: K) -> Result<&[u8], RecordError> {
+        self.lookup(&key)?
+            .as_bytes()
+            .map_err(|err| RecordError::TypeMismatch {
+                field: key.describe(),
+                expected: err.expected,
+                found: err.found,
+            })
+    }
+}
+
+impl Index<usize> for DecodedRecord {
+    type Output = DecodedData;
+
+    fn index(&self, index: usize) -> &DecodedData {
+        &self.values[index]
+    }
+}
+
+impl Index<&str> for DecodedRecord {
+    type Output = DecodedData;
+
+    fn index(&self, name: &str) -> &DecodedData {
+        self.get(name)
+            .unwrap_or_else(|| panic!("record has no field '{}'", name))
+    }
+}
@@ -0,0 +1,1156 @@
+use crate::bitfield::{BitField, BitOrder, BitValue};
+use crate::checksum::ChecksumKind;
+use crate::decoder::{DecodeOrder, DecodeType, DecodedData, FieldRef, Predicate};
+use crate::schema::{Field, Schema};
+use std::fmt;
+use std::ops::Neg;
+use std::str::FromStr;
+
+// The textual schema language describes one or more structs:
+//
+//     struct Header version 2 {      // the version is optional
+//         magic: const b"PK";        // verified, left out of the decoded values
+//         version: u8 = 1;           // default value used when reading older versions
+//         len: u32be;                // `be`/`le` suffixes override the byte order of the field
+//         name: str[len];            // length stored in an earlier field, `#2` refers to it by index
+//         _: reserved[2];            // `_` declares an unnamed field
+//         levels: bits(lsb) { u1; u3; i4; };
+//         flags: u8;
+//         extra: if flags & 0x1 { u16; bytes[#0]; };  // masks only apply to integer fields
+//         crc: checksum crc32(0..7);
+//     }
+//
+// Arrays are written as a postfix length, `u16[4]` or `u16[count]`, and can be nested: `u8[2][3]` is three
+// arrays of two bytes.
+//
+// Values, in defaults and conditions, are written as `-1`, `0.5`, `inf`, `true`, `"text"` and `b"PK"` or
+// `[0x50, 0x4b]` for bytes. Arrays, bit-groups and the values of conditional elements are lists, `[1, -2]`, and a
+// conditional element that is absent is `none`. Names cannot be resolved inside nested lists, the values compared
+// with them are preceded by their type: `if kind == (u8) 2 { u16; }`.
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// `ParseError` is returned when a schema text cannot be parsed, it points to the offending token.
+/// Example: `line 3, column 10: unknown type 'u24'`.
+pub struct ParseError {
+    /// line of the offending token, starting from 1
+    pub line: usize,
+    /// column of the offending token, starting from 1
+    pub column: usize,
+    /// description of the error
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "line {}, column {}: {}",
+            self.line, self.column, self.message
+        )
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// parses every struct of the given text into a schema
+pub fn parse(text: &str) -> Result<Vec<Schema>, ParseError> {
+    let mut parser = Parser::new(text)?;
+    let mut schemas = vec![];
+    while parser.peek() != &Token::End {
+        schemas.push(parser.parse_struct()?);
+    }
+    Ok(schemas)
+}
+
+/// parses a text holding exactly one struct
+pub fn parse_schema(text: &str) -> Result<Schema, ParseError> {
+    let mut parser = Parser::new(text)?;
+    let schema = parser.parse_struct()?;
+    match parser.peek() {
+        Token::End => Ok(schema),
+        _ => Err(parser.error("expected a single struct")),
+    }
+}
+
+/// prints a schema in the textual schema language, the output can be parsed back with `parse_schema`.
+/// `Predicate::Custom` conditions have no textual form and are printed as `custom`, which cannot be parsed.
+pub fn print(schema: &Schema) -> String {
+    let mut text = String::from("struct ");
+    if let Some(name) = &schema.name {
+        text.push_str(name);
+        text.push(' ');
+    }
+    if let Some(version) = schema.version {
+        text.push_str(&format!("version {} ", version));
+    }
+    text.push_str("{\n");
+    for field in &schema.fields {
+        text.push_str("    ");
+        text.push_str(field.name.as_deref().unwrap_or("_"));
+        text.push_str(": ");
+        print_type(&mut text, &field.kind, field.order.as_ref(), false);
+        if let Some(default) = &field.default {
+            text.push_str(" = ");
+            print_value(&mut text, default);
+        }
+        text.push_str(";\n");
+    }
+    text.push_str("}\n");
+    text
+}
+
+impl FromStr for Schema {
+    type Err = ParseError;
+
+    fn from_str(text: &str) -> Result<Schema, ParseError> {
+        parse_schema(text)
+    }
+}
+
+impl fmt::Display for Schema {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&print(self))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Ident(String),
+    Number(u128),
+    // kept as text so that it is parsed with the precision of the value it is assigned to
+    Float(String),
+    Str(String),
+    ByteStr(Vec<u8>),
+    Punct(&'static str),
+    End,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Token::Ident(ident) => write!(f, "'{}'", ident),
+            Token::Number(number) => write!(f, "'{}'", number),
+            Token::Float(number) => write!(f, "'{}'", number),
+            Token::Str(_) => f.write_str("string"),
+            Token::ByteStr(_) => f.write_str("byte string"),
+            Token::Punct(punct) => write!(f, "'{}'", punct),
+            Token::End => f.write_str("end of input"),
+        }
+    }
+}
+
+// longer punctuation first, so that `..` is not read as two tokens
+const PUNCTUATION: &[&str] = &[
+    "..", "==", "!=", "!&", "{", "}", "[", "]", "(", ")", ":", ";", ",", "#", "&", "-", "=",
+];
+
+/// splits the text into tokens along with their line and column
+fn tokenize(text: &str) -> Result<Vec<(Token, usize, usize)>, ParseError> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = vec![];
+    let (mut pos, mut line, mut column) = (0, 1, 1);
+
+    let error = |line, column, message: &str| ParseError {
+        line,
+        column,
+        message: message.to_owned(),
+    };
+
+    while pos < chars.len() {
+        let start = (line, column);
+        let c = chars[pos];
+        if c == '\n' {
+            pos += 1;
+            line += 1;
+            column = 1;
+            continue;
+        }
+        if c.is_whitespace() {
+            pos += 1;
+            column += 1;
+            continue;
+        }
+        if c == '/' && chars.get(pos + 1) == Some(&'/') {
+            while pos < chars.len() && chars[pos] != '\n' {
+                pos += 1;
+            }
+            continue;
+        }
+
+        let token = if c.is_ascii_alphabetic() || c == '_' {
+            if c == 'b' && chars.get(pos + 1) == Some(&'"') {
+                let (content, size) = read_string(&chars[pos + 1..])
+                    .map_err(|(offset, message)| error(line, column + 1 + offset, message))?;
+                pos += 1 + size;
+                column += 1 + size;
+                Token::ByteStr(content)
+            } else {
+                let size = chars[pos..]
+                    .iter()
+                    .take_while(|c| c.is_ascii_alphanumeric() || **c == '_')
+                    .count();
+                let ident: String = chars[pos..pos + size].iter().collect();
+                pos += size;
+                column += size;
+                Token::Ident(ident)
+            }
+        } else if c.is_ascii_digit() {
+            let hex = chars.get(pos + 1) == Some(&'x');
+            let mut size = 0;
+            // a dot is part of the number only when a digit follows, `0..6` is a range
+            while let Some(next) = chars.get(pos + size) {
+                let exponent = !hex && size > 0 && matches!(chars[pos + size - 1], 'e' | 'E');
+                let fraction = chars
+                    .get(pos + size + 1)
+                    .is_some_and(|c| c.is_ascii_digit());
+                match next {
+                    c if c.is_ascii_alphanumeric() || *c == '_' => size += 1,
+                    '.' if fraction => size += 1,
+                    '+' | '-' if exponent => size += 1,
+                    _ => break,
+                }
+            }
+            let literal: String = chars[pos..pos + size]
+                .iter()
+                .filter(|c| **c != '_')
+                .collect();
+            let token = if hex {
+                literal
+                    .strip_prefix("0x")
+                    .and_then(|hex| u128::from_str_radix(hex, 16).ok())
+                    .map(Token::Number)
+            } else if literal.contains(['.', 'e', 'E']) {
+                literal
+                    .parse::<f64>()
+                    .ok()
+                    .map(|_| Token::Float(literal.clone()))
+            } else {
+                literal.parse().ok().map(Token::Number)
+            }
+            .ok_or_else(|| error(line, column, "invalid number"))?;
+            pos += size;
+            column += size;
+            token
+        } else if c == '"' {
+            let (content, size) = read_string(&chars[pos..])
+                .map_err(|(offset, message)| error(line, column + offset, message))?;
+            pos += size;
+            column += size;
+            let content = String::from_utf8(content)
+                .map_err(|_| error(start.0, start.1, "string is not valid UTF-8"))?;
+            Token::Str(content)
+        } else {
+            let punct = PUNCTUATION
+                .iter()
+                .find(|punct| {
+                    punct
+                        .chars()
+                        .enumerate()
+                        .all(|(offset, c)| chars.get(pos + offset) == Some(&c))
+                })
+                .ok_or_else(|| error(line, column, &format!("unexpected character '{}'", c)))?;
+            pos += punct.len();
+            column += punct.len();
+            Token::Punct(punct)
+        };
+        tokens.push((token, start.0, start.1));
+    }
+
+    tokens.push((Token::End, line, column));
+    Ok(tokens)
+}
+
+/// reads a quoted string starting at the opening quote, returns its bytes and the number of characters it occupies.
+/// Supports the escapes `\\`, `\"`, `\n`, `\r`, `\t`, `\0` and `\xNN`.
+fn read_string(chars: &[char]) -> Result<(Vec<u8>, usize), (usize, &'static str)> {
+    let mut content = vec![];
+    let mut pos = 1;
+    loop {
+        match chars.get(pos) {
+            None | Some('\n') => return Err((0, "unterminated string")),
+            Some('"') => return Ok((content, pos + 1)),
+            Some('\\') => {
+                let byte = match chars.get(pos + 1) {
+                    Some('\\') => b'\\',
+                    Some('"') => b'"',
+                    Some('n') => b'\n',
+                    Some('r') => b'\r',
+                    Some('t') => b'\t',
+                    Some('0') => 0,
+                    Some('x') => {
+                        let hex: String = chars.iter().skip(pos + 2).take(2).collect();
+                        pos += 2;
+                        u8::from_str_radix(&hex, 16).map_err(|_| (pos - 2, "invalid escape"))?
+                    }
+                    _ => return Err((pos, "invalid escape")),
+                };
+                content.push(byte);
+                pos += 2;
+            }
+            Some(c) => {
+                let mut utf8 = [0; 4];
+                content.extend_from_slice(c.encode_utf8(&mut utf8).as_bytes());
+                pos += 1;
+            }
+        }
+    }
+}
+
+/// `Parser` reads the tokens of a schema text, the elements parsed so far in the current list are kept to resolve
+/// references and to type the values of conditions. `depth` counts the nested lists being parsed, names are only
+/// resolved outside of them.
+struct Parser {
+    tokens: Vec<(Token, usize, usize)>,
+    pos: usize,
+    depth: usize,
+}
+
+impl Parser {
+    fn new(text: &str) -> Result<Parser, ParseError> {
+        Ok(Parser {
+            tokens: tokenize(text)?,
+            pos: 0,
+            depth: 0,
+        })
+    }
+
+    fn peek(&self) -> &Token {
+        &self.tokens[self.pos].0
+    }
+
+    fn next(&mut self) -> Token {
+        let token = self.tokens[self.pos].0.clone();
+        if token != Token::End {
+            self.pos += 1;
+        }
+        token
+    }
+
+    /// returns an error located at the next token
+    fn error(&self, message: &str) -> ParseError {
+        let (_, line, column) = self.tokens[self.pos];
+        ParseError {
+            line,
+            column,
+            message: message.to_owned(),
+        }
+    }
+
+    /// returns an error located at the token that was just read
+    fn error_before(&self, message: &str) -> ParseError {
+        let (_, line, column) = self.tokens[self.pos.saturating_sub(1)];
+        ParseError {
+            line,
+            column,
+            message: message.to_owned(),
+        }
+    }
+
+    fn accept(&mut self, punct: &'static str) -> bool {
+        if self.peek() == &Token::Punct(punct) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, punct: &'static str) -> Result<(), ParseError> {
+        if self.accept(punct) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected '{}', found {}", punct, self.peek())))
+        }
+    }
+
+    fn ident(&mut self) -> Result<String, ParseError> {
+        match self.next() {
+            Token::Ident(ident) => Ok(ident),
+            other => Err(self.error_before(&format!("expected a name, found {}", other))),
+        }
+    }
+
+    fn number(&mut self) -> Result<u128, ParseError> {
+        match self.next() {
+            Token::Number(number) => Ok(number),
+            other => Err(self.error_before(&format!("expected a number, found {}", other))),
+        }
+    }
+
+    fn version(&mut self) -> Result<u32, ParseError> {
+        let number = self.number()?;
+        u32::try_from(number).map_err(|_| self.error_before("version is too large"))
+    }
+
+    fn size(&mut self) -> Result<usize, ParseError> {
+        let number = self.number()?;
+        usize::try_from(number).map_err(|_| self.error_before("size is too large"))
+    }
+
+    fn parse_struct(&mut self) -> Result<Schema, ParseError> {
+        match self.next() {
+            Token::Ident(keyword) if keyword == "struct" => {}
+            other => return Err(self.error_before(&format!("expected 'struct', found {}", other))),
+        }
+        let mut schema = Schema::new();
+        if let Token::Ident(_) = self.peek() {
+            let name = self.ident()?;
+            // `version` is only a name when it is not followed by the version number
+            match self.peek() {
+                Token::Number(_) if name == "version" => schema.version = Some(self.version()?),
+                _ => schema.name = Some(name),
+            }
+        }
+        if schema.version.is_none() && self.peek() == &Token::Ident("version".to_owned()) {
+            self.next();
+            schema.version = Some(self.version()?);
+        }
+        self.expect("{")?;
+
+        let mut names: Vec<Option<String>> = vec![];
+        let mut kinds: Vec<DecodeType> = vec![];
+        while !self.accept("}") {
+            let name = self.ident()?;
+            let name = (name != "_").then_some(name);
+            if name.is_some() && names.contains(&name) {
+                return Err(self.error_before("duplicate field name"));
+            }
+            self.expect(":")?;
+            let mut order = None;
+            let kind = self.parse_type(&names, &kinds, &mut order)?;
+            let mut default = None;
+            if self.accept("=") {
+                if !has_default(&kind) {
+                    return Err(self.error_before("the field cannot have a default value"));
+                }
+                default = Some(self.parse_value(&kind)?);
+            }
+            self.expect(";")?;
+
+            names.push(name.clone());
+            kinds.push(kind.clone());
+            schema.fields.push(Field {
+                name,
+                kind,
+                order,
+                default,
+            });
+        }
+        Ok(schema)
+    }
+
+    /// parses a type along with its array suffixes. `names` and `kinds` describe the elements placed before it in
+    /// the same list, `order` collects the byte order suffixes used by the type.
+    fn parse_type(
+        &mut self,
+        names: &[Option<String>],
+        kinds: &[DecodeType],
+        order: &mut Option<DecodeOrder>,
+    ) -> Result<DecodeType, ParseError> {
+        let keyword = self.ident()?;
+        let mut kind = match keyword.as_str() {
+            "str" => match self.parse_length(names, kinds)? {
+                Length::Fixed(size) => DecodeType::Str(size),
+                Length::Ref(field) => DecodeType::StrRef(field),
+            },
+            "bytes" => match self.parse_length(names, kinds)? {
+                Length::Fixed(size) => DecodeType::Bytes(size),
+                Length::Ref(field) => DecodeType::BytesRef(field),
+            },
+            "reserved" => {
+                self.expect("[")?;
+                let size = self.size()?;
+                self.expect("]")?;
+                DecodeType::Reserved(size)
+            }
+            "padding" => {
+                self.expect("[")?;
+                let size = self.size()?;
+                self.expect("]")?;
+                DecodeType::Padding(size)
+            }
+            "const" => DecodeType::Const(self.parse_bytes()?),
+            "bits" => self.parse_bits()?,
+            "checksum" => {
+                let name = self.ident()?;
+                let (kind, suffix) = checksum_kind(&name)
+                    .ok_or_else(|| self.error_before(&format!("unknown checksum '{}'", name)))?;
+                self.set_order(order, suffix)?;
+                self.expect("(")?;
+                let start = self.size()?;
+                self.expect("..")?;
+                let end = self.size()?;
+                if start > end || end > kinds.len() {
+                    return Err(self.error_before("checksum range must cover earlier fields"));
+                }
+                self.expect(")")?;
+                DecodeType::Checksum(kind, start..end)
+            }
+            "if" => {
+                let (field, target) = self.parse_ref(names, kinds)?;
+                let predicate = self.parse_predicate(target.map(|target| &kinds[target]))?;
+                let types = self.parse_list(order)?;
+                DecodeType::Conditional(field, predicate, types)
+            }
+            "bool" => DecodeType::Bool,
+            "optional" => match self.peek() {
+                Token::Ident(ident) if ident == "none" => {
+                    self.pos += 1;
+                    DecodeType::Optional(None)
+                }
+                _ => DecodeType::Optional(Some(self.parse_list(order)?)),
+            },
+            other => {
+                let (kind, suffix) = number_type(other)
+                    .ok_or_else(|| self.error_before(&format!("unknown type '{}'", other)))?;
+                self.set_order(order, suffix)?;
+                kind
+            }
+        };
+
+        while self.peek() == &Token::Punct("[") {
+            kind = match self.parse_length(names, kinds)? {
+                Length::Fixed(count) => DecodeType::Array(Box::new(kind), count),
+                Length::Ref(field) => DecodeType::ArrayRef(Box::new(kind), field),
+            };
+        }
+        Ok(kind)
+    }
+
+    /// parses the nested list of a conditional or optional element: `{ u8; str[#0]; }`
+    fn parse_list(
+        &mut self,
+        order: &mut Option<DecodeOrder>,
+    ) -> Result<Vec<DecodeType>, ParseError> {
+        self.expect("{")?;
+        self.depth += 1;
+        let mut kinds: Vec<DecodeType> = vec![];
+        while !self.accept("}") {
+            let names = vec![None; kinds.len()];
+            let kind = self.parse_type(&names, &kinds, order)?;
+            self.expect(";")?;
+            kinds.push(kind);
+        }
+        self.depth -= 1;
+        Ok(kinds)
+    }
+
+    /// parses `(msb) { u3; i5; }`, the bit order defaults to msb
+    fn parse_bits(&mut self) -> Result<DecodeType, ParseError> {
+        let mut bit_order = BitOrder::Msb;
+        if self.accept("(") {
+            bit_order = match self.ident()?.as_str() {
+                "msb" => BitOrder::Msb,
+                "lsb" => BitOrder::Lsb,
+                _ => return Err(self.error_before("expected 'msb' or 'lsb'")),
+            };
+            self.expect(")")?;
+        }
+
+        self.expect("{")?;
+        let mut fields = vec![];
+        while !self.accept("}") {
+            let name = self.ident()?;
+            let width = name
+                .get(1..)
+                .and_then(|width| width.parse::<u8>().ok())
+                .filter(|width| (1..=64).contains(width));
+            let field = match (name.chars().next(), width) {
+                (Some('u'), Some(width)) => BitField::Unsigned(width),
+                (Some('i'), Some(width)) => BitField::Signed(width),
+                _ => {
+                    return Err(self.error_before(&format!(
+                        "expected a bit-field like 'u3' or 'i5', found '{}'",
+                        name
+                    )))
+                }
+            };
+            self.expect(";")?;
+            fields.push(field);
+        }
+        Ok(DecodeType::BitGroup(fields, bit_order))
+    }
+
+    /// parses `b"PK"` or `[0x50, 0x4b]`
+    fn parse_bytes(&mut self) -> Result<Vec<u8>, ParseError> {
+        if let Token::ByteStr(content) = self.peek() {
+            let content = content.clone();
+            self.pos += 1;
+            return Ok(content);
+        }
+
+        self.parse_items(|parser, _| {
+            let byte = parser.number()?;
+            u8::try_from(byte).map_err(|_| parser.error_before("byte out of range"))
+        })
+    }
+
+    /// parses `[item, item]`, `item` is called with the index of each item
+    fn parse_items<T>(
+        &mut self,
+        mut item: impl FnMut(&mut Parser, usize) -> Result<T, ParseError>,
+    ) -> Result<Vec<T>, ParseError> {
+        self.expect("[")?;
+        let mut items = vec![];
+        while !self.accept("]") {
+            if !items.is_empty() {
+                self.expect(",")?;
+                // trailing comma
+                if self.accept("]") {
+                    break;
+                }
+            }
+            items.push(item(self, items.len())?);
+        }
+        Ok(items)
+    }
+
+    /// parses `[N]`, `[name]` or `[#index]`
+    fn parse_length(
+        &mut self,
+        names: &[Option<String>],
+        kinds: &[DecodeType],
+    ) -> Result<Length, ParseError> {
+        self.expect("[")?;
+        let length = match self.peek() {
+            Token::Number(_) => Length::Fixed(self.size()?),
+            _ => {
+                let (field, target) = self.parse_ref(names, kinds)?;
+                if target.is_some_and(|target| !is_integer(&kinds[target])) {
+                    return Err(self.error_before("length must refer to an integer field"));
+                }
+                Length::Ref(field)
+            }
+        };
+        self.expect("]")?;
+        Ok(length)
+    }
+
+    /// parses a reference to an earlier element, `name` or `#index`, returns it along with the index of the element.
+    /// Inside nested lists names are kept unresolved, like `FieldRef::Name` they only refer to the fields of a
+    /// schema.
+    fn parse_ref(
+        &mut self,
+        names: &[Option<String>],
+        kinds: &[DecodeType],
+    ) -> Result<(FieldRef, Option<usize>), ParseError> {
+        if self.accept("#") {
+            let index = self.size()?;
+            if index >= kinds.len() {
+                return Err(self.error_before("reference must point to an earlier field"));
+            }
+            return Ok((FieldRef::Index(index), Some(index)));
+        }
+
+        let name = self.ident()?;
+        let target = names
+            .iter()
+            .position(|entry| entry.as_deref() == Some(name.as_str()));
+        if target.is_none() && self.depth == 0 {
+            return Err(self.error_before(&format!("unknown field '{}'", name)));
+        }
+        Ok((FieldRef::Name(name), target))
+    }
+
+    /// parses `== value`, `!= value`, `& mask` or `!& mask`, values take the type of the referred element
+    fn parse_predicate(&mut self, target: Option<&DecodeType>) -> Result<Predicate, ParseError> {
+        let masked = matches!(self.peek(), Token::Punct("&" | "!&"));
+        // the decoder only tests masks against integers, bit-groups and other values never match
+        if masked
+            && target.is_some_and(|target| {
+                !is_integer(target) && !matches!(target, DecodeType::Checksum(..))
+            })
+        {
+            return Err(self.error("masks can only test integer fields"));
+        }
+        if self.accept("&") {
+            return Ok(Predicate::BitsSet(self.number()?));
+        }
+        if self.accept("!&") {
+            return Ok(Predicate::BitsClear(self.number()?));
+        }
+        if self.accept("==") {
+            return Ok(Predicate::Equals(self.parse_compared(target)?));
+        }
+        if self.accept("!=") {
+            return Ok(Predicate::NotEquals(self.parse_compared(target)?));
+        }
+        match self.peek() {
+            Token::Ident(ident) if ident == "custom" => {
+                Err(self.error("custom conditions cannot be parsed"))
+            }
+            other => Err(self.error(&format!(
+                "expected '==', '!=', '&' or '!&', found {}",
+                other
+            ))),
+        }
+    }
+
+    /// parses the value compared by a condition, the values compared with unresolved names are preceded by their
+    /// type: `(u8) 2`
+    fn parse_compared(&mut self, target: Option<&DecodeType>) -> Result<DecodedData, ParseError> {
+        if let Some(target) = target {
+            return self.parse_value(target);
+        }
+        if !self.accept("(") {
+            return Err(self.error("expected the type of the value, like '(u8) 1'"));
+        }
+        let kind = self.parse_type(&[], &[], &mut None)?;
+        self.expect(")")?;
+        self.parse_value(&kind)
+    }
+
+    fn parse_value(&mut self, target: &DecodeType) -> Result<DecodedData, ParseError> {
+        let value = match target {
+            DecodeType::Str(_) | DecodeType::StrRef(_) => match self.next() {
+                Token::Str(content) => return Ok(DecodedData::Str(content)),
+                _ => return Err(self.error_before("expected a string")),
+            },
+            DecodeType::Bytes(_) | DecodeType::BytesRef(_) => {
+                return Ok(DecodedData::Bytes(self.parse_bytes()?))
+            }
+            DecodeType::Bool => match self.next() {
+                Token::Ident(ident) if ident == "true" => return Ok(DecodedData::Bool(true)),
+                Token::Ident(ident) if ident == "false" => return Ok(DecodedData::Bool(false)),
+                _ => return Err(self.error_before("expected 'true' or 'false'")),
+            },
+            DecodeType::Float32 => return self.parse_float().map(DecodedData::Float32),
+            DecodeType::Float64 => return self.parse_float().map(DecodedData::Float64),
+            DecodeType::Array(item, _) | DecodeType::ArrayRef(item, _) => {
+                let items = self.parse_items(|parser, _| parser.parse_value(item))?;
+                return Ok(DecodedData::Array(items));
+            }
+            DecodeType::BitGroup(fields, bit_order) => {
+                let values = self.parse_items(|parser, idx| {
+                    let field = fields
+                        .get(idx)
+                        .ok_or_else(|| parser.error("too many values for the bit-group"))?;
+                    let value = parser.parse_integer()?.ok();
+                    match field {
+                        BitField::Unsigned(width) => value
+                            .and_then(|value| u64::try_from(value).ok())
+                            .map(|value| BitValue::Unsigned(*width, value)),
+                        BitField::Signed(width) => value
+                            .and_then(|value| i64::try_from(value).ok())
+                            .map(|value| BitValue::Signed(*width, value)),
+                    }
+                    .ok_or_else(|| parser.error_before("value out of range for the bit-field"))
+                })?;
+                if values.len() != fields.len() {
+                    return Err(self.error_before("missing values for the bit-group"));
+                }
+                return Ok(DecodedData::BitGroup(values, *bit_order));
+            }
+            DecodeType::Optional(kinds) => return self.parse_optional(kinds.as_deref()),
+            DecodeType::Conditional(_, _, kinds) => return self.parse_optional(Some(kinds)),
+            _ => self.parse_integer()?,
+        };
+
+        let out_of_range = || self.error_before("value out of range for the referred field");
+        // values that do not fit in i128 are only valid for `u128`
+        let Ok(value) = value else {
+            return match (target, value) {
+                (DecodeType::Uint128, Err(number)) => Ok(DecodedData::Uint128(number)),
+                _ => Err(out_of_range()),
+            };
+        };
+        macro_rules! typed {
+            ($type:ty, $variant:ident) => {
+                <$type>::try_from(value)
+                    .map(DecodedData::$variant)
+                    .map_err(|_| out_of_range())
+            };
+        }
+        match target {
+            DecodeType::Int8 => typed!(i8, Int8),
+            DecodeType::Int16 => typed!(i16, Int16),
+            DecodeType::Int32 => typed!(i32, Int32),
+            DecodeType::Int64 => typed!(i64, Int64),
+            DecodeType::Int128 => Ok(DecodedData::Int128(value)),
+            DecodeType::Uint8 => typed!(u8, Uint8),
+            DecodeType::Uint16 => typed!(u16, Uint16),
+            DecodeType::Uint32 => typed!(u32, Uint32),
+            DecodeType::Uint64 => typed!(u64, Uint64),
+            DecodeType::Uint128 => typed!(u128, Uint128),
+            DecodeType::Checksum(kind, _) => match kind.size() {
+                1 => typed!(u8, Uint8),
+                2 => typed!(u16, Uint16),
+                _ => typed!(u32, Uint32),
+            },
+            _ => Err(self.error_before("the referred field cannot be compared with a value")),
+        }
+    }
+
+    /// parses an integer with an optional minus sign, the values that do not fit in i128 are returned as errors
+    fn parse_integer(&mut self) -> Result<Result<i128, u128>, ParseError> {
+        let negative = self.accept("-");
+        let number = self.number()?;
+        Ok(if negative {
+            0i128.checked_sub_unsigned(number)
+        } else {
+            i128::try_from(number).ok()
+        }
+        .ok_or(number))
+    }
+
+    /// parses `1`, `-0.5`, `1e-7`, `inf` or `nan` with the precision of the type it is assigned to
+    fn parse_float<T: FromStr + Neg<Output = T>>(&mut self) -> Result<T, ParseError> {
+        let negative = self.accept("-");
+        let literal = match self.next() {
+            Token::Number(number) => number.to_string(),
+            Token::Float(literal) => literal,
+            Token::Ident(ident) if ident == "inf" || ident == "nan" => ident,
+            other => return Err(self.error_before(&format!("expected a number, found {}", other))),
+        };
+        let value: T = literal
+            .parse()
+            .map_err(|_| self.error_before("invalid number"))?;
+        Ok(if negative { -value } else { value })
+    }
+
+    /// parses the value of a conditional or optional element, `none` or the values of its nested list:
+    /// `[1, "ab"]`
+    fn parse_optional(&mut self, kinds: Option<&[DecodeType]>) -> Result<DecodedData, ParseError> {
+        if self.peek() == &Token::Ident("none".to_owned()) {
+            self.pos += 1;
+            return Ok(DecodedData::Optional(None));
+        }
+        let Some(kinds) = kinds else {
+            return Err(self.error(&format!("expected 'none', found {}", self.peek())));
+        };
+        // constant, reserved and padding elements have no value
+        let kinds: Vec<&DecodeType> = kinds.iter().filter(|kind| has_value(kind)).collect();
+        let values = self.parse_items(|parser, idx| match kinds.get(idx) {
+            Some(kind) => parser.parse_value(kind),
+            None => Err(parser.error("too many values for the nested list")),
+        })?;
+        if values.len() != kinds.len() {
+            return Err(self.error_before("missing values for the nested list"));
+        }
+        Ok(DecodedData::Optional(Some(values)))
+    }
+
+    /// records the byte order suffix of a type, all the suffixes used by a field must agree
+    fn set_order(
+        &self,
+        order: &mut Option<DecodeOrder>,
+        suffix: Option<DecodeOrder>,
+    ) -> Result<(), ParseError> {
+        match (order.as_ref(), suffix) {
+            (_, None) => Ok(()),
+            (None, Some(suffix)) => {
+                *order = Some(suffix);
+                Ok(())
+            }
+            (Some(DecodeOrder::Big), Some(DecodeOrder::Big))
+            | (Some(DecodeOrder::Little), Some(DecodeOrder::Little)) => Ok(()),
+            _ => Err(self.error_before("conflicting byte orders in the same field")),
+        }
+    }
+}
+
+enum Length {
+    Fixed(usize),
+    Ref(FieldRef),
+}
+
+/// returns true for the types that decode to a value
+fn has_value(kind: &DecodeType) -> bool {
+    !matches!(
+        kind,
+        DecodeType::Const(_) | DecodeType::Reserved(_) | DecodeType::Padding(_)
+    )
+}
+
+/// returns true for the types that can have a default value, checksums are always computed
+fn has_default(kind: &DecodeType) -> bool {
+    has_value(kind) && !matches!(kind, DecodeType::Checksum(..))
+}
+
+fn is_integer(kind: &DecodeType) -> bool {
+    matches!(
+        kind,
+        DecodeType::Int8
+            | DecodeType::Int16
+            | DecodeType::Int32
+            | DecodeType::Int64
+            | DecodeType::Int128
+            | DecodeType::Uint8
+            | DecodeType::Uint16
+            | DecodeType::Uint32
+            | DecodeType::Uint64
+            | DecodeType::Uint128
+    )
+}
+
+/// splits an optional `be`/`le` suffix from a type name
+fn split_order(name: &str) -> (&str, Option<DecodeOrder>) {
+    if let Some(base) = name.strip_suffix("be") {
+        (base, Some(DecodeOrder::Big))
+    } else if let Some(base) = name.strip_suffix("le") {
+        (base, Some(DecodeOrder::Little))
+    } else {
+        (name, None)
+    }
+}
+
+fn number_type(name: &str) -> Option<(DecodeType, Option<DecodeOrder>)> {
+    let (base, order) = split_order(name);
+    let kind = match base {
+        "i8" => DecodeType::Int8,
+        "i16" => DecodeType::Int16,
+        "i32" => DecodeType::Int32,
+        "i64" => DecodeType::Int64,
+        "i128" => DecodeType::Int128,
+        "u8" => DecodeType::Uint8,
+        "u16" => DecodeType::Uint16,
+        "u32" => DecodeType::Uint32,
+        "u64" => DecodeType::Uint64,
+        "u128" => DecodeType::Uint128,
+        "f32" => DecodeType::Float32,
+        "f64" => DecodeType::Float64,
+        _ => return None,
+    };
+    Some((kind, order))
+}
+
+fn checksum_kind(name: &str) -> Option<(ChecksumKind, Option<DecodeOrder>)> {
+    let (base, order) = split_order(name);
+    let kind = match base {
+        "crc8" => ChecksumKind::Crc8,
+        "crc16" => ChecksumKind::Crc16Ccitt,
+        "crc32" => ChecksumKind::Crc32,
+        "crc32c" => ChecksumKind::Crc32c,
+        "adler32" => ChecksumKind::Adler32,
+        "sum8" => ChecksumKind::Sum8,
+        "xor8" => ChecksumKind::Xor8,
+        _ => return None,
+    };
+    Some((kind, order))
+}
+
+fn order_suffix(order: Option<&DecodeOrder>) -> &'static str {
+    match order {
+        None => "",
+        Some(DecodeOrder::Big) => "be",
+        Some(DecodeOrder::Little) => "le",
+    }
+}
+
+fn print_ref(text: &mut String, field: &FieldRef) {
+    match field {
+        FieldRef::Index(index) => text.push_str(&format!("#{}", index)),
+        FieldRef::Name(name) => text.push_str(name),
+    }
+}
+
+fn print_length(text: &mut String, field: &FieldRef) {
+    text.push('[');
+    print_ref(text, field);
+    text.push(']');
+}
+
+/// prints a quoted string using the escapes understood by the parser
+fn print_string(text: &mut String, content: &str) {
+    text.push('"');
+    for c in content.chars() {
+        match c {
+            '"' => text.push_str("\\\""),
+            '\\' => text.push_str("\\\\"),
+            '\n' => text.push_str("\\n"),
+            '\r' => text.push_str("\\r"),
+            '\t' => text.push_str("\\t"),
+            c if c.is_ascii_control() => text.push_str(&format!("\\x{:02x}", c as u8)),
+            c => text.push(c),
+        }
+    }
+    text.push('"');
+}
+
+/// prints bytes as a byte string when they are printable, as a list of hexadecimal bytes otherwise
+fn print_bytes(text: &mut String, content: &[u8]) {
+    if content.iter().all(|byte| (0x20..0x7f).contains(byte)) {
+        text.push_str("b\"");
+        for byte in content {
+            if *byte == b'"' || *byte == b'\\' {
+                text.push('\\');
+            }
+            text.push(*byte as char);
+        }
+        text.push('"');
+    } else {
+        let bytes: Vec<String> = content
+            .iter()
+            .map(|byte| format!("0x{:02x}", byte))
+            .collect();
+        text.push_str(&format!("[{}]", bytes.join(", ")));
+    }
+}
+
+fn print_value(text: &mut String, value: &DecodedData) {
+    match value {
+        DecodedData::Int8(value) => text.push_str(&value.to_string()),
+        DecodedData::Int16(value) => text.push_str(&value.to_string()),
+        DecodedData::Int32(value) => text.push_str(&value.to_string()),
+        DecodedData::Int64(value) => text.push_str(&value.to_string()),
+        DecodedData::Int128(value) => text.push_str(&value.to_string()),
+        DecodedData::Uint8(value) => text.push_str(&value.to_string()),
+        DecodedData::Uint16(value) => text.push_str(&value.to_string()),
+        DecodedData::Uint32(value) => text.push_str(&value.to_string()),
+        DecodedData::Uint64(value) => text.push_str(&value.to_string()),
+        DecodedData::Uint128(value) => text.push_str(&value.to_string()),
+        DecodedData::Float32(value) => print_float(text, *value as f64, format!("{:?}", value)),
+        DecodedData::Float64(value) => print_float(text, *value, format!("{:?}", value)),
+        DecodedData::Bool(value) => text.push_str(&value.to_string()),
+        DecodedData::Str(value) => print_string(text, value),
+        DecodedData::Bytes(value) => print_bytes(text, value),
+        DecodedData::BitGroup(values, _) => {
+            let values: Vec<String> = values
+                .iter()
+                .map(|value| match value {
+                    BitValue::Unsigned(_, value) => value.to_string(),
+                    BitValue::Signed(_, value) => value.to_string(),
+                })
+                .collect();
+            text.push_str(&format!("[{}]", values.join(", ")));
+        }
+        DecodedData::Array(items) | DecodedData::Optional(Some(items)) => {
+            text.push('[');
+            for (idx, item) in items.iter().enumerate() {
+                if idx > 0 {
+                    text.push_str(", ");
+                }
+                print_value(text, item);
+            }
+            text.push(']');
+        }
+        DecodedData::Optional(None) => text.push_str("none"),
+    }
+}
+
+/// prints a float, `literal` is its shortest representation which parses back to the same value
+fn print_float(text: &mut String, value: f64, literal: String) {
+    if value.is_nan() {
+        text.push_str("nan");
+    } else if value.is_infinite() {
+        text.push_str(if value < 0.0 { "-inf" } else { "inf" });
+    } else {
+        text.push_str(&literal);
+    }
+}
+
+fn print_list(text: &mut String, kinds: &[DecodeType], order: Option<&DecodeOrder>) {
+    text.push_str(" {");
+    for kind in kinds {
+        text.push(' ');
+        print_type(text, kind, order, true);
+        text.push(';');
+    }
+    text.push_str(" }");
+}
+
+/// prints a type, `order` is the byte order of the field and is attached to every integer and checksum. `nested`
+/// tells whether the type is part of a nested list, where names are not resolved by the parser.
+fn print_type(text: &mut String, kind: &DecodeType, order: Option<&DecodeOrder>, nested: bool) {
+    let suffix = order_suffix(order);
+    match kind {
+        DecodeType::Int8 => text.push_str(&format!("i8{}", suffix)),
+        DecodeType::Int16 => text.push_str(&format!("i16{}", suffix)),
+        DecodeType::Int32 => text.push_str(&format!("i32{}", suffix)),
+        DecodeType::Int64 => text.push_str(&format!("i64{}", suffix)),
+        DecodeType::Int128 => text.push_str(&format!("i128{}", suffix)),
+        DecodeType::Uint8 => text.push_str(&format!("u8{}", suffix)),
+        DecodeType::Uint16 => text.push_str(&format!("u16{}", suffix)),
+        DecodeType::Uint32 => text.push_str(&format!("u32{}", suffix)),
+        DecodeType::Uint64 => text.push_str(&format!("u64{}", suffix)),
+        DecodeType::Uint128 => text.push_str(&format!("u128{}", suffix)),
+        DecodeType::Float32 => text.push_str(&format!("f32{}", suffix)),
+        DecodeType::Float64 => text.push_str(&format!("f64{}", suffix)),
+        DecodeType::Bool => text.push_str("bool"),
+        DecodeType::Str(size) => text.push_str(&format!("str[{}]", size)),
+        DecodeType::Bytes(size) => text.push_str(&format!("bytes[{}]", size)),
+        DecodeType::StrRef(field) => {
+            text.push_str("str");
+            print_length(text, field);
+        }
+        DecodeType::BytesRef(field) => {
+            text.push_str("bytes");
+            print_length(text, field);
+        }
+        DecodeType::BitGroup(fields, bit_order) => {
+            text.push_str(match bit_order {
+                BitOrder::Msb => "bits(msb) {",
+                BitOrder::Lsb => "bits(lsb) {",
+            });
+            for field in fields {
+                match field {
+                    BitField::Unsigned(width) => text.push_str(&format!(" u{};", width)),
+                    BitField::Signed(width) => text.push_str(&format!(" i{};", width)),
+                }
+            }
+            text.push_str(" }");
+        }
+        DecodeType::Checksum(kind, range) => {
+            let name = match kind {
+                ChecksumKind::Crc8 => "crc8",
+                ChecksumKind::Crc16Ccitt => "crc16",
+                ChecksumKind::Crc32 => "crc32",
+                ChecksumKind::Crc32c => "crc32c",
+                ChecksumKind::Adler32 => "adler32",
+                ChecksumKind::Sum8 => "sum8",
+                ChecksumKind::Xor8 => "xor8",
+            };
+            text.push_str(&format!(
+                "checksum {}{}({}..{})",
+                name, suffix, range.start, range.end
+            ));
+        }
+        DecodeType::Const(content) => {
+            text.push_str("const ");
+            print_bytes(text, content);
+        }
+        DecodeType::Reserved(size) => text.push_str(&format!("reserved[{}]", size)),
+        DecodeType::Padding(size) => text.push_str(&format!("padding[{}]", size)),
+        DecodeType::Array(item, count) => {
+            print_type(text, item, order, nested);
+            text.push_str(&format!("[{}]", count));
+        }
+        DecodeType::ArrayRef(item, field) => {
+            print_type(text, item, order, nested);
+            print_length(text, field);
+        }
+        DecodeType::Conditional(field, predicate, kinds) => {
+            text.push_str("if ");
+            print_ref(text, field);
+            // the parser cannot type the values compared with unresolved names
+            let typed = nested && matches!(field, FieldRef::Name(_));
+            let print_compared = |text: &mut String, value: &DecodedData| {
+                if typed {
+                    text.push('(');
+                    print_type(text, &value.decode_type(), None, nested);
+                    text.push_str(") ");
+                }
+                print_value(text, value);
+            };
+            match predicate {
+                Predicate::Equals(value) => {
+                    text.push_str(" == ");
+                    print_compared(text, value);
+                }
+                Predicate::NotEquals(value) => {
+                    text.push_str(" != ");
+                    print_compared(text, value);
+                }
+                Predicate::BitsSet(mask) => text.push_str(&format!(" & {:#x}", mask)),
+                Predicate::BitsClear(mask) => text.push_str(&format!(" !& {:#x}", mask)),
+                Predicate::Custom(_) => text.push_str(" custom"),
+            }
+            print_list(text, kinds, order);
+        }
+        DecodeType::Optional(None) => text.push_str("optional none"),
+        DecodeType::Optional(Some(kinds)) => {
+            text.push_str("optional");
+            print_list(text, kinds, order);
+        }
+    }
+}
@@ -0,0 +1,410 @@
+//! `tlv` implements the Type-Length-Value mode, where every element is written with a tag telling its type and
+//! size instead of relying on its position. A decoder does not need to know the layout in advance and skips the
+//! entries whose type it does not know, so that new elements can be added without breaking older decoders.
+//!
+//! Every entry starts with a tag byte holding the type in its high five bits and the wire class in its low three bits.
+//! The wire class tells how many bytes the value uses, so that the entries of unknown types can be skipped:
+//!
+//! - `0` to `4`: 1, 2, 4, 8 or 16 bytes
+//! - `5`: an unsigned LEB128 varint length followed by that many bytes
+//!
+//! The known types are:
+//!
+//! - `1` signed integer and `2` unsigned integer, of the width given by the wire class
+//! - `3` floating point number, wire class `2` or `3`
+//! - `4` boolean, wire class `0`
+//! - `5` string and `6` byte-array, wire class `5`
+//! - `7` array, wire class `5`, the items are entries themselves
+//! - `8` optional or conditional element, wire class `5`, `0` when absent or `1` followed by the nested entries
+//! - `9` bit-group, wire class `5`, the bit order (`0` msb, `1` lsb), the number of fields, one byte per field
+//!   holding its width and `0x80` when signed, then the packed bits
+//!
+//! References are resolved while encoding: strings, byte-arrays and arrays carry their own length and the integer
+//! elements they refer to hold the back-filled length. Constant and reserved elements only shape the positional
+//! layout, they are left out like they are left out of the decoded values. Checksums cannot be written in this mode.
+
+use crate::bitfield::{self, BitField, BitOrder, BitValue};
+use crate::decoder::{self, DecodeError, DecodeOrder, DecodeType, DecodedData, FieldRef};
+use crate::encoder::{self, EncodeError, EncodeOrder, EncodeType};
+use crate::framing::{decode_varint, encode_varint};
+
+const TYPE_INT: u8 = 1;
+const TYPE_UINT: u8 = 2;
+const TYPE_FLOAT: u8 = 3;
+const TYPE_BOOL: u8 = 4;
+const TYPE_STR: u8 = 5;
+const TYPE_BYTES: u8 = 6;
+const TYPE_ARRAY: u8 = 7;
+const TYPE_OPTIONAL: u8 = 8;
+const TYPE_BIT_GROUP: u8 = 9;
+
+/// wire class of values prefixed by their length
+const CLASS_LENGTH: u8 = 5;
+
+/// deepest nesting of arrays and optional elements accepted by the decoder
+const MAX_DEPTH: usize = 32;
+
+#[derive(Debug, Clone, PartialEq)]
+/// `TlvEntry` is an entry decoded by `decode_tlv`, either a value of a known type or the raw content of an entry
+/// whose type is unknown.
+pub enum TlvEntry {
+    /// Value holds the decoded value of an entry of a known type
+    Value(DecodedData),
+    /// Unknown holds the tag and the content of an entry of an unknown type, without its length prefix
+    Unknown { tag: u8, content: Vec<u8> },
+}
+
+impl TlvEntry {
+    /// returns the decoded value of a known entry
+    pub fn value(&self) -> Option<&DecodedData> {
+        match self {
+            TlvEntry::Value(value) => Some(value),
+            TlvEntry::Unknown { .. } => None,
+        }
+    }
+
+    /// returns the type of the entry, the high five bits of its tag
+    pub fn type_id(&self) -> u8 {
+        match self {
+            TlvEntry::Value(value) => match value {
+                DecodedData::Int8(_)
+                | DecodedData::Int16(_)
+                | DecodedData::Int32(_)
+                | DecodedData::Int64(_)
+                | DecodedData::Int128(_) => TYPE_INT,
+                DecodedData::Uint8(_)
+                | DecodedData::Uint16(_)
+                | DecodedData::Uint32(_)
+                | DecodedData::Uint64(_)
+                | DecodedData::Uint128(_) => TYPE_UINT,
+                DecodedData::Float32(_) | DecodedData::Float64(_) => TYPE_FLOAT,
+                DecodedData::Bool(_) => TYPE_BOOL,
+                DecodedData::Str(_) => TYPE_STR,
+                DecodedData::Bytes(_) => TYPE_BYTES,
+                DecodedData::Array(_) => TYPE_ARRAY,
+                DecodedData::Optional(_) => TYPE_OPTIONAL,
+                DecodedData::BitGroup(..) => TYPE_BIT_GROUP,
+            },
+            TlvEntry::Unknown { tag, .. } => tag >> 3,
+        }
+    }
+}
+
+/// returns the size of the values of a fixed wire class
+fn class_size(class: u8) -> Option<usize> {
+    match class {
+        0..=4 => Some(1 << class),
+        _ => None,
+    }
+}
+
+/// `encode_tlv` encodes the values as a list of Type-Length-Value entries, integers and floating point numbers use
+/// the given byte order.
+///
+/// # Examples
+/// ```rust
+/// use packed_encoder::decoder::{DecodeOrder, DecodedData, FieldRef};
+/// use packed_encoder::encoder::{EncodeOrder, EncodeType};
+/// use packed_encoder::tlv::{self, TlvEntry};
+///
+/// let values = [
+///     EncodeType::Uint8(0),
+///     EncodeType::StrRef("hi".to_owned(), FieldRef::Index(0)),
+/// ];
+/// let encoded = tlv::encode_tlv(&values, EncodeOrder::Big).unwrap();
+/// assert_eq!(encoded, vec![0x10, 2, 0x2d, 2, b'h', b'i']);
+///
+/// // an entry of a type added later is skipped by this decoder
+/// let mut newer = encoded.clone();
+/// newer.extend_from_slice(&[0xfd, 0x01, 0xff]);
+/// let entries = tlv::decode_tlv(&newer, DecodeOrder::Big).unwrap();
+/// assert_eq!(entries[1], TlvEntry::Value(DecodedData::Str("hi".to_owned())));
+/// assert_eq!(entries[2], TlvEntry::Unknown { tag: 0xfd, content: vec![0xff] });
+/// ```
+pub fn encode_tlv(
+    values: &[EncodeType],
+    encode_order: EncodeOrder,
+) -> Result<Vec<u8>, EncodeError> {
+    let mut buffer = vec![];
+    write_list(values, &mut buffer, &encode_order)?;
+    Ok(buffer)
+}
+
+/// appends the entries of a list of elements, references inside the list refer to the elements of the same list
+fn write_list(
+    values: &[EncodeType],
+    buffer: &mut Vec<u8>,
+    encode_order: &EncodeOrder,
+) -> Result<(), EncodeError> {
+    for value in resolve(values)? {
+        write_entry(&value, buffer, encode_order)?;
+    }
+    Ok(())
+}
+
+/// returns the elements with the lengths back-filled into the integer elements referred by strings, byte-arrays
+/// and arrays, and with conditional elements replaced by optional elements
+fn resolve(values: &[EncodeType]) -> Result<Vec<EncodeType>, EncodeError> {
+    let mut resolved = values.to_vec();
+    for (idx, value) in values.iter().enumerate() {
+        let target = |field: &FieldRef| match field {
+            FieldRef::Index(target) if *target < idx => Ok(*target),
+            _ => Err(EncodeError::LengthField(field.clone())),
+        };
+        match value {
+            EncodeType::StrRef(content, field) => {
+                let target = target(field)?;
+                resolved[target] = encoder::with_length(&resolved[target], content.len(), field)?;
+                resolved[idx] = EncodeType::Str(content.clone());
+            }
+            EncodeType::BytesRef(content, field) => {
+                let target = target(field)?;
+                resolved[target] = encoder::with_length(&resolved[target], content.len(), field)?;
+                resolved[idx] = EncodeType::Bytes(content.clone());
+            }
+            EncodeType::ArrayRef(items, field) => {
+                let target = target(field)?;
+                resolved[target] = encoder::with_length(&resolved[target], items.len(), field)?;
+                resolved[idx] = EncodeType::Array(items.clone());
+            }
+            EncodeType::Conditional(field, predicate, elements) => {
+                let value = match field {
+                    FieldRef::Index(target) if *target < idx => {
+                        DecodedData::try_from(&resolved[*target]).ok()
+                    }
+                    _ => None,
+                }
+                .ok_or_else(|| EncodeError::ConditionField(field.clone()))?;
+                resolved[idx] =
+                    EncodeType::Optional(predicate.test(&value).then(|| elements.clone()));
+            }
+            _ => {}
+        }
+    }
+    Ok(resolved)
+}
+
+/// appends a single entry, the references of the element must have been resolved
+fn write_entry(
+    value: &EncodeType,
+    buffer: &mut Vec<u8>,
+    encode_order: &EncodeOrder,
+) -> Result<(), EncodeError> {
+    let type_id = match value {
+        EncodeType::Int8(_)
+        | EncodeType::Int16(_)
+        | EncodeType::Int32(_)
+        | EncodeType::Int64(_)
+        | EncodeType::Int128(_) => TYPE_INT,
+        EncodeType::Uint8(_)
+        | EncodeType::Uint16(_)
+        | EncodeType::Uint32(_)
+        | EncodeType::Uint64(_)
+        | EncodeType::Uint128(_) => TYPE_UINT,
+        EncodeType::Float32(_) | EncodeType::Float64(_) => TYPE_FLOAT,
+        EncodeType::Bool(_) => TYPE_BOOL,
+        EncodeType::Str(_) => TYPE_STR,
+        EncodeType::Bytes(_) => TYPE_BYTES,
+        EncodeType::Array(_) => TYPE_ARRAY,
+        EncodeType::Optional(_) => TYPE_OPTIONAL,
+        EncodeType::BitGroup(..) => TYPE_BIT_GROUP,
+        EncodeType::Const(_) | EncodeType::Reserved(_) => return Ok(()),
+        EncodeType::Checksum(kind, range) => {
+            return Err(EncodeError::Checksum(*kind, range.clone()))
+        }
+        // references and conditions are resolved by `resolve`
+        EncodeType::StrRef(_, field)
+        | EncodeType::BytesRef(_, field)
+        | EncodeType::ArrayRef(_, field) => return Err(EncodeError::LengthField(field.clone())),
+        EncodeType::Conditional(field, ..) => {
+            return Err(EncodeError::ConditionField(field.clone()))
+        }
+    };
+
+    let content = match value {
+        EncodeType::Str(content) => content.as_bytes().to_vec(),
+        EncodeType::Bytes(content) => content.clone(),
+        EncodeType::Array(items) => {
+            let mut content = vec![];
+            for item in items {
+                write_entry(item, &mut content, encode_order)?;
+            }
+            content
+        }
+        EncodeType::Optional(None) => vec![0],
+        EncodeType::Optional(Some(values)) => {
+            let mut content = vec![1];
+            write_list(values, &mut content, encode_order)?;
+            content
+        }
+        EncodeType::BitGroup(fields, order) => {
+            let mut content = vec![matches!(order, BitOrder::Lsb) as u8];
+            encode_varint(fields.len() as u64, &mut content);
+            for field in fields {
+                match field {
+                    BitValue::Unsigned(width, _) => content.push(*width & 0x7f),
+                    BitValue::Signed(width, _) => content.push(*width & 0x7f | 0x80),
+                }
+            }
+            let packed = bitfield::pack_bits(fields, *order).map_err(EncodeError::BitField)?;
+            content.extend_from_slice(&packed);
+            content
+        }
+        // fixed-size values are written like in `encode_packed` and use the wire class of their size
+        _ => {
+            let content =
+                encoder::encode_packed(std::slice::from_ref(value), encode_order.clone())?;
+            let class = content.len().trailing_zeros() as u8;
+            buffer.push(type_id << 3 | class);
+            buffer.extend_from_slice(&content);
+            return Ok(());
+        }
+    };
+    buffer.push(type_id << 3 | CLASS_LENGTH);
+    encode_varint(content.len() as u64, buffer);
+    buffer.extend_from_slice(&content);
+    Ok(())
+}
+
+/// `decode_tlv` decodes a list of Type-Length-Value entries written by `encode_tlv`. The entries of unknown types
+/// are returned as `TlvEntry::Unknown` at the top level and skipped inside arrays and optional elements.
+/// `DecodeError::InvalidData` holds the index of the offending top-level entry.
+pub fn decode_tlv(buffer: &[u8], decode_order: DecodeOrder) -> Result<Vec<TlvEntry>, DecodeError> {
+    let mut entries = vec![];
+    let mut offset = 0;
+    while offset < buffer.len() {
+        let idx = entries.len();
+        let (tag, content, size) = read_entry(&buffer[offset..], idx)?;
+        entries.push(match read_value(tag, content, &decode_order, idx, 0)? {
+            Some(value) => TlvEntry::Value(value),
+            None => TlvEntry::Unknown {
+                tag,
+                content: content.to_vec(),
+            },
+        });
+        offset += size;
+    }
+    Ok(entries)
+}
+
+/// splits the entry at the start of the buffer into its tag and its content, and returns them with the size of the
+/// entry
+fn read_entry(buffer: &[u8], idx: usize) -> Result<(u8, &[u8], usize), DecodeError> {
+    let tag = *buffer.first().ok_or(DecodeError::IndexOutOfBounds)?;
+    let (start, size) = match class_size(tag & 0x07) {
+        Some(size) => (1, size),
+        None if tag & 0x07 == CLASS_LENGTH => match decode_varint(&buffer[1..]) {
+            Ok(Some((size, used))) => (
+                1 + used,
+                usize::try_from(size).map_err(|_| DecodeError::IndexOutOfBounds)?,
+            ),
+            Ok(None) => return Err(DecodeError::IndexOutOfBounds),
+            Err(_) => return Err(DecodeError::InvalidData(idx)),
+        },
+        // entries of unknown wire classes cannot be skipped
+        None => return Err(DecodeError::InvalidData(idx)),
+    };
+    let end = start
+        .checked_add(size)
+        .ok_or(DecodeError::IndexOutOfBounds)?;
+    let content = buffer
+        .get(start..end)
+        .ok_or(DecodeError::IndexOutOfBounds)?;
+    Ok((tag, content, end))
+}
+
+/// decodes the content of an entry, `None` is returned for unknown types
+fn read_value(
+    tag: u8,
+    content: &[u8],
+    decode_order: &DecodeOrder,
+    idx: usize,
+    depth: usize,
+) -> Result<Option<DecodedData>, DecodeError> {
+    let invalid = DecodeError::InvalidData(idx);
+    let fixed = |kind: DecodeType| {
+        decoder::decode_packed(&[kind], content, decode_order.clone())
+            .map(|mut values| values.pop())
+            .map_err(|_| DecodeError::InvalidData(idx))
+    };
+    let class = tag & 0x07;
+    match (tag >> 3, class) {
+        (TYPE_INT, 0) => fixed(DecodeType::Int8),
+        (TYPE_INT, 1) => fixed(DecodeType::Int16),
+        (TYPE_INT, 2) => fixed(DecodeType::Int32),
+        (TYPE_INT, 3) => fixed(DecodeType::Int64),
+        (TYPE_INT, 4) => fixed(DecodeType::Int128),
+        (TYPE_UINT, 0) => fixed(DecodeType::Uint8),
+        (TYPE_UINT, 1) => fixed(DecodeType::Uint16),
+        (TYPE_UINT, 2) => fixed(DecodeType::Uint32),
+        (TYPE_UINT, 3) => fixed(DecodeType::Uint64),
+        (TYPE_UINT, 4) => fixed(DecodeType::Uint128),
+        (TYPE_FLOAT, 2) => fixed(DecodeType::Float32),
+        (TYPE_FLOAT, 3) => fixed(DecodeType::Float64),
+        (TYPE_BOOL, 0) => fixed(DecodeType::Bool),
+        (TYPE_STR, CLASS_LENGTH) => fixed(DecodeType::Str(content.len())),
+        (TYPE_BYTES, CLASS_LENGTH) => Ok(Some(DecodedData::Bytes(content.to_vec()))),
+        (TYPE_ARRAY, CLASS_LENGTH) if depth < MAX_DEPTH => {
+            read_nested(content, decode_order, idx, depth)
+                .map(|items| Some(DecodedData::Array(items)))
+        }
+        (TYPE_OPTIONAL, CLASS_LENGTH) if depth < MAX_DEPTH => match content.split_first() {
+            Some((0, [])) => Ok(Some(DecodedData::Optional(None))),
+            Some((1, nested)) => read_nested(nested, decode_order, idx, depth)
+                .map(|values| Some(DecodedData::Optional(Some(values)))),
+            _ => Err(invalid),
+        },
+        (TYPE_BIT_GROUP, CLASS_LENGTH) => {
+            let order = match content.first() {
+                Some(0) => BitOrder::Msb,
+                Some(1) => BitOrder::Lsb,
+                _ => return Err(invalid),
+            };
+            let (count, used) = match decode_varint(&content[1..]) {
+                Ok(Some((count, used))) => (count as usize, used),
+                _ => return Err(invalid),
+            };
+            let widths = content
+                .get(1 + used..)
+                .and_then(|rest| rest.get(..count))
+                .ok_or(DecodeError::InvalidData(idx))?;
+            let fields: Vec<BitField> = widths
+                .iter()
+                .map(|byte| match byte & 0x80 {
+                    0 => BitField::Unsigned(*byte),
+                    _ => BitField::Signed(byte & 0x7f),
+                })
+                .collect();
+            let packed = &content[1 + used + count..];
+            let bits = fields.iter().map(|field| field.width() as usize).sum();
+            if packed.len() != bitfield::group_size(bits) {
+                return Err(invalid);
+            }
+            bitfield::unpack_bits(&fields, packed, order)
+                .map(|values| Some(DecodedData::BitGroup(values, order)))
+                .ok_or(invalid)
+        }
+        (TYPE_ARRAY | TYPE_OPTIONAL, CLASS_LENGTH) => Err(invalid),
+        _ => Ok(None),
+    }
+}
+
+/// decodes the entries nested in an array or an optional element, the entries of unknown types are skipped
+fn read_nested(
+    mut content: &[u8],
+    decode_order: &DecodeOrder,
+    idx: usize,
+    depth: usize,
+) -> Result<Vec<DecodedData>, DecodeError> {
+    let mut values = vec![];
+    while !content.is_empty() {
+        // the nested entries are bounded by the length of the outer entry
+        let (tag, nested, size) =
+            read_entry(content, idx).map_err(|_| DecodeError::InvalidData(idx))?;
+        if let Some(value) = read_value(tag, nested, decode_order, idx, depth + 1)? {
+            values.push(value);
+        }
+        content = &content[size..];
+    }
+    Ok(values)
+}
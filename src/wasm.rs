@@ -0,0 +1,220 @@
+//! `wasm` exposes `encode_packed` and `decode_packed` to JavaScript through `wasm-bindgen`, it is enabled by the
+//! `wasm` feature. Values are passed as `{type, value}` objects where `type` is one of `u8`, `u16`, `u32`, `u64`,
+//! `u128`, `i8`, `i16`, `i32`, `i64`, `i128`, `f32`, `f64`, `bool`, `str` or `bytes`, the names used by the schema
+//! text language. 64- and 128-bit integers are `BigInt`s, bytes are `Uint8Array`s and the byte order is `"big"` or
+//! `"little"`.
+//!
+//! ```js
+//! import { encodePacked, decodePacked } from "packed-encoder";
+//!
+//! const buffer = encodePacked([{ type: "u8", value: 1 }, { type: "u64", value: 2n }], "little");
+//! const values = decodePacked([{ type: "u8" }, { type: "u64" }], buffer, "little");
+//! ```
+
+use crate::decoder::{self, DecodeOrder, DecodeType, DecodedData};
+use crate::encoder::{self, EncodeOrder, EncodeType};
+use js_sys::{Array, BigInt, Object, Reflect, Uint8Array};
+use wasm_bindgen::prelude::*;
+
+/// `encodePacked` encodes an array of `{type, value}` objects and returns the encoded bytes.
+#[wasm_bindgen(js_name = encodePacked)]
+pub fn encode_packed(values: Array, order: &str) -> Result<Uint8Array, JsError> {
+    let order = EncodeOrder::from(byte_order(order)?);
+    let to_encode = values
+        .iter()
+        .enumerate()
+        .map(|(index, entry)| {
+            let kind = property(&entry, "type")?
+                .as_string()
+                .ok_or_else(|| JsError::new(&format!("value {}: type is not a string", index)))?;
+            let value = property(&entry, "value")?;
+            encode_value(&kind, &value)
+                .map_err(|message| JsError::new(&format!("value {}: {}", index, message)))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let encoded = encoder::encode_packed(&to_encode, order)
+        .map_err(|error| JsError::new(&format!("encoding failed: {:?}", error)))?;
+    Ok(Uint8Array::from(encoded.as_slice()))
+}
+
+/// `decodePacked` decodes a buffer laid out as the given `{type, size}` objects, `size` is only needed by `str` and
+/// `bytes`. It returns an array of `{type, value}` objects.
+#[wasm_bindgen(js_name = decodePacked)]
+pub fn decode_packed(types: Array, buffer: &[u8], order: &str) -> Result<Array, JsError> {
+    let order = byte_order(order)?;
+    let expected = types
+        .iter()
+        .enumerate()
+        .map(|(index, entry)| {
+            let kind = property(&entry, "type")?
+                .as_string()
+                .ok_or_else(|| JsError::new(&format!("type {}: type is not a string", index)))?;
+            let size = property(&entry, "size")?.as_f64();
+            decode_type(&kind, size)
+                .map_err(|message| JsError::new(&format!("type {}: {}", index, message)))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let decoded = decoder::decode_packed(&expected, buffer, order)
+        .map_err(|error| JsError::new(&format!("decoding failed: {:?}", error)))?;
+    let values = Array::new();
+    for value in decoded {
+        let (kind, value) = decoded_value(value)?;
+        let entry = Object::new();
+        Reflect::set(&entry, &"type".into(), &kind.into()).map_err(js_error)?;
+        Reflect::set(&entry, &"value".into(), &value).map_err(js_error)?;
+        values.push(&entry);
+    }
+    Ok(values)
+}
+
+fn byte_order(order: &str) -> Result<DecodeOrder, JsError> {
+    match order {
+        "big" => Ok(DecodeOrder::Big),
+        "little" => Ok(DecodeOrder::Little),
+        _ => Err(JsError::new(&format!(
+            "byte order must be \"big\" or \"little\", found \"{}\"",
+            order
+        ))),
+    }
+}
+
+fn property(entry: &JsValue, name: &str) -> Result<JsValue, JsError> {
+    Reflect::get(entry, &name.into()).map_err(js_error)
+}
+
+fn js_error(value: JsValue) -> JsError {
+    JsError::new(&value.as_string().unwrap_or_else(|| format!("{:?}", value)))
+}
+
+/// returns the integer held by a `BigInt` or by a `Number` without fraction, as `i128` or as `u128` when it is
+/// positive and too large for `i128`
+fn integer(value: &JsValue) -> Option<Result<i128, u128>> {
+    if value.is_bigint() {
+        let value = BigInt::from(value.clone());
+        i128::try_from(value.clone())
+            .map(Ok)
+            .or_else(|_| u128::try_from(value).map(Err))
+            .ok()
+    } else {
+        let value = value.as_f64()?;
+        // larger numbers lost their precision, they must be passed as BigInt
+        (value.fract() == 0.0 && value.abs() <= 9007199254740991.0).then_some(Ok(value as i128))
+    }
+}
+
+fn encode_value(kind: &str, value: &JsValue) -> Result<EncodeType, String> {
+    macro_rules! int {
+        ($type:ty, $variant:ident) => {
+            match integer(value) {
+                Some(Ok(value)) => <$type>::try_from(value).ok(),
+                Some(Err(value)) => <$type>::try_from(value).ok(),
+                None => None,
+            }
+            .map(EncodeType::$variant)
+            .ok_or_else(|| format!("expected an integer that fits in {}", kind))
+        };
+    }
+
+    match kind {
+        "u8" => int!(u8, Uint8),
+        "u16" => int!(u16, Uint16),
+        "u32" => int!(u32, Uint32),
+        "u64" => int!(u64, Uint64),
+        "u128" => int!(u128, Uint128),
+        "i8" => int!(i8, Int8),
+        "i16" => int!(i16, Int16),
+        "i32" => int!(i32, Int32),
+        "i64" => int!(i64, Int64),
+        "i128" => int!(i128, Int128),
+        "f32" => value
+            .as_f64()
+            .map(|value| EncodeType::Float32(value as f32))
+            .ok_or_else(|| "expected a number".to_owned()),
+        "f64" => value
+            .as_f64()
+            .map(EncodeType::Float64)
+            .ok_or_else(|| "expected a number".to_owned()),
+        "bool" => value
+            .as_bool()
+            .map(EncodeType::Bool)
+            .ok_or_else(|| "expected a boolean".to_owned()),
+        "str" => value
+            .as_string()
+            .map(EncodeType::Str)
+            .ok_or_else(|| "expected a string".to_owned()),
+        "bytes" => {
+            // accepts a Uint8Array as well as an array of numbers, other objects would become empty bytes
+            if value.is_instance_of::<Uint8Array>() {
+                Ok(EncodeType::Bytes(Uint8Array::new(value).to_vec()))
+            } else if Array::is_array(value) {
+                Array::from(value)
+                    .iter()
+                    .map(|item| {
+                        item.as_f64()
+                            .filter(|byte| byte.fract() == 0.0 && (0.0..=255.0).contains(byte))
+                            .map(|byte| byte as u8)
+                    })
+                    .collect::<Option<Vec<u8>>>()
+                    .map(EncodeType::Bytes)
+                    .ok_or_else(|| "expected an array of numbers from 0 to 255".to_owned())
+            } else {
+                Err("expected a Uint8Array".to_owned())
+            }
+        }
+        _ => Err(format!("unknown type \"{}\"", kind)),
+    }
+}
+
+fn decode_type(kind: &str, size: Option<f64>) -> Result<DecodeType, String> {
+    let size = || {
+        size.filter(|size| size.fract() == 0.0 && *size >= 0.0)
+            .map(|size| size as usize)
+            .ok_or_else(|| format!("{} needs a size", kind))
+    };
+    Ok(match kind {
+        "u8" => DecodeType::Uint8,
+        "u16" => DecodeType::Uint16,
+        "u32" => DecodeType::Uint32,
+        "u64" => DecodeType::Uint64,
+        "u128" => DecodeType::Uint128,
+        "i8" => DecodeType::Int8,
+        "i16" => DecodeType::Int16,
+        "i32" => DecodeType::Int32,
+        "i64" => DecodeType::Int64,
+        "i128" => DecodeType::Int128,
+        "f32" => DecodeType::Float32,
+        "f64" => DecodeType::Float64,
+        "bool" => DecodeType::Bool,
+        "str" => DecodeType::Str(size()?),
+        "bytes" => DecodeType::Bytes(size()?),
+        _ => return Err(format!("unknown type \"{}\"", kind)),
+    })
+}
+
+fn decoded_value(value: DecodedData) -> Result<(&'static str, JsValue), JsError> {
+    Ok(match value {
+        DecodedData::Uint8(value) => ("u8", value.into()),
+        DecodedData::Uint16(value) => ("u16", value.into()),
+        DecodedData::Uint32(value) => ("u32", value.into()),
+        DecodedData::Uint64(value) => ("u64", BigInt::from(value).into()),
+        DecodedData::Uint128(value) => ("u128", BigInt::from(value).into()),
+        DecodedData::Int8(value) => ("i8", value.into()),
+        DecodedData::Int16(value) => ("i16", value.into()),
+        DecodedData::Int32(value) => ("i32", value.into()),
+        DecodedData::Int64(value) => ("i64", BigInt::from(value).into()),
+        DecodedData::Int128(value) => ("i128", BigInt::from(value).into()),
+        DecodedData::Float32(value) => ("f32", value.into()),
+        DecodedData::Float64(value) => ("f64", value.into()),
+        DecodedData::Bool(value) => ("bool", value.into()),
+        DecodedData::Str(value) => ("str", value.into()),
+        DecodedData::Bytes(value) => ("bytes", Uint8Array::from(value.as_slice()).into()),
+        other => {
+            return Err(JsError::new(&format!(
+                "{} values are not supported",
+                other.type_name()
+            )))
+        }
+    })
+}